@@ -0,0 +1,222 @@
+use crate::math::{Point, Vector};
+use crate::hitable::Transform;
+
+// what a track can interpolate
+pub trait Interpolate: Copy {
+    fn lerp(a: Self, b: Self, t: f32) -> Self;
+
+    // Catmull-Rom spline between `p1` and `p2`, going through every key
+    fn catmull_rom(p0: Self, p1: Self, p2: Self, p3: Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn lerp(a: f32, b: f32, t: f32) -> f32 {
+        a + (b - a) * t
+    }
+
+    fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+        let (t2, t3) = (t * t, t * t * t);
+        0.5 * (2.0 * p1
+            + (p2 - p0) * t
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+    }
+}
+
+impl Interpolate for Vector {
+    fn lerp(a: Vector, b: Vector, t: f32) -> Vector {
+        Vector::new(f32::lerp(a.x, b.x, t), f32::lerp(a.y, b.y, t), f32::lerp(a.z, b.z, t))
+    }
+
+    fn catmull_rom(p0: Vector, p1: Vector, p2: Vector, p3: Vector, t: f32) -> Vector {
+        Vector::new(
+            f32::catmull_rom(p0.x, p1.x, p2.x, p3.x, t),
+            f32::catmull_rom(p0.y, p1.y, p2.y, p3.y, t),
+            f32::catmull_rom(p0.z, p1.z, p2.z, p3.z, t),
+        )
+    }
+}
+
+impl Interpolate for Point {
+    fn lerp(a: Point, b: Point, t: f32) -> Point {
+        let v = Vector::lerp(a.as_vector(), b.as_vector(), t);
+        Point::new(v.x, v.y, v.z)
+    }
+
+    fn catmull_rom(p0: Point, p1: Point, p2: Point, p3: Point, t: f32) -> Point {
+        let v = Vector::catmull_rom(p0.as_vector(), p1.as_vector(), p2.as_vector(), p3.as_vector(), t);
+        Point::new(v.x, v.y, v.z)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Spline,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe<T> {
+    // in frames
+    pub time: f32,
+    pub value: T,
+}
+
+// value changing over time, held before the first key and after the last one
+#[derive(Debug, Clone)]
+pub struct Track<T: Interpolate> {
+    keys: Vec<Keyframe<T>>,
+    interpolation: Interpolation,
+}
+
+impl<T: Interpolate> Track<T> {
+    pub fn new(interpolation: Interpolation) -> Track<T> {
+        Track { keys: Vec::new(), interpolation }
+    }
+
+    pub fn constant(value: T) -> Track<T> {
+        Track::new(Interpolation::Linear).with_key(0.0, value)
+    }
+
+    // keys can be given in any order
+    pub fn with_key(mut self, time: f32, value: T) -> Track<T> {
+        let index = self.keys.iter().position(|key| key.time > time).unwrap_or(self.keys.len());
+        self.keys.insert(index, Keyframe { time, value });
+        self
+    }
+
+    pub fn at(&self, time: f32) -> T {
+        let keys = &self.keys;
+        assert!(!keys.is_empty(), "track without keys");
+        let next = keys.iter().position(|key| key.time > time).unwrap_or(keys.len());
+        if next == 0 {
+            return keys[0].value;
+        }
+        if next == keys.len() {
+            return keys[keys.len() - 1].value;
+        }
+
+        let (a, b) = (&keys[next - 1], &keys[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        match self.interpolation {
+            Interpolation::Linear => T::lerp(a.value, b.value, t),
+            Interpolation::Spline => {
+                // the ends of the spline are repeated
+                let before = keys[next.saturating_sub(2)].value;
+                let after = keys[(next + 1).min(keys.len() - 1)].value;
+                T::catmull_rom(before, a.value, b.value, after, t)
+            },
+        }
+    }
+}
+
+// placement of an object over time, see `Transform::new`
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    pub translation: Track<Vector>,
+    // euler angles in degrees
+    pub rotation: Track<Vector>,
+    pub scale: Track<f32>,
+}
+
+impl AnimatedTransform {
+    pub fn new() -> AnimatedTransform {
+        AnimatedTransform::default()
+    }
+
+    pub fn with_translation(self, translation: Track<Vector>) -> AnimatedTransform {
+        AnimatedTransform { translation, ..self }
+    }
+
+    pub fn with_rotation(self, rotation: Track<Vector>) -> AnimatedTransform {
+        AnimatedTransform { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: Track<f32>) -> AnimatedTransform {
+        AnimatedTransform { scale, ..self }
+    }
+
+    pub fn at(&self, time: f32) -> Transform {
+        Transform::new(self.translation.at(time), self.rotation.at(time), self.scale.at(time))
+    }
+}
+
+impl Default for AnimatedTransform {
+    fn default() -> AnimatedTransform {
+        AnimatedTransform {
+            translation: Track::constant(Vector::zero()),
+            rotation: Track::constant(Vector::zero()),
+            scale: Track::constant(1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn catmull_rom_goes_through_its_inner_points() {
+        assert_close(f32::catmull_rom(5.0, 1.0, 3.0, -2.0, 0.0), 1.0);
+        assert_close(f32::catmull_rom(5.0, 1.0, 3.0, -2.0, 1.0), 3.0);
+        // evenly spaced points lie on a line the spline follows
+        assert_close(f32::catmull_rom(0.0, 1.0, 2.0, 3.0, 0.25), 1.25);
+    }
+
+    #[test]
+    fn linear_track_interpolates_between_keys() {
+        // given out of order
+        let track = Track::new(Interpolation::Linear).with_key(10.0, 4.0).with_key(0.0, 2.0).with_key(20.0, 0.0);
+        assert_close(track.at(0.0), 2.0);
+        assert_close(track.at(5.0), 3.0);
+        assert_close(track.at(10.0), 4.0);
+        assert_close(track.at(12.5), 3.0);
+        assert_close(track.at(20.0), 0.0);
+    }
+
+    #[test]
+    fn spline_track_goes_through_its_keys() {
+        let track = Track::new(Interpolation::Spline)
+            .with_key(0.0, Point::new(0.0, 0.0, 0.0))
+            .with_key(10.0, Point::new(1.0, 2.0, 0.0))
+            .with_key(20.0, Point::new(3.0, 2.0, 1.0))
+            .with_key(30.0, Point::new(4.0, 0.0, 1.0));
+        for &(time, expected) in &[(0.0, 0.0), (10.0, 1.0), (20.0, 3.0), (30.0, 4.0)] {
+            assert_close(track.at(time).x, expected);
+        }
+        // curving over the two keys at the same height, a line wouldn't
+        let between = track.at(15.0);
+        assert_close(between.x, 2.0);
+        assert_close(between.y, 2.25);
+    }
+
+    #[test]
+    fn track_holds_outside_of_its_keys() {
+        for &interpolation in &[Interpolation::Linear, Interpolation::Spline] {
+            let track = Track::new(interpolation).with_key(5.0, 1.0).with_key(8.0, 7.0).with_key(9.0, -1.0);
+            assert_close(track.at(-100.0), 1.0);
+            assert_close(track.at(4.9), 1.0);
+            assert_close(track.at(9.1), -1.0);
+            assert_close(track.at(1000.0), -1.0);
+        }
+        assert_close(Track::constant(3.0).at(42.0), 3.0);
+    }
+
+    #[test]
+    fn scale_going_through_zero_shrinks_the_object() {
+        let shrink = AnimatedTransform::new().with_scale(Track::new(Interpolation::Linear).with_key(0.0, 1.0).with_key(10.0, -1.0));
+        for i in 0..=20 {
+            let transform = shrink.at(i as f32 * 0.5);
+            let p = transform.point(Point::new(1.0, 0.0, 0.0));
+            assert!(p.x.is_finite() && p.x.abs() <= 1.0, "{:?} at {}", p, i);
+            assert!(transform.normal(Vector::new(1.0, 0.0, 0.0)).x.is_finite());
+        }
+        // the middle frame squeezes it to a point, which stays where it was
+        let middle = shrink.at(5.0);
+        assert!(middle.point(Point::new(1.0, 1.0, 1.0)).as_vector().norm() < 0.01);
+    }
+}
@@ -0,0 +1,47 @@
+use super::*;
+use crate::animation::Track;
+
+// thin lens camera whose placement and lens change over time, the focus
+// staying on `lookat` unless a focus distance is given
+#[derive(Debug, Clone)]
+pub struct AnimatedCamera {
+    pub lookfrom: Track<Point>,
+    pub lookat: Track<Point>,
+    pub vup: Vector,
+    // vertical, in degrees
+    pub vfov: Track<f32>,
+    pub aperture: Track<f32>,
+    pub focus_dist: Option<Track<f32>>,
+    pub aspect: f32,
+}
+
+impl AnimatedCamera {
+    pub fn new(lookfrom: Track<Point>, lookat: Track<Point>, vup: Vector, vfov: Track<f32>, aspect: f32) -> AnimatedCamera {
+        AnimatedCamera {
+            lookfrom,
+            lookat,
+            vup,
+            vfov,
+            aperture: Track::constant(0.0),
+            focus_dist: None,
+            aspect,
+        }
+    }
+
+    pub fn with_aperture(self, aperture: Track<f32>) -> AnimatedCamera {
+        AnimatedCamera { aperture, ..self }
+    }
+
+    pub fn with_focus_dist(self, focus_dist: Track<f32>) -> AnimatedCamera {
+        AnimatedCamera { focus_dist: Some(focus_dist), ..self }
+    }
+
+    pub fn at(&self, time: f32) -> Camera {
+        let (lookfrom, lookat) = (self.lookfrom.at(time), self.lookat.at(time));
+        let focus_dist = match &self.focus_dist {
+            Some(track) => track.at(time),
+            None => (lookat - lookfrom).norm(),
+        };
+        Camera::new(lookfrom, lookat, self.vup, self.vfov.at(time), self.aspect, self.aperture.at(time), focus_dist)
+    }
+}
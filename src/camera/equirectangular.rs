@@ -0,0 +1,94 @@
+use std::f32::consts::PI;
+
+use rand::RngCore;
+
+use super::*;
+
+// full 360 x 180 degrees latitude-longitude panorama, the center of the image
+// looking at `lookat`
+#[derive(Debug, Clone)]
+pub struct EquirectangularCamera {
+    origin: Point,
+    u: Vector,
+    v: Vector,
+    w: Vector,
+    // signed distance of the eye to the center, for omni-directional stereo
+    eye_offset: f32,
+}
+
+impl EquirectangularCamera {
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vector) -> Self {
+        EquirectangularCamera::stereo(lookfrom, lookat, vup, 0.0)
+    }
+
+    // omni-directional stereo: every column is seen from an eye on a circle of
+    // radius |eye_offset|, negative for the left eye and positive for the right one
+    pub fn stereo(lookfrom: Point, lookat: Point, vup: Vector, eye_offset: f32) -> Self {
+        let (u, v, w) = look_at_basis(lookfrom, lookat, vup);
+        EquirectangularCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            eye_offset,
+        }
+    }
+}
+
+impl CameraModel for EquirectangularCamera {
+    fn generate_ray(&self, s: f32, t: f32, _: &mut dyn RngCore) -> Option<Ray> {
+        let phi = (s - 0.5) * 2.0 * PI;
+        let theta = (t - 0.5) * PI;
+
+        let horizontal = self.u * phi.sin() - self.w * phi.cos();
+        let dir = horizontal * theta.cos() + self.v * theta.sin();
+        let tangent = self.u * phi.cos() + self.w * phi.sin();
+
+        Some(Ray::new(self.origin + tangent * self.eye_offset, dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+
+    fn direction(camera: &EquirectangularCamera, s: f32, t: f32) -> Vector {
+        camera.generate_ray(s, t, &mut XorShiftRng::from_seed([1; 16])).unwrap().direction
+    }
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).norm() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn panorama_covers_every_direction() {
+        let camera = EquirectangularCamera::new(Point::origin(), Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 0.0));
+        assert_close(direction(&camera, 0.5, 0.5), Vector::new(0.0, 0.0, -1.0));
+        assert_close(direction(&camera, 0.75, 0.5), Vector::new(1.0, 0.0, 0.0));
+        assert_close(direction(&camera, 0.25, 0.5), Vector::new(-1.0, 0.0, 0.0));
+        assert_close(direction(&camera, 0.0, 0.5), Vector::new(0.0, 0.0, 1.0));
+        assert_close(direction(&camera, 0.5, 1.0), Vector::new(0.0, 1.0, 0.0));
+        assert_close(direction(&camera, 0.5, 0.0), Vector::new(0.0, -1.0, 0.0));
+        // the left and right borders meet behind the camera
+        assert_close(direction(&camera, 0.0, 0.3), direction(&camera, 1.0, 0.3));
+    }
+
+    #[test]
+    fn stereo_eyes_sit_on_a_circle_across_the_view() {
+        let camera = EquirectangularCamera::stereo(Point::origin(), Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 0.0), 0.5);
+        let mut rng = XorShiftRng::from_seed([1; 16]);
+        for &s in &[0.1, 0.5, 0.8] {
+            let ray = camera.generate_ray(s, 0.6, &mut rng).unwrap();
+            let eye = ray.origin.as_vector();
+            assert!((eye.norm() - 0.5).abs() < 1e-5 && eye.y == 0.0);
+            // the eye is to the side of the horizontal view direction
+            assert!(eye.dot(ray.direction).abs() < 1e-5);
+        }
+        // looking forward, the right eye is on the right
+        let ray = camera.generate_ray(0.5, 0.5, &mut rng).unwrap();
+        assert_close(ray.origin.as_vector(), Vector::new(0.5, 0.0, 0.0));
+    }
+}
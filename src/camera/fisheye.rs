@@ -0,0 +1,112 @@
+use std::f32::consts::PI;
+
+use rand::RngCore;
+
+use super::*;
+
+// how the angle to the optical axis is laid out along the image radius
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FisheyeMapping {
+    // r = f * theta
+    Equidistant,
+    // r = 2 f * sin(theta / 2)
+    Equisolid,
+}
+
+// circular fisheye, the image circle fits the height of the picture
+#[derive(Debug, Clone)]
+pub struct FisheyeCamera {
+    origin: Point,
+    u: Vector,
+    v: Vector,
+    w: Vector,
+    half_fov: f32,
+    aspect: f32,
+    mapping: FisheyeMapping,
+}
+
+impl FisheyeCamera {
+    // `fov` is the angle covered by the image circle diameter, in degrees
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vector, fov: f32, aspect: f32, mapping: FisheyeMapping) -> Self {
+        let (u, v, w) = look_at_basis(lookfrom, lookat, vup);
+        FisheyeCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            half_fov: fov * PI / 360.0,
+            aspect,
+            mapping,
+        }
+    }
+}
+
+impl CameraModel for FisheyeCamera {
+    fn generate_ray(&self, s: f32, t: f32, _: &mut dyn RngCore) -> Option<Ray> {
+        let x = (2.0 * s - 1.0) * self.aspect;
+        let y = 2.0 * t - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.half_fov / 2.0).sin()).asin(),
+        };
+        let phi = y.atan2(x);
+
+        let dir = self.u * (theta.sin() * phi.cos()) + self.v * (theta.sin() * phi.sin()) - self.w * theta.cos();
+        Some(Ray::new(self.origin, dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+
+    // angle of the ray at the image point with the view axis, in degrees
+    fn angle(camera: &FisheyeCamera, s: f32, t: f32) -> Option<f32> {
+        let ray = camera.generate_ray(s, t, &mut XorShiftRng::from_seed([1; 16]))?;
+        Some(ray.direction.normalized().dot(Vector::new(0.0, 0.0, -1.0)).acos().to_degrees())
+    }
+
+    fn fisheye(mapping: FisheyeMapping) -> FisheyeCamera {
+        FisheyeCamera::new(Point::origin(), Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 0.0), 180.0, 1.0, mapping)
+    }
+
+    #[test]
+    fn image_circle_spans_the_field_of_view() {
+        for &mapping in &[FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+            let camera = fisheye(mapping);
+            assert!(angle(&camera, 0.5, 0.5).unwrap() < 1e-2);
+            assert!((angle(&camera, 0.5, 1.0).unwrap() - 90.0).abs() < 1e-2);
+            assert!((angle(&camera, 0.0, 0.5).unwrap() - 90.0).abs() < 1e-2);
+            // the corners are outside of the image circle
+            assert!(angle(&camera, 0.0, 0.0).is_none());
+        }
+    }
+
+    #[test]
+    fn mappings_lay_out_the_angle_differently() {
+        // halfway to the edge of the circle
+        let equidistant = angle(&fisheye(FisheyeMapping::Equidistant), 0.75, 0.5).unwrap();
+        let equisolid = angle(&fisheye(FisheyeMapping::Equisolid), 0.75, 0.5).unwrap();
+        assert!((equidistant - 45.0).abs() < 1e-2);
+        // r = 2 f sin(theta / 2), with r = 1 at 90 degrees
+        let expected = 2.0 * (0.5 * 45.0f32.to_radians().sin()).asin().to_degrees();
+        assert!((equisolid - expected).abs() < 1e-2, "{} != {}", equisolid, expected);
+    }
+
+    #[test]
+    fn up_in_the_image_is_up_in_the_scene() {
+        let camera = fisheye(FisheyeMapping::Equidistant);
+        let ray = camera.generate_ray(0.5, 0.9, &mut XorShiftRng::from_seed([1; 16])).unwrap();
+        assert!(ray.direction.y > 0.0 && ray.direction.x.abs() < 1e-5);
+        let ray = camera.generate_ray(0.9, 0.5, &mut XorShiftRng::from_seed([1; 16])).unwrap();
+        assert!(ray.direction.x > 0.0 && ray.direction.y.abs() < 1e-5);
+    }
+}
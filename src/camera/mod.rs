@@ -0,0 +1,322 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::sync::Arc;
+
+use rand;
+use rand::{Rng, RngCore};
+
+use crate::prelude::*;
+
+mod orthographic;
+mod fisheye;
+mod equirectangular;
+mod stereo;
+mod animated;
+pub use self::orthographic::*;
+pub use self::fisheye::*;
+pub use self::equirectangular::*;
+pub use self::stereo::*;
+pub use self::animated::*;
+
+// anything that maps image coordinates in [0, 1]^2 to primary rays, (0, 0)
+// being the lower left corner
+pub trait CameraModel: Send + Sync {
+    // None when the image point is not covered by the projection
+    fn generate_ray(&self, s: f32, t: f32, rng: &mut dyn RngCore) -> Option<Ray>;
+}
+
+// right handed basis looking from `lookfrom` towards `lookat`, w points backward
+fn look_at_basis(lookfrom: Point, lookat: Point, vup: Vector) -> (Vector, Vector, Vector) {
+    let w = (lookfrom - lookat).normalized();
+    let u = vup.cross(w).normalized();
+    let v = w.cross(u);
+    (u, v, w)
+}
+
+// lens description in photographic units, the sensor is fitted horizontally
+// and its height follows the image aspect
+#[derive(Debug, Clone, Copy)]
+pub struct PhysicalLens {
+    // millimeters
+    pub sensor_width: f32,
+    // millimeters
+    pub focal_length: f32,
+    pub f_stop: f32,
+    // scale of the scene, 1.0 when it is modeled in meters
+    pub units_per_meter: f32,
+}
+
+impl PhysicalLens {
+    pub fn full_frame(focal_length: f32, f_stop: f32) -> PhysicalLens {
+        PhysicalLens {
+            sensor_width: 36.0,
+            focal_length,
+            f_stop,
+            units_per_meter: 1.0,
+        }
+    }
+
+    pub fn vfov(&self, aspect: f32) -> f32 {
+        let sensor_height = self.sensor_width / aspect;
+        2.0 * (sensor_height / (2.0 * self.focal_length)).atan() * 180.0 / PI
+    }
+
+    // diameter of the entrance pupil, in scene units
+    pub fn aperture(&self) -> f32 {
+        self.focal_length / self.f_stop / 1000.0 * self.units_per_meter
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Focus {
+    Distance(f32),
+    // focus plane going through this point
+    Point(Point),
+}
+
+// shape of the lens opening, which is also the shape of the bokeh
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    // regular polygon inscribed in the lens disk, rotation in degrees
+    Polygon { blades: u32, rotation: f32 },
+    // the mask texture is read over the [0, 1]^2 square covering the lens disk
+    Mask(Arc<dyn Texture>),
+}
+
+impl Aperture {
+    // point on the lens, in the unit disk
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vector {
+        match self {
+            Aperture::Circle => Vector::rand_in_unit_disk(rng),
+            Aperture::Polygon { blades, rotation } => {
+                // the polygon is made of `blades` triangles of the same area around the center
+                let blades = (*blades).max(3);
+                let step = 2.0 * PI / blades as f32;
+                let start = rotation * PI / 180.0 + step * rng.gen_range(0, blades) as f32;
+                let (a, b) = (Vector::new(start.cos(), start.sin(), 0.0), Vector::new((start + step).cos(), (start + step).sin(), 0.0));
+
+                let (mut s, mut t): (f32, f32) = (rng.gen(), rng.gen());
+                if s + t > 1.0 {
+                    s = 1.0 - s;
+                    t = 1.0 - t;
+                }
+                a * s + b * t
+            },
+            Aperture::Mask(mask) => {
+                const MAX_TRIES: usize = 64;
+                for _ in 0..MAX_TRIES {
+                    let p = Vector::rand_in_unit_disk(rng);
+                    let (u, v) = ((p.x + 1.0) / 2.0, (p.y + 1.0) / 2.0);
+                    if rng.gen::<f32>() < mask.scalar_value(u, v, Point::new(u, v, 0.0)) {
+                        return p;
+                    }
+                }
+                Vector::zero()
+            },
+        }
+    }
+}
+
+impl fmt::Debug for Aperture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Aperture::Circle => write!(f, "Circle"),
+            Aperture::Polygon { blades, rotation } => write!(f, "Polygon {{ blades: {}, rotation: {} }}", blades, rotation),
+            Aperture::Mask(_) => write!(f, "Mask"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Camera {
+    lower_left_corner: Point,
+    origin: Point,
+    horizontal: Vector,
+    vertical: Vector,
+    u: Vector,
+    v: Vector,
+    w: Vector,
+    lens_radius: f32,
+    aperture: Aperture,
+}
+
+impl Camera {
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vector, vfov: f32, aspect: f32, aperture: f32, focus_dist: f32) -> Self {
+        let lens_radius = aperture / 2.0;
+
+        let theta = vfov * std::f32::consts::PI / 180.0;
+        let half_height = (theta / 2.0).tan();
+        let half_width = aspect * half_height;
+
+        let (u, v, w) = look_at_basis(lookfrom, lookat, vup);
+
+        let lower_left_corner = lookfrom - u * focus_dist * half_width - v * focus_dist * half_height - w * focus_dist;
+        let horizontal = u * half_width * 2.0 * focus_dist;
+        let vertical = v * half_height * 2.0 * focus_dist;
+
+        Camera {
+            lower_left_corner,
+            origin: lookfrom,
+            horizontal,
+            vertical,
+            u,
+            v,
+            w,
+            lens_radius,
+            aperture: Aperture::Circle,
+        }
+    }
+
+    pub fn from_lens(lookfrom: Point, lookat: Point, vup: Vector, lens: PhysicalLens, aspect: f32, focus: Focus) -> Self {
+        let focus_dist = match focus {
+            Focus::Distance(d) => d,
+            // distance along the view axis, not to the point itself
+            Focus::Point(p) => (p - lookfrom).dot((lookat - lookfrom).normalized()),
+        };
+        Camera::new(lookfrom, lookat, vup, lens.vfov(aspect), aspect, lens.aperture(), focus_dist)
+    }
+
+    pub fn with_aperture(self, aperture: Aperture) -> Self {
+        Camera { aperture, ..self }
+    }
+
+    // same camera moved by `eye_offset` along its right axis, the image window
+    // being shifted back so the view still converges at `convergence`
+    pub fn off_axis_eye(&self, eye_offset: f32, convergence: f32) -> Self {
+        let window_center = self.lower_left_corner + self.horizontal / 2.0 + self.vertical / 2.0;
+        let focus_dist = (self.origin - window_center).dot(self.w);
+        let shift = self.u * eye_offset;
+
+        Camera {
+            origin: self.origin + shift,
+            lower_left_corner: self.lower_left_corner + shift * (1.0 - focus_dist / convergence),
+            ..self.clone()
+        }
+    }
+
+    pub fn get_ray(&self, s: f32, t: f32) -> Ray {
+        self.thin_lens_ray(s, t, &mut rand::thread_rng())
+    }
+
+    fn thin_lens_ray<R: Rng + ?Sized>(&self, s: f32, t: f32, rng: &mut R) -> Ray {
+        let rd = self.aperture.sample(rng) * self.lens_radius;
+        let offset = self.u * rd.x + self.v * rd.y;
+        let dir = self.lower_left_corner.as_vector() + self.horizontal * s + self.vertical * t - self.origin.as_vector() - offset;
+        Ray::new(self.origin + offset, dir)
+    }
+}
+
+impl CameraModel for Camera {
+    fn generate_ray(&self, s: f32, t: f32, rng: &mut dyn RngCore) -> Option<Ray> {
+        Some(self.thin_lens_ray(s, t, rng))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).norm() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn camera(aperture: f32) -> Camera {
+        Camera::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 0.0), 90.0, 2.0, aperture, 5.0)
+    }
+
+    // lens point of a ray, in units of the lens radius
+    fn lens_point(camera: &Camera, ray: Ray) -> Vector {
+        let offset = ray.origin - camera.origin;
+        Vector::new(offset.dot(camera.u), offset.dot(camera.v), 0.0) / camera.lens_radius
+    }
+
+    struct RightHalf;
+
+    impl Texture for RightHalf {
+        fn value(&self, u: f32, _: f32, _: Point) -> Color {
+            if u > 0.5 { Color::new(255, 255, 255) } else { Color::new(0, 0, 0) }
+        }
+    }
+
+    #[test]
+    fn physical_lens_gives_the_field_of_view_and_aperture() {
+        let lens = PhysicalLens::full_frame(50.0, 2.0);
+        // 24mm of sensor height at 3:2
+        let expected = 2.0 * (12.0f32 / 50.0).atan().to_degrees();
+        assert!((lens.vfov(1.5) - expected).abs() < 1e-4);
+        assert!((lens.aperture() - 0.025).abs() < 1e-6);
+        let centimeters = PhysicalLens { units_per_meter: 100.0, ..lens };
+        assert!((centimeters.aperture() - 2.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn focus_point_is_measured_along_the_view_axis() {
+        let lens = PhysicalLens::full_frame(50.0, 1.4);
+        let (lookfrom, lookat, up) = (Point::new(0.0, 0.0, 0.0), Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 0.0));
+        let by_point = Camera::from_lens(lookfrom, lookat, up, lens, 1.5, Focus::Point(Point::new(3.0, 1.0, -4.0)));
+        let by_distance = Camera::from_lens(lookfrom, lookat, up, lens, 1.5, Focus::Distance(4.0));
+        assert_close(by_point.lower_left_corner.as_vector(), by_distance.lower_left_corner.as_vector());
+        assert!((by_point.lens_radius - lens.aperture() / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rays_through_the_lens_meet_on_the_focus_plane() {
+        let camera = camera(0.5);
+        let mut rng = XorShiftRng::from_seed([2; 16]);
+        for &(s, t) in &[(0.5, 0.5), (0.1, 0.9), (0.8, 0.3)] {
+            let target = camera.lower_left_corner + camera.horizontal * s + camera.vertical * t;
+            for _ in 0..100 {
+                let ray = camera.generate_ray(s, t, &mut rng).unwrap();
+                assert!(lens_point(&camera, ray).norm() <= 1.0 + 1e-4);
+                assert_close(ray.point_at(1.0).as_vector(), target.as_vector());
+            }
+        }
+        // at the center of the image, the focus plane is 5 in front of the camera
+        let center = camera.lower_left_corner + camera.horizontal * 0.5 + camera.vertical * 0.5;
+        assert_close(center.as_vector(), Vector::new(0.0, 0.0, -5.0));
+    }
+
+    #[test]
+    fn pinhole_rays_start_at_the_eye() {
+        let camera = camera(0.0);
+        let ray = camera.generate_ray(0.3, 0.6, &mut XorShiftRng::from_seed([2; 16])).unwrap();
+        assert_close(ray.origin.as_vector(), Vector::zero());
+    }
+
+    #[test]
+    fn polygon_aperture_stays_inside_its_blades() {
+        let mut rng = XorShiftRng::from_seed([4; 16]);
+        let (blades, rotation) = (6, 15.0f32);
+        let aperture = Aperture::Polygon { blades, rotation };
+        let step = 2.0 * PI / blades as f32;
+        let apothem = (step / 2.0).cos();
+        let mut mean = Vector::zero();
+        for _ in 0..10_000 {
+            let p = aperture.sample(&mut rng);
+            // on the inner side of every edge
+            for i in 0..blades {
+                let middle = rotation.to_radians() + step * (i as f32 + 0.5);
+                assert!(p.dot(Vector::new(middle.cos(), middle.sin(), 0.0)) <= apothem + 1e-5, "{:?} outside", p);
+            }
+            mean = mean + p / 10_000.0;
+        }
+        assert!(mean.norm() < 0.02, "uneven polygon {:?}", mean);
+    }
+
+    #[test]
+    fn mask_aperture_only_opens_where_it_is_white() {
+        let mut rng = XorShiftRng::from_seed([4; 16]);
+        let aperture = Aperture::Mask(Arc::new(RightHalf));
+        for _ in 0..1000 {
+            let p = aperture.sample(&mut rng);
+            assert!(p.x > 0.0 && p.norm() < 1.0, "{:?} in the closed half", p);
+        }
+        // a closed mask falls back to a pinhole
+        let closed = Aperture::Mask(Arc::new(crate::texture::ConstantTexture::scalar(0.0)));
+        assert_eq!(closed.sample(&mut rng), Vector::zero());
+    }
+}
@@ -0,0 +1,57 @@
+use rand::RngCore;
+
+use super::*;
+
+// parallel projection, for technical drawings
+#[derive(Debug, Clone)]
+pub struct OrthographicCamera {
+    lower_left_corner: Point,
+    horizontal: Vector,
+    vertical: Vector,
+    direction: Vector,
+}
+
+impl OrthographicCamera {
+    // `height` is the extent of the view, in scene units
+    pub fn new(lookfrom: Point, lookat: Point, vup: Vector, height: f32, aspect: f32) -> Self {
+        let (u, v, w) = look_at_basis(lookfrom, lookat, vup);
+        let horizontal = u * height * aspect;
+        let vertical = v * height;
+
+        OrthographicCamera {
+            lower_left_corner: lookfrom - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            direction: -w,
+        }
+    }
+}
+
+impl CameraModel for OrthographicCamera {
+    fn generate_ray(&self, s: f32, t: f32, _: &mut dyn RngCore) -> Option<Ray> {
+        let origin = self.lower_left_corner + self.horizontal * s + self.vertical * t;
+        Some(Ray::new(origin, self.direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+
+    #[test]
+    fn rays_are_parallel_and_cover_the_height() {
+        let camera = OrthographicCamera::new(Point::new(0.0, 0.0, 5.0), Point::origin(), Vector::new(0.0, 1.0, 0.0), 4.0, 2.0);
+        let mut rng = XorShiftRng::from_seed([1; 16]);
+        let mut ray = |s, t| camera.generate_ray(s, t, &mut rng).unwrap();
+
+        let (lower_left, upper_right, inside) = (ray(0.0, 0.0), ray(1.0, 1.0), ray(0.3, 0.7));
+        assert!((lower_left.origin - Point::new(-4.0, -2.0, 5.0)).norm() < 1e-5);
+        assert!((upper_right.origin - Point::new(4.0, 2.0, 5.0)).norm() < 1e-5);
+        for ray in &[lower_left, upper_right, inside] {
+            assert!((ray.direction - Vector::new(0.0, 0.0, -1.0)).norm() < 1e-5);
+        }
+    }
+}
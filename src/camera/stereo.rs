@@ -0,0 +1,77 @@
+use super::*;
+
+// pair of eye cameras for stereoscopic output
+pub struct StereoCamera {
+    pub left: Box<dyn CameraModel>,
+    pub right: Box<dyn CameraModel>,
+}
+
+impl StereoCamera {
+    pub fn new<L: CameraModel + 'static, R: CameraModel + 'static>(left: L, right: R) -> Self {
+        StereoCamera {
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    // parallel eyes separated by `interocular`, with off-axis frustums so that
+    // objects at `convergence` have no parallax
+    pub fn off_axis(camera: &Camera, interocular: f32, convergence: f32) -> Self {
+        StereoCamera::new(
+            camera.off_axis_eye(-interocular / 2.0, convergence),
+            camera.off_axis_eye(interocular / 2.0, convergence),
+        )
+    }
+
+    // omni-directional stereo panorama
+    pub fn equirectangular(lookfrom: Point, lookat: Point, vup: Vector, interocular: f32) -> Self {
+        StereoCamera::new(
+            EquirectangularCamera::stereo(lookfrom, lookat, vup, -interocular / 2.0),
+            EquirectangularCamera::stereo(lookfrom, lookat, vup, interocular / 2.0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+
+    // where the ray crosses the plane at `depth` in front of a camera looking down -z
+    fn at_depth(ray: Ray, depth: f32) -> Point {
+        ray.point_at((-depth - ray.origin.z) / ray.direction.z)
+    }
+
+    #[test]
+    fn off_axis_eyes_converge_without_parallax() {
+        let camera = Camera::new(Point::origin(), Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 0.0), 60.0, 1.5, 0.0, 5.0);
+        let stereo = StereoCamera::off_axis(&camera, 0.065, 3.0);
+        let mut rng = XorShiftRng::from_seed([1; 16]);
+        for &(s, t) in &[(0.5, 0.5), (0.1, 0.2), (0.9, 0.7)] {
+            let left = stereo.left.generate_ray(s, t, &mut rng).unwrap();
+            let right = stereo.right.generate_ray(s, t, &mut rng).unwrap();
+            assert!((right.origin - left.origin - Vector::new(0.065, 0.0, 0.0)).norm() < 1e-6);
+
+            // same point at the convergence distance, nearer objects pop out
+            assert!((at_depth(left, 3.0) - at_depth(right, 3.0)).norm() < 1e-5);
+            let near = at_depth(right, 1.0).x - at_depth(left, 1.0).x;
+            let far = at_depth(right, 10.0).x - at_depth(left, 10.0).x;
+            assert!(near > 0.0 && far < 0.0, "near {} far {}", near, far);
+        }
+    }
+
+    #[test]
+    fn image_centers_look_towards_the_convergence_point() {
+        let camera = Camera::new(Point::origin(), Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 0.0), 60.0, 1.5, 0.0, 5.0);
+        let stereo = StereoCamera::off_axis(&camera, 0.065, 3.0);
+        let mut rng = XorShiftRng::from_seed([1; 16]);
+        // the frustums are sheared rather than toed in, only horizontally
+        let center = camera.generate_ray(0.5, 0.5, &mut rng).unwrap();
+        let left = stereo.left.generate_ray(0.5, 0.5, &mut rng).unwrap();
+        let right = stereo.right.generate_ray(0.5, 0.5, &mut rng).unwrap();
+        assert!(left.direction.x > 0.0 && right.direction.x < 0.0);
+        assert!((left.direction.y - center.direction.y).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,284 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::film::{self, Bounds, Film};
+use crate::filter::FilterKind;
+use crate::sampler::SamplerKind;
+
+const MAGIC: &[u8; 4] = b"RTCK";
+const VERSION: u32 = 6;
+
+// state of an interrupted progressive render: the accumulated film, the
+// settings it was rendered with and the index of the first sample still to
+// take, samplers being stateless the render goes on exactly as if it never
+// stopped when the settings are the same
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub film: Film,
+    pub seed: u32,
+    pub sampler: SamplerKind,
+    pub filter: FilterKind,
+    pub region: Bounds,
+    pub samples_per_pass: usize,
+    // samples per pixel of the finished render
+    pub max_samples: usize,
+    pub max_depth: usize,
+    pub adaptive_threshold: Option<f32>,
+    pub spectral: bool,
+    pub next_sample: usize,
+    pub elapsed: Duration,
+}
+
+impl Checkpoint {
+    // written next to the destination then moved over it, so a crash while
+    // saving leaves the previous checkpoint intact
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            writer.write_all(MAGIC)?;
+            bytes::write_u32(&mut writer, VERSION)?;
+            bytes::write_u32(&mut writer, self.seed)?;
+            bytes::write_name(&mut writer, self.sampler.name())?;
+            bytes::write_name(&mut writer, self.filter.name())?;
+            film::write_bounds(&mut writer, self.region)?;
+            bytes::write_u64(&mut writer, self.samples_per_pass as u64)?;
+            bytes::write_u64(&mut writer, self.max_samples as u64)?;
+            bytes::write_u64(&mut writer, self.max_depth as u64)?;
+            bytes::write_bool(&mut writer, self.adaptive_threshold.is_some())?;
+            bytes::write_f32(&mut writer, self.adaptive_threshold.unwrap_or(0.0))?;
+            bytes::write_bool(&mut writer, self.spectral)?;
+            bytes::write_u64(&mut writer, self.next_sample as u64)?;
+            bytes::write_u64(&mut writer, self.elapsed.as_secs())?;
+            bytes::write_u32(&mut writer, self.elapsed.subsec_nanos())?;
+            self.film.write_to(&mut writer)?;
+            writer.flush()?;
+        }
+        fs::rename(tmp_path, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Checkpoint> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC || bytes::read_u32(&mut reader)? != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a checkpoint or unsupported version"));
+        }
+
+        let seed = bytes::read_u32(&mut reader)?;
+        let sampler = SamplerKind::from_name(&bytes::read_name(&mut reader)?)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown sampler"))?;
+        let filter = FilterKind::from_name(&bytes::read_name(&mut reader)?)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown filter"))?;
+        let region = film::read_bounds(&mut reader)?;
+        let samples_per_pass = bytes::read_u64(&mut reader)? as usize;
+        let max_samples = bytes::read_u64(&mut reader)? as usize;
+        let max_depth = bytes::read_u64(&mut reader)? as usize;
+        let adaptive = bytes::read_bool(&mut reader)?;
+        let threshold = bytes::read_f32(&mut reader)?;
+        let spectral = bytes::read_bool(&mut reader)?;
+        let next_sample = bytes::read_u64(&mut reader)? as usize;
+        let secs = bytes::read_u64(&mut reader)?;
+        let nanos = bytes::read_u32(&mut reader)?;
+        if nanos >= 1_000_000_000 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid elapsed time"));
+        }
+        let film = Film::read_from(&mut reader)?;
+
+        Ok(Checkpoint {
+            film,
+            seed,
+            sampler,
+            filter,
+            region,
+            samples_per_pass,
+            max_samples,
+            max_depth,
+            adaptive_threshold: if adaptive { Some(threshold) } else { None },
+            spectral,
+            next_sample,
+            elapsed: Duration::new(secs, nanos),
+        })
+    }
+}
+
+// little endian encoding of the checkpoint fields
+pub(crate) mod bytes {
+    use std::io::{self, Read, Write};
+
+    pub fn write_u32(w: &mut dyn Write, v: u32) -> io::Result<()> {
+        w.write_all(&v.to_le_bytes())
+    }
+
+    pub fn write_u64(w: &mut dyn Write, v: u64) -> io::Result<()> {
+        w.write_all(&v.to_le_bytes())
+    }
+
+    pub fn write_f32(w: &mut dyn Write, v: f32) -> io::Result<()> {
+        write_u32(w, v.to_bits())
+    }
+
+    pub fn write_bool(w: &mut dyn Write, v: bool) -> io::Result<()> {
+        w.write_all(&[v as u8])
+    }
+
+    pub fn read_u32(r: &mut dyn Read) -> io::Result<u32> {
+        let mut buf = [0; 4];
+        r.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    pub fn read_u64(r: &mut dyn Read) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        r.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    pub fn read_f32(r: &mut dyn Read) -> io::Result<f32> {
+        Ok(f32::from_bits(read_u32(r)?))
+    }
+
+    pub fn read_bool(r: &mut dyn Read) -> io::Result<bool> {
+        let mut buf = [0];
+        r.read_exact(&mut buf)?;
+        match buf[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid flag")),
+        }
+    }
+
+    // short identifier, like the name of a sampler
+    pub fn write_name(w: &mut dyn Write, name: &str) -> io::Result<()> {
+        assert!(name.len() <= u8::MAX as usize);
+        w.write_all(&[name.len() as u8])?;
+        w.write_all(name.as_bytes())
+    }
+
+    pub fn read_name(r: &mut dyn Read) -> io::Result<String> {
+        let mut len = [0];
+        r.read_exact(&mut len)?;
+        let mut name = vec![0; len[0] as usize];
+        r.read_exact(&mut name)?;
+        String::from_utf8(name).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid name"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::{Features, FilmSample, FilmTile};
+    use crate::math::Vector;
+
+    // offsets of the spectral flag, of the elapsed nanoseconds and of the film size
+    const SPECTRAL: usize = 4 + 4 + 4 + 1 + 5 + 1 + 8 + 32 + 8 + 8 + 8 + 1 + 4;
+    const NANOS: usize = SPECTRAL + 1 + 8 + 8;
+    const FILM: usize = NANOS + 4;
+
+    // bytes of a small checkpoint, to be corrupted
+    fn saved(name: &str) -> (std::path::PathBuf, Vec<u8>) {
+        let path = std::env::temp_dir().join(format!("rt_checkpoint_{}_{}.bin", name, std::process::id()));
+        let mut film = Film::new(4, 3).with_aovs();
+        let mut tile = FilmTile::new(Bounds::new(0, 0, 4, 3), true);
+        let filter = FilterKind::Tent.build();
+        for i in 0..6 {
+            let sample = FilmSample {
+                radiance: Vector::new(0.1 * i as f32, 0.5, 2.0),
+                alpha: 1.0,
+                direct: Vector::new(0.25, 0.0, 0.0),
+                features: Features::zero(),
+            };
+            tile.add_sample(0.5 + i as f32 * 0.6, 1.3, &sample, filter.as_ref());
+        }
+        film.merge_tile(&tile);
+        let checkpoint = Checkpoint {
+            film,
+            seed: 7,
+            sampler: SamplerKind::Sobol,
+            filter: FilterKind::Gaussian,
+            region: Bounds::new(0, 0, 4, 3),
+            samples_per_pass: 2,
+            max_samples: 64,
+            max_depth: 12,
+            adaptive_threshold: Some(0.02),
+            spectral: true,
+            next_sample: 16,
+            elapsed: Duration::new(12, 345),
+        };
+        checkpoint.save(&path).unwrap();
+        let data = fs::read(&path).unwrap();
+        (path, data)
+    }
+
+    fn load_corrupted(name: &str, corrupt: impl Fn(&mut Vec<u8>)) -> io::Result<Checkpoint> {
+        let (path, mut data) = saved(name);
+        corrupt(&mut data);
+        fs::write(&path, &data).unwrap();
+        let loaded = Checkpoint::load(&path);
+        fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn loads_what_was_saved() {
+        let (path, data) = saved("round_trip");
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded.seed, 7);
+        assert_eq!(loaded.sampler, SamplerKind::Sobol);
+        assert_eq!(loaded.filter, FilterKind::Gaussian);
+        assert_eq!(loaded.region, Bounds::new(0, 0, 4, 3));
+        assert_eq!(loaded.samples_per_pass, 2);
+        assert_eq!(loaded.max_samples, 64);
+        assert_eq!(loaded.max_depth, 12);
+        assert_eq!(loaded.adaptive_threshold, Some(0.02));
+        assert!(loaded.spectral);
+        assert_eq!(loaded.next_sample, 16);
+        assert_eq!(loaded.elapsed, Duration::new(12, 345));
+        assert_eq!(loaded.film.get_dimensions(), (4, 3));
+        assert!(loaded.film.has_aovs());
+
+        // the film too, bit for bit
+        loaded.save(&path).unwrap();
+        assert!(fs::read(&path).unwrap() == data);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn flags_other_than_zero_or_one_are_invalid() {
+        let err = load_corrupted("flag", |data| data[SPECTRAL] = 2).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn nanos_past_a_second_are_invalid() {
+        let err = load_corrupted("nanos", |data| data[NANOS..NANOS + 4].copy_from_slice(&1_000_000_000u32.to_le_bytes())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn overflowing_film_size_is_invalid() {
+        let err = load_corrupted("overflow", |data| {
+            data[FILM..FILM + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+            data[FILM + 8..FILM + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        }).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn huge_film_size_fails_without_allocating_it() {
+        let err = load_corrupted("huge", |data| {
+            data[FILM..FILM + 8].copy_from_slice(&(1u64 << 31).to_le_bytes());
+            data[FILM + 8..FILM + 16].copy_from_slice(&(1u64 << 31).to_le_bytes());
+        }).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn truncated_checkpoint_fails() {
+        let err = load_corrupted("truncated", |data| data.truncate(data.len() - 1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,376 @@
+use std::env;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use raytracer::denoise::Denoiser;
+use raytracer::filter::FilterKind;
+use raytracer::sampler::SamplerKind;
+use raytracer::film::{Aov, Bounds, RenderRegion};
+use raytracer::post::{AlphaMode, Bloom, ChromaticAberration, ColorSpace, PostProcess, ToneMap, Transfer, Vignette};
+
+pub const USAGE: &str = "usage: rt_driver [options]
+
+options:
+    --denoise                       denoise the image before writing it
+    --denoise-radius <pixels>       half size of the denoising window
+    --denoise-sigma-spatial <f32>   falloff with the distance to the pixel
+    --denoise-sigma-color <f32>     tolerance to lighting differences
+    --denoise-sigma-albedo <f32>    tolerance to albedo differences
+    --denoise-sigma-normal <f32>    tolerance to normal differences
+    --aovs <names|all>              comma separated passes to write besides the image:
+                                    depth, normal, albedo, position, object_id,
+                                    material_id, direct, indirect
+    --aov-format <png|exr>          one png per pass next to the image, or a single
+                                    exr holding the image and the passes as layers
+    --exposure <stops>              brightens or darkens the image before tone mapping
+    --tone-map <name>               clamp, reinhard, aces or agx
+    --reinhard-white <f32>          luminance reinhard maps to white, picking reinhard
+                                    when no tone map is given
+    --working-space <name>          space of the scene colors: srgb, display-p3,
+                                    rec2020 or acescg
+    --display-space <name>          space of the written image, same names
+    --transfer <srgb|linear|gamma>  encoding of the written image, a gamma being
+                                    given as a number
+    --chromatic-aberration <f32>    relative change of magnification of red and blue
+    --bloom                         glow around the bright parts of the image
+    --bloom-threshold <f32>         radiance over which light glows
+    --bloom-intensity <f32>         part of the light over the threshold that glows
+    --bloom-size <f32>              size of the glow, relative to the image width
+    --vignette <f32>                darkening of the corners, tangent of their angle
+    --transparent                   camera rays missing the scene give a transparent
+                                    background, the image getting an alpha channel
+    --alpha <mode>                  premultiplied or straight, how colors are stored
+                                    next to the alpha
+    --sampler <name>                sobol, halton, stratified, latin-hypercube or random
+    --filter <name>                 reconstruction filter: gaussian, box, tent, mitchell
+                                    or lanczos
+    --region <x0,y0,x1,y1>          renders only these pixels, the end being excluded
+    --crop-window <x0,y0,x1,y1>     renders only this part of the image, in fractions
+                                    of its size
+    --crop                          writes the region alone instead of the whole frame
+    --frames <first-last>           renders these frames of the animation, each one
+                                    to a file numbered after the image
+    --coordinator <address>         hands the render out to the workers connecting
+                                    to this address, like 0.0.0.0:7878, rendering
+                                    here while none is connected
+    --worker <address>              renders for the coordinator at this address,
+                                    which must have the same scene and seed
+    --preview <address>             serves a page at this address, like 127.0.0.1:8000,
+                                    showing the image refine while the camera is moved
+                                    around, when built with the preview feature
+    --help                          print this message
+
+any --denoise-* option turns denoising on, any --bloom-* option turns bloom on,
+effects are applied in the order above, before exposure and tone mapping";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AovFormat {
+    #[default]
+    Png,
+    Exr,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub denoise: Option<Denoiser>,
+    pub aovs: Vec<Aov>,
+    pub aov_format: AovFormat,
+    pub post: PostProcess,
+    pub sampler: SamplerKind,
+    pub filter: FilterKind,
+    pub transparent: bool,
+    pub region: Option<RenderRegion>,
+    pub crop: bool,
+    pub frames: Option<RangeInclusive<usize>>,
+    pub coordinator: Option<String>,
+    pub worker: Option<String>,
+    pub preview: Option<String>,
+    pub help: bool,
+}
+
+// the region has to lie in the `width` by `height` image
+pub fn parse_args(width: usize, height: usize) -> Result<Options, String> {
+    parse(env::args().skip(1), width, height)
+}
+
+fn parse<I: Iterator<Item = String>>(mut args: I, width: usize, height: usize) -> Result<Options, String> {
+    let mut options = Options::default();
+    let (mut aberration, mut bloom, mut vignette) = (None, None::<Bloom>, None);
+    let (mut tone_map, mut reinhard_white) = (None, None);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => options.help = true,
+            "--denoise" => {
+                options.denoise.get_or_insert_with(Denoiser::default);
+            },
+            "--denoise-radius" => {
+                denoiser(&mut options).radius = value(&arg, args.next())?;
+            },
+            "--denoise-sigma-spatial" => {
+                denoiser(&mut options).sigma_spatial = positive(&arg, args.next())?;
+            },
+            "--denoise-sigma-color" => {
+                denoiser(&mut options).sigma_color = positive(&arg, args.next())?;
+            },
+            "--denoise-sigma-albedo" => {
+                denoiser(&mut options).sigma_albedo = positive(&arg, args.next())?;
+            },
+            "--denoise-sigma-normal" => {
+                denoiser(&mut options).sigma_normal = positive(&arg, args.next())?;
+            },
+            "--aovs" => {
+                options.aovs = aovs(&value::<String>(&arg, args.next())?)?;
+            },
+            "--aov-format" => {
+                options.aov_format = match value::<String>(&arg, args.next())?.as_str() {
+                    "png" => AovFormat::Png,
+                    "exr" => AovFormat::Exr,
+                    format => return Err(format!("unknown AOV format {}", format)),
+                };
+            },
+            "--exposure" => {
+                options.post.exposure = value(&arg, args.next())?;
+            },
+            "--tone-map" => {
+                let name = value::<String>(&arg, args.next())?;
+                tone_map = Some(ToneMap::from_name(&name)
+                    .ok_or_else(|| format!("unknown tone map {}, expected one of {}", name, ToneMap::NAMES.join(", ")))?);
+            },
+            "--reinhard-white" => {
+                reinhard_white = Some(positive(&arg, args.next())?);
+            },
+            "--working-space" => {
+                options.post.working_space = color_space(&value::<String>(&arg, args.next())?)?;
+            },
+            "--display-space" => {
+                options.post.display_space = color_space(&value::<String>(&arg, args.next())?)?;
+            },
+            "--transfer" => {
+                options.post.transfer = match value::<String>(&arg, args.next())?.as_str() {
+                    "srgb" => Transfer::Srgb,
+                    "linear" => Transfer::Linear,
+                    gamma => match gamma.parse::<f32>() {
+                        Ok(gamma) if gamma > 0.0 && gamma.is_finite() => Transfer::Gamma(gamma),
+                        _ => return Err(format!("unknown transfer {}, expected srgb, linear or a positive gamma", gamma)),
+                    },
+                };
+            },
+            "--chromatic-aberration" => {
+                aberration = Some(ChromaticAberration::new(value(&arg, args.next())?));
+            },
+            "--bloom" => {
+                bloom.get_or_insert_with(Bloom::default);
+            },
+            "--bloom-threshold" => {
+                bloom.get_or_insert_with(Bloom::default).threshold = value(&arg, args.next())?;
+            },
+            "--bloom-intensity" => {
+                bloom.get_or_insert_with(Bloom::default).intensity = value(&arg, args.next())?;
+            },
+            "--bloom-size" => {
+                bloom.get_or_insert_with(Bloom::default).size = value(&arg, args.next())?;
+            },
+            "--vignette" => {
+                vignette = Some(Vignette::new(value(&arg, args.next())?));
+            },
+            "--transparent" => options.transparent = true,
+            "--alpha" => {
+                options.post.alpha_mode = match value::<String>(&arg, args.next())?.as_str() {
+                    "premultiplied" => AlphaMode::Premultiplied,
+                    "straight" => AlphaMode::Straight,
+                    mode => return Err(format!("unknown alpha mode {}", mode)),
+                };
+            },
+            "--region" => {
+                let [x0, y0, x1, y1] = list(&arg, args.next())?;
+                if x1 <= x0 || y1 <= y0 || x1 > width || y1 > height {
+                    return Err(format!(
+                        "invalid region {},{},{},{}, expected a non empty part of the {}x{} image",
+                        x0, y0, x1, y1, width, height,
+                    ));
+                }
+                options.region = Some(RenderRegion::Pixels(Bounds::new(x0, y0, x1, y1)));
+            },
+            "--crop-window" => {
+                let [x0, y0, x1, y1]: [f32; 4] = list(&arg, args.next())?;
+                let fraction = |t: f32| (0.0..=1.0).contains(&t);
+                if x1 <= x0 || y1 <= y0 || ![x0, y0, x1, y1].iter().all(|&t| fraction(t)) {
+                    return Err(format!(
+                        "invalid crop window {},{},{},{}, expected a non empty window with fractions between 0 and 1",
+                        x0, y0, x1, y1,
+                    ));
+                }
+                options.region = Some(RenderRegion::Window { x0, y0, x1, y1 });
+            },
+            "--crop" => options.crop = true,
+            "--frames" => {
+                options.frames = Some(frames(&value::<String>(&arg, args.next())?)?);
+            },
+            "--sampler" => {
+                let name = value::<String>(&arg, args.next())?;
+                options.sampler = SamplerKind::from_name(&name).ok_or_else(|| {
+                    let names: Vec<_> = SamplerKind::ALL.iter().map(|kind| kind.name()).collect();
+                    format!("unknown sampler {}, expected one of {}", name, names.join(", "))
+                })?;
+            },
+            "--filter" => {
+                let name = value::<String>(&arg, args.next())?;
+                options.filter = FilterKind::from_name(&name).ok_or_else(|| {
+                    let names: Vec<_> = FilterKind::ALL.iter().map(|kind| kind.name()).collect();
+                    format!("unknown filter {}, expected one of {}", name, names.join(", "))
+                })?;
+            },
+            "--coordinator" => options.coordinator = Some(value(&arg, args.next())?),
+            "--worker" => options.worker = Some(value(&arg, args.next())?),
+            "--preview" => options.preview = Some(value(&arg, args.next())?),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+
+    if options.coordinator.is_some() && options.worker.is_some() {
+        return Err("--coordinator and --worker can't be given together".to_string());
+    }
+    if options.preview.is_some() && (options.coordinator.is_some() || options.worker.is_some()) {
+        return Err("--preview renders on its own, without --coordinator or --worker".to_string());
+    }
+    // the white point alone picks reinhard, whatever the order of the options
+    options.post.tone_map = match (tone_map, reinhard_white) {
+        (None, None) => options.post.tone_map,
+        (Some(tone_map), None) => tone_map,
+        (None, Some(white)) | (Some(ToneMap::Reinhard { .. }), Some(white)) => ToneMap::Reinhard { white: Some(white) },
+        (Some(_), Some(_)) => return Err("--reinhard-white only applies to the reinhard tone map".to_string()),
+    };
+    if let Some(aberration) = aberration {
+        options.post = options.post.with_effect(aberration);
+    }
+    if let Some(bloom) = bloom {
+        options.post = options.post.with_effect(bloom);
+    }
+    if let Some(vignette) = vignette {
+        options.post = options.post.with_effect(vignette);
+    }
+    Ok(options)
+}
+
+fn denoiser(options: &mut Options) -> &mut Denoiser {
+    options.denoise.get_or_insert_with(Denoiser::default)
+}
+
+fn aovs(names: &str) -> Result<Vec<Aov>, String> {
+    if names == "all" {
+        return Ok(Aov::ALL.to_vec());
+    }
+    names.split(',')
+        .map(|name| Aov::from_name(name).ok_or_else(|| format!("unknown AOV {}", name)))
+        .collect()
+}
+
+fn color_space(name: &str) -> Result<ColorSpace, String> {
+    ColorSpace::from_name(name).ok_or_else(|| format!("unknown color space {}", name))
+}
+
+// a single frame or an inclusive range
+fn frames(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let invalid = || format!("invalid frames {}, expected a frame or first-last", value);
+    let frame = |v: &str| v.trim().parse::<usize>().map_err(|_| invalid());
+    let (first, last) = match value.split_once('-') {
+        Some((first, last)) => (frame(first)?, frame(last)?),
+        None => (frame(value)?, frame(value)?),
+    };
+    if first > last {
+        return Err(invalid());
+    }
+    Ok(first..=last)
+}
+
+// four comma separated values
+fn list<T: FromStr + Copy>(option: &str, value: Option<String>) -> Result<[T; 4], String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", option))?;
+    let values = value.split(',').map(|v| v.trim().parse().ok()).collect::<Option<Vec<T>>>();
+    match values.as_deref() {
+        Some(&[a, b, c, d]) => Ok([a, b, c, d]),
+        _ => Err(format!("invalid value {} for {}, expected four comma separated numbers", value, option)),
+    }
+}
+
+// a value strictly above zero, used as a divisor
+fn positive(option: &str, value: Option<String>) -> Result<f32, String> {
+    let value: f32 = self::value(option, value)?;
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("invalid value {} for {}, expected a positive number", value, option))
+    }
+}
+
+fn value<T: FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", option))?;
+    value.parse().map_err(|_| format!("invalid value {} for {}", value, option))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_region(option: &str, value: &str) -> Result<Option<RenderRegion>, String> {
+        let args = vec![option.to_string(), value.to_string()];
+        parse(args.into_iter(), 320, 180).map(|options| options.region)
+    }
+
+    #[test]
+    fn region_inside_the_image() {
+        assert_eq!(parse_region("--region", "0,0,320,180"), Ok(Some(RenderRegion::Pixels(Bounds::new(0, 0, 320, 180)))));
+        assert_eq!(
+            parse_region("--crop-window", "0.25,0,1,0.5"),
+            Ok(Some(RenderRegion::Window { x0: 0.25, y0: 0.0, x1: 1.0, y1: 0.5 })),
+        );
+    }
+
+    fn parse_all(args: &[&str]) -> Result<Options, String> {
+        parse(args.iter().map(|arg| arg.to_string()), 320, 180)
+    }
+
+    #[test]
+    fn denoise_sigmas_must_be_positive() {
+        let options = parse_all(&["--denoise-sigma-color", "0.25"]).unwrap();
+        assert_eq!(options.denoise.unwrap().sigma_color, 0.25);
+        for option in &["--denoise-sigma-spatial", "--denoise-sigma-color", "--denoise-sigma-albedo", "--denoise-sigma-normal"] {
+            for value in &["0", "-1", "NaN", "inf"] {
+                assert!(parse_all(&[option, value]).is_err(), "{} {}", option, value);
+            }
+        }
+    }
+
+    #[test]
+    fn reinhard_white_is_kept_whatever_the_order() {
+        let white = ToneMap::Reinhard { white: Some(4.0) };
+        assert_eq!(parse_all(&["--tone-map", "reinhard", "--reinhard-white", "4"]).unwrap().post.tone_map, white);
+        assert_eq!(parse_all(&["--reinhard-white", "4", "--tone-map", "reinhard"]).unwrap().post.tone_map, white);
+        assert_eq!(parse_all(&["--reinhard-white", "4"]).unwrap().post.tone_map, white);
+        assert_eq!(parse_all(&["--tone-map", "reinhard"]).unwrap().post.tone_map, ToneMap::Reinhard { white: None });
+        assert_eq!(parse_all(&["--tone-map", "reinhard", "--tone-map", "aces"]).unwrap().post.tone_map, ToneMap::Aces);
+        assert!(parse_all(&["--reinhard-white", "4", "--tone-map", "aces"]).is_err());
+        assert!(parse_all(&["--tone-map", "agx", "--reinhard-white", "4"]).is_err());
+        assert!(parse_all(&["--reinhard-white", "0"]).is_err());
+    }
+
+    #[test]
+    fn transfer_gamma_must_be_positive() {
+        assert_eq!(parse_all(&["--transfer", "2.2"]).unwrap().post.transfer, Transfer::Gamma(2.2));
+        assert_eq!(parse_all(&["--transfer", "linear"]).unwrap().post.transfer, Transfer::Linear);
+        for value in &["0", "-2.2", "NaN", "inf", "gamma"] {
+            assert!(parse_all(&["--transfer", value]).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn empty_reversed_or_outside_region_is_refused() {
+        for value in &["10,10,10,20", "10,20,20,10", "30,0,20,10", "0,0,321,180", "0,0,320,181"] {
+            assert!(parse_region("--region", value).is_err(), "{}", value);
+        }
+        for value in &["0.5,0,0.5,1", "0,0.6,1,0.4", "-0.1,0,1,1", "0,0,1.5,1", "0,0,1,NaN"] {
+            assert!(parse_region("--crop-window", value).is_err(), "{}", value);
+        }
+    }
+}
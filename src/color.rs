@@ -53,7 +53,31 @@ impl Mul<Vector> for Color {
     }
 }
 
-#[derive(Debug, Clone)]
+// 8 bit color with coverage, the color being multiplied by the alpha or not
+// following the alpha mode of the post process that developed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgba {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl Rgba {
+    pub fn new(red: u8, green: u8, blue: u8, alpha: u8) -> Rgba {
+        Rgba { red, green, blue, alpha }
+    }
+
+    pub fn from_color(color: Color, alpha: u8) -> Rgba {
+        Rgba::new(color.red, color.green, color.blue, alpha)
+    }
+
+    pub fn color(self) -> Color {
+        Color::new(self.red, self.green, self.blue)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct ColorAverager {
     red: u64,
     green: u64,
@@ -63,12 +87,7 @@ pub struct ColorAverager {
 
 impl ColorAverager {
     pub fn new() -> ColorAverager {
-        ColorAverager {
-            red: 0,
-            green: 0,
-            blue: 0,
-            counter: 0,
-        }
+        ColorAverager::default()
     }
 
     pub fn average(&self) -> Color {
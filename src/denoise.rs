@@ -0,0 +1,261 @@
+use std::thread;
+
+use crate::math::Vector;
+use crate::film::{luminance, Bounds, Features, Film};
+
+// joint bilateral filter guided by the albedo and normal of the first visible
+// surfaces: the lighting is divided by the albedo before filtering so textures
+// stay sharp, and neighbours only count when they look like the same surface
+#[derive(Debug, Clone)]
+pub struct Denoiser {
+    // half size of the window, in pixels
+    pub radius: usize,
+    pub sigma_spatial: f32,
+    // relative difference of the lighting
+    pub sigma_color: f32,
+    pub sigma_albedo: f32,
+    // one minus the cosine between the normals, the sky having a zero normal
+    pub sigma_normal: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Denoiser {
+        Denoiser {
+            radius: 6,
+            sigma_spatial: 3.0,
+            sigma_color: 0.5,
+            sigma_albedo: 0.1,
+            sigma_normal: 0.1,
+        }
+    }
+}
+
+const MIN_ALBEDO: f32 = 0.01;
+
+fn demodulate(radiance: Vector, albedo: Vector) -> Vector {
+    Vector::new(
+        radiance.x / albedo.x.max(MIN_ALBEDO),
+        radiance.y / albedo.y.max(MIN_ALBEDO),
+        radiance.z / albedo.z.max(MIN_ALBEDO),
+    )
+}
+
+fn remodulate(irradiance: Vector, albedo: Vector) -> Vector {
+    Vector::new(
+        irradiance.x * albedo.x.max(MIN_ALBEDO),
+        irradiance.y * albedo.y.max(MIN_ALBEDO),
+        irradiance.z * albedo.z.max(MIN_ALBEDO),
+    )
+}
+
+fn abs(v: Vector) -> Vector {
+    Vector::new(v.x.abs(), v.y.abs(), v.z.abs())
+}
+
+impl Denoiser {
+    // only the pixels of `region` are filtered, and only with each other, the
+    // ones outside of it having never been rendered
+    pub fn denoise(&self, film: &Film, region: Bounds) -> Film {
+        let region = region.intersect(film.bounds());
+        if region.area() == 0 {
+            return film.clone();
+        }
+        let filtered = self.denoise_all(&film.crop(region));
+        let mut denoised = film.clone();
+        for y in 0..region.height() {
+            for x in 0..region.width() {
+                denoised.set_pixel(region.x0 + x, region.y0 + y, filtered.get_pixel(x, y));
+            }
+        }
+        denoised
+    }
+
+    fn denoise_all(&self, film: &Film) -> Film {
+        let (width, height) = film.get_dimensions();
+        let features: Vec<Features> = (0..width * height).map(|i| film.get_features(i % width, i / width)).collect();
+        let irradiance: Vec<Vector> = (0..width * height)
+            .map(|i| demodulate(film.get_pixel(i % width, i / width), features[i].albedo))
+            .collect();
+        let guide = box_blur(&irradiance, width, height);
+
+        let mut filtered = vec![Vector::zero(); width * height];
+        let threads = thread::available_parallelism().map_or(4, |n| n.get());
+        let rows_per_chunk = height.div_ceil(threads).max(1);
+        thread::scope(|scope| {
+            for (chunk, rows) in filtered.chunks_mut(rows_per_chunk * width).enumerate() {
+                let (features, irradiance, guide) = (&features, &irradiance, &guide);
+                scope.spawn(move || {
+                    for (i, out) in rows.iter_mut().enumerate() {
+                        let index = chunk * rows_per_chunk * width + i;
+                        *out = self.filter_pixel(index % width, index / width, width, height, features, irradiance, guide);
+                    }
+                });
+            }
+        });
+
+        let mut denoised = film.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let albedo = features[y * width + x].albedo;
+                denoised.set_pixel(x, y, remodulate(filtered[y * width + x], albedo));
+            }
+        }
+        denoised
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn filter_pixel(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        features: &[Features],
+        irradiance: &[Vector],
+        guide: &[Vector],
+    ) -> Vector {
+        let center = y * width + x;
+        let (albedo, normal, color) = (features[center].albedo, features[center].normal, guide[center]);
+        let radius = self.radius as isize;
+
+        let mut sum = Vector::zero();
+        let mut total = 0.0;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let (qx, qy) = (x as isize + dx, y as isize + dy);
+                if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                    continue;
+                }
+                let q = qy as usize * width + qx as usize;
+
+                let spatial = (dx * dx + dy * dy) as f32 / (2.0 * self.sigma_spatial * self.sigma_spatial);
+                let color_distance = luminance(abs(guide[q] - color)) / (luminance(color).max(luminance(guide[q])) + 1e-2);
+                let color = color_distance * color_distance / (2.0 * self.sigma_color * self.sigma_color);
+                let albedo_distance = features[q].albedo - albedo;
+                let albedo = albedo_distance.dot(albedo_distance) / (2.0 * self.sigma_albedo * self.sigma_albedo);
+                let normal_distance = (features[q].normal - normal).dot(features[q].normal - normal) / 2.0;
+                let normal = normal_distance * normal_distance / (2.0 * self.sigma_normal * self.sigma_normal);
+
+                let weight = (-(spatial + color + albedo + normal)).exp();
+                sum = sum + irradiance[q] * weight;
+                total += weight;
+            }
+        }
+
+        // the center pixel always has a weight of one
+        sum / total
+    }
+}
+
+// 3x3 average, taking the edge of the image into account
+fn box_blur(values: &[Vector], width: usize, height: usize) -> Vec<Vector> {
+    let mut blurred = Vec::with_capacity(values.len());
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Vector::zero();
+            let mut count = 0.0;
+            for qy in y.saturating_sub(1)..(y + 2).min(height) {
+                for qx in x.saturating_sub(1)..(x + 2).min(width) {
+                    sum = sum + values[qy * width + qx];
+                    count += 1.0;
+                }
+            }
+            blurred.push(sum / count);
+        }
+    }
+    blurred
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+    use crate::film::{FilmSample, FilmTile};
+    use crate::filter::FilterKind;
+
+    const SIZE: usize = 24;
+
+    // one sample per pixel of `region`, noisy around `radiance(x)`, seeing
+    // what `features(x)` describes
+    fn noisy_film(region: Bounds, radiance: impl Fn(usize) -> f32, features: impl Fn(usize) -> Features) -> Film {
+        let mut rng = XorShiftRng::from_seed([9; 16]);
+        let filter = FilterKind::Box.build();
+        let mut tile = FilmTile::new(region, false);
+        for y in region.y0..region.y1 {
+            for x in region.x0..region.x1 {
+                let value = radiance(x) * rng.gen_range(0.5, 1.5);
+                let sample = FilmSample { radiance: Vector::new(value, value, value), alpha: 1.0, direct: Vector::zero(), features: features(x) };
+                tile.add_sample(x as f32 + 0.5, y as f32 + 0.5, &sample, filter.as_ref());
+            }
+        }
+        let mut film = Film::new(SIZE, SIZE);
+        film.merge_tile(&tile);
+        film
+    }
+
+    // mean and standard deviation of the pixels of columns `columns` in `region`
+    fn stats(film: &Film, region: Bounds, columns: std::ops::Range<usize>) -> (f32, f32) {
+        let values: Vec<f32> = (region.y0..region.y1)
+            .flat_map(|y| columns.clone().map(move |x| (x, y)))
+            .map(|(x, y)| film.get_pixel(x, y).x)
+            .collect();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / values.len() as f32;
+        (mean, variance.sqrt())
+    }
+
+    // wall whose albedo changes at x = SIZE / 2, lit evenly
+    fn wall(x: usize) -> f32 {
+        if x < SIZE / 2 { 0.8 } else { 0.2 }
+    }
+
+    fn wall_features(x: usize) -> Features {
+        Features {
+            albedo: Vector::new(wall(x), wall(x), wall(x)),
+            normal: Vector::new(0.0, 0.0, 1.0),
+            ..Features::zero()
+        }
+    }
+
+    // dark sky, with the zero normal of the sky, as unrendered pixels have
+    fn sky_features(_: usize) -> Features {
+        Features { albedo: Vector::new(0.02, 0.02, 0.02), ..Features::zero() }
+    }
+
+    #[test]
+    fn noise_is_smoothed_and_edges_kept() {
+        let whole = Bounds::new(0, 0, SIZE, SIZE);
+        let film = noisy_film(whole, wall, wall_features);
+        let denoised = Denoiser::default().denoise(&film, whole);
+        for (columns, expected) in [(0..SIZE / 2, 0.8), (SIZE / 2..SIZE, 0.2)] {
+            let (noisy_mean, noise) = stats(&film, whole, columns.clone());
+            let (mean, remaining) = stats(&denoised, whole, columns.clone());
+            assert!(remaining < noise / 3.0, "noise went from {} to {}", noise, remaining);
+            assert!((mean - noisy_mean).abs() < 0.02 * expected, "mean went from {} to {}", noisy_mean, mean);
+        }
+        // the columns on each side of the edge keep their own level
+        let (left, _) = stats(&denoised, whole, SIZE / 2 - 1..SIZE / 2);
+        let (right, _) = stats(&denoised, whole, SIZE / 2..SIZE / 2 + 1);
+        assert!((left - 0.8).abs() < 0.08 && (right - 0.2).abs() < 0.02, "edge blurred to {} and {}", left, right);
+    }
+
+    #[test]
+    fn unrendered_pixels_stay_out_of_the_region() {
+        let region = Bounds::new(4, 6, 10, 18);
+        let film = noisy_film(region, |_| 0.5, sky_features);
+        let denoised = Denoiser::default().denoise(&film, region);
+        // the border of the region isn't darkened by the black around it
+        let (border, _) = stats(&denoised, region, region.x0..region.x0 + 1);
+        let (inside, _) = stats(&film, region, region.x0..region.x1);
+        assert!((border - inside).abs() < 0.05, "border {} against {}", border, inside);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                if !region.contains(x, y) {
+                    assert_eq!(denoised.get_pixel(x, y), Vector::zero());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,565 @@
+use std::collections::VecDeque;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::Range;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::checkpoint::bytes;
+use crate::film::{self, Bounds, FilmTile};
+use crate::filter::FilterKind;
+use crate::sampler::SamplerKind;
+
+// rendering split between machines: a coordinator hands out the samples of
+// a tile as jobs to the workers connected to it, which render them with the
+// same scene and seed and send back the tile, merged by the coordinator in
+// the order of the jobs so the image is the one a single machine gives
+
+const MAGIC: &[u8; 4] = b"RTDS";
+const VERSION: u32 = 3;
+
+// how long the coordinator waits for a result before rendering the jobs
+// itself when no worker is connected
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// messages of the coordinator
+const JOB: u32 = 0;
+const FINISHED: u32 = 1;
+const REJECTED: u32 = 2;
+
+// what a worker has to share with the coordinator to render the same image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handshake {
+    pub seed: u32,
+    pub width: usize,
+    pub height: usize,
+    pub sampler: SamplerKind,
+    pub filter: FilterKind,
+    // samples per pixel of the finished render, which the samplers depend on
+    pub max_samples: usize,
+    pub spectral: bool,
+}
+
+impl Handshake {
+    fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        bytes::write_u32(w, VERSION)?;
+        bytes::write_u32(w, self.seed)?;
+        bytes::write_u64(w, self.width as u64)?;
+        bytes::write_u64(w, self.height as u64)?;
+        bytes::write_name(w, self.sampler.name())?;
+        bytes::write_name(w, self.filter.name())?;
+        bytes::write_u64(w, self.max_samples as u64)?;
+        bytes::write_bool(w, self.spectral)
+    }
+
+    fn read_from(r: &mut dyn Read) -> io::Result<Handshake> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC || bytes::read_u32(r)? != VERSION {
+            return Err(invalid("not a worker or unsupported version"));
+        }
+        Ok(Handshake {
+            seed: bytes::read_u32(r)?,
+            width: bytes::read_u64(r)? as usize,
+            height: bytes::read_u64(r)? as usize,
+            sampler: SamplerKind::from_name(&bytes::read_name(r)?).ok_or_else(|| invalid("unknown sampler"))?,
+            filter: FilterKind::from_name(&bytes::read_name(r)?).ok_or_else(|| invalid("unknown filter"))?,
+            max_samples: bytes::read_u64(r)? as usize,
+            spectral: bytes::read_bool(r)?,
+        })
+    }
+}
+
+// samples to take in the pixels of `bounds`, splatted on an empty tile
+// covering `tile`, the view being the eye of a stereo render
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: usize,
+    pub frame: usize,
+    pub view: usize,
+    pub bounds: Bounds,
+    pub tile: Bounds,
+    pub aovs: bool,
+    pub transparent: bool,
+    pub samples: Range<usize>,
+    // pixels of `bounds` still needing samples, row by row
+    pub active: Vec<bool>,
+}
+
+impl Job {
+    pub fn is_active(&self, x: usize, y: usize) -> bool {
+        self.active[(y - self.bounds.y0) * self.bounds.width() + x - self.bounds.x0]
+    }
+
+    fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        bytes::write_u64(w, self.id as u64)?;
+        bytes::write_u64(w, self.frame as u64)?;
+        bytes::write_u64(w, self.view as u64)?;
+        film::write_bounds(w, self.bounds)?;
+        film::write_bounds(w, self.tile)?;
+        bytes::write_u32(w, self.aovs as u32)?;
+        bytes::write_u32(w, self.transparent as u32)?;
+        bytes::write_u64(w, self.samples.start as u64)?;
+        bytes::write_u64(w, self.samples.end as u64)?;
+        let active: Vec<u8> = self.active.iter().map(|&a| a as u8).collect();
+        w.write_all(&active)
+    }
+
+    // the bounds have to lie in the tile, itself in the image of the
+    // handshake, before anything is allocated for them
+    fn read_from(r: &mut dyn Read, handshake: &Handshake) -> io::Result<Job> {
+        let id = bytes::read_u64(r)? as usize;
+        let frame = bytes::read_u64(r)? as usize;
+        let view = bytes::read_u64(r)? as usize;
+        let bounds = film::read_bounds(r)?;
+        let tile = film::read_bounds(r)?;
+        let image = Bounds::new(0, 0, handshake.width, handshake.height);
+        if !image.encloses(tile) || !tile.encloses(bounds) {
+            return Err(invalid("job bounds outside of the image"));
+        }
+        let aovs = bytes::read_u32(r)? != 0;
+        let transparent = bytes::read_u32(r)? != 0;
+        let samples = bytes::read_u64(r)? as usize..bytes::read_u64(r)? as usize;
+        if samples.start > samples.end || samples.end > handshake.max_samples {
+            return Err(invalid("invalid job samples"));
+        }
+        let mut active = vec![0; bounds.area()];
+        r.read_exact(&mut active)?;
+        let active = active.into_iter().map(|a| a != 0).collect();
+        Ok(Job { id, frame, view, bounds, tile, aovs, transparent, samples, active })
+    }
+}
+
+// tile rendered by a worker for the job of the same id
+#[derive(Debug, Clone)]
+pub struct JobResult {
+    pub id: usize,
+    // number of samples taken
+    pub taken: usize,
+    pub tile: FilmTile,
+}
+
+impl JobResult {
+    fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        bytes::write_u64(w, self.id as u64)?;
+        bytes::write_u64(w, self.taken as u64)?;
+        self.tile.write_to(w)
+    }
+
+    // the result of `job`, anything else being invalid
+    fn read_from(r: &mut dyn Read, job: &Job) -> io::Result<JobResult> {
+        let id = bytes::read_u64(r)? as usize;
+        if id != job.id {
+            return Err(invalid("result of another job"));
+        }
+        let taken = bytes::read_u64(r)? as usize;
+        let tile = FilmTile::read_from(r, job.tile)?;
+        Ok(JobResult { id, taken, tile })
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// jobs waiting for a worker
+#[derive(Debug, Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    finished: bool,
+    // connected workers that weren't told yet that the render is over
+    workers: usize,
+}
+
+type SharedQueue = Arc<(Mutex<Queue>, Condvar)>;
+
+// accepts workers in the background, at any time, each of them being served
+// by its own thread taking jobs from the queue one at a time, the job of a
+// worker going away going back to the queue
+pub struct Coordinator {
+    queue: SharedQueue,
+    results: Receiver<JobResult>,
+    address: SocketAddr,
+    timeout: Duration,
+}
+
+impl Coordinator {
+    pub fn bind<A: ToSocketAddrs>(address: A, handshake: Handshake) -> io::Result<Coordinator> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let queue = SharedQueue::default();
+        let (sender, results) = mpsc::channel();
+
+        let accepted = queue.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        eprintln!("Error accepting a worker: {}", err);
+                        continue;
+                    },
+                };
+                let (queue, sender) = (accepted.clone(), sender.clone());
+                thread::spawn(move || {
+                    let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                    match serve(stream, handshake, &queue, &sender) {
+                        Ok(()) => {},
+                        Err(err) => eprintln!("Worker {} left: {}", peer, err),
+                    }
+                });
+            }
+        });
+
+        Ok(Coordinator { queue, results, address, timeout: DEFAULT_TIMEOUT })
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Coordinator {
+        self.timeout = timeout;
+        self
+    }
+
+    // where the workers connect, with the port picked when binding to port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    // hands the jobs out and waits for all of them, the results coming back
+    // in the order of the ids, the jobs still queued going to
+    // `render_locally` whenever no result came for the timeout and no worker
+    // is there to take them
+    pub fn run<F>(&self, jobs: Vec<Job>, render_locally: F) -> Vec<JobResult>
+        where F: Fn(Vec<Job>) -> Vec<JobResult>
+    {
+        let count = jobs.len();
+        {
+            let (queue, available) = &*self.queue;
+            queue.lock().unwrap().jobs.extend(jobs);
+            available.notify_all();
+        }
+
+        let mut results = Vec::with_capacity(count);
+        while results.len() < count {
+            match self.results.recv_timeout(self.timeout) {
+                Ok(result) => results.push(result),
+                Err(_) => {
+                    let unassigned: Vec<Job> = {
+                        let mut queue = self.queue.0.lock().unwrap();
+                        if queue.workers == 0 { queue.jobs.drain(..).collect() } else { Vec::new() }
+                    };
+                    if !unassigned.is_empty() {
+                        results.extend(render_locally(unassigned));
+                    }
+                },
+            }
+        }
+        results.sort_by_key(|result| result.id);
+        results
+    }
+}
+
+impl Drop for Coordinator {
+    // the workers are told there is nothing left once they ask for more
+    fn drop(&mut self) {
+        let (queue, available) = &*self.queue;
+        let mut queue = queue.lock().unwrap();
+        queue.finished = true;
+        available.notify_all();
+        while queue.workers > 0 {
+            queue = available.wait(queue).unwrap();
+        }
+    }
+}
+
+fn serve(stream: TcpStream, handshake: Handshake, queue: &SharedQueue, results: &Sender<JobResult>) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    let theirs = Handshake::read_from(&mut reader)?;
+    if theirs != handshake {
+        bytes::write_u32(&mut writer, REJECTED)?;
+        writer.flush()?;
+        return Err(invalid("seed, image size, sampler, filter, samples or spectral mode differ from the coordinator"));
+    }
+    println!("Worker {} joined", writer.get_ref().peer_addr()?);
+    let _joined = Joined::new(queue);
+
+    loop {
+        let job = {
+            let (queue, available) = &**queue;
+            let mut queue = queue.lock().unwrap();
+            loop {
+                if let Some(job) = queue.jobs.pop_front() {
+                    break job;
+                }
+                if queue.finished {
+                    bytes::write_u32(&mut writer, FINISHED)?;
+                    return writer.flush();
+                }
+                queue = available.wait(queue).unwrap();
+            }
+        };
+
+        // back to the queue unless its result comes, whatever happens
+        let pending = Pending::new(queue, job);
+        bytes::write_u32(&mut writer, JOB)?;
+        pending.job().write_to(&mut writer)?;
+        writer.flush()?;
+        let result = JobResult::read_from(&mut reader, pending.job())?;
+        pending.done();
+        // the coordinator going away leaves nobody to send it to
+        if results.send(result).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+// job handed to a worker, going back to the front of the queue when dropped
+// before its result came, even on a panic
+struct Pending<'a> {
+    queue: &'a SharedQueue,
+    job: Option<Job>,
+}
+
+impl<'a> Pending<'a> {
+    fn new(queue: &'a SharedQueue, job: Job) -> Pending<'a> {
+        Pending { queue, job: Some(job) }
+    }
+
+    fn job(&self) -> &Job {
+        self.job.as_ref().unwrap()
+    }
+
+    fn done(mut self) {
+        self.job = None;
+    }
+}
+
+impl<'a> Drop for Pending<'a> {
+    fn drop(&mut self) {
+        if let Some(job) = self.job.take() {
+            let (queue, available) = &**self.queue;
+            queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).jobs.push_front(job);
+            available.notify_one();
+        }
+    }
+}
+
+// counts a worker as connected while it lives
+struct Joined<'a>(&'a SharedQueue);
+
+impl<'a> Joined<'a> {
+    fn new(queue: &'a SharedQueue) -> Joined<'a> {
+        queue.0.lock().unwrap().workers += 1;
+        Joined(queue)
+    }
+}
+
+impl<'a> Drop for Joined<'a> {
+    fn drop(&mut self) {
+        let (queue, available) = &**self.0;
+        queue.lock().unwrap().workers -= 1;
+        available.notify_all();
+    }
+}
+
+// connection of a worker to the coordinator
+pub struct Worker {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    handshake: Handshake,
+}
+
+impl Worker {
+    pub fn connect<A: ToSocketAddrs>(address: A, handshake: Handshake) -> io::Result<Worker> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        handshake.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(Worker { reader, writer, handshake })
+    }
+
+    // None once the coordinator is done
+    pub fn next_job(&mut self) -> io::Result<Option<Job>> {
+        match bytes::read_u32(&mut self.reader)? {
+            JOB => Ok(Some(Job::read_from(&mut self.reader, &self.handshake)?)),
+            FINISHED => Ok(None),
+            REJECTED => Err(invalid("rejected by the coordinator, its seed, image size, sampler, filter, samples or spectral mode differ")),
+            _ => Err(invalid("unknown message")),
+        }
+    }
+
+    pub fn send(&mut self, result: &JobResult) -> io::Result<()> {
+        result.write_to(&mut self.writer)?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HANDSHAKE: Handshake = Handshake {
+        seed: 3,
+        width: 8,
+        height: 6,
+        sampler: SamplerKind::Halton,
+        filter: FilterKind::Mitchell,
+        max_samples: 8,
+        spectral: true,
+    };
+
+    fn job() -> Job {
+        let bounds = Bounds::new(2, 1, 5, 4);
+        Job {
+            id: 7,
+            frame: 2,
+            view: 1,
+            bounds,
+            tile: Bounds::new(1, 0, 6, 5),
+            aovs: true,
+            transparent: true,
+            samples: 4..6,
+            active: (0..bounds.area()).map(|i| i % 3 != 0).collect(),
+        }
+    }
+
+    fn result() -> JobResult {
+        JobResult { id: 7, taken: 12, tile: FilmTile::new(Bounds::new(1, 0, 6, 5), true) }
+    }
+
+    fn encoded(write: impl Fn(&mut Vec<u8>) -> io::Result<()>) -> Vec<u8> {
+        let mut data = Vec::new();
+        write(&mut data).unwrap();
+        data
+    }
+
+    // every message cut short somewhere fails to read
+    fn truncations<T>(data: &[u8], read: impl Fn(&mut &[u8]) -> io::Result<T>) {
+        for len in 0..data.len() {
+            assert!(read(&mut &data[..len]).is_err(), "read {} of {} bytes", len, data.len());
+        }
+    }
+
+    #[test]
+    fn handshake_round_trip() {
+        let data = encoded(|w| HANDSHAKE.write_to(w));
+        assert_eq!(Handshake::read_from(&mut &data[..]).unwrap(), HANDSHAKE);
+        truncations(&data, |r| Handshake::read_from(r));
+    }
+
+    #[test]
+    fn handshake_with_an_invalid_flag_is_invalid() {
+        let mut data = encoded(|w| HANDSHAKE.write_to(w));
+        *data.last_mut().unwrap() = 2;
+        assert_eq!(Handshake::read_from(&mut &data[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn workers_of_another_render_are_rejected() {
+        let coordinator = Coordinator::bind("127.0.0.1:0", HANDSHAKE).unwrap();
+        let address = coordinator.local_addr();
+        let others = [
+            Handshake { max_samples: 16, ..HANDSHAKE },
+            Handshake { spectral: false, ..HANDSHAKE },
+            Handshake { seed: 4, ..HANDSHAKE },
+        ];
+        for other in &others {
+            let err = Worker::connect(address, *other).and_then(|mut worker| worker.next_job()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", other);
+        }
+        // while the same render is told when it's over
+        let mut worker = Worker::connect(address, HANDSHAKE).unwrap();
+        drop(coordinator);
+        assert!(worker.next_job().unwrap().is_none());
+    }
+
+    #[test]
+    fn jobs_are_rendered_locally_without_workers() {
+        let coordinator = Coordinator::bind("127.0.0.1:0", HANDSHAKE).unwrap().with_timeout(Duration::from_millis(10));
+        let jobs = vec![Job { id: 3, ..job() }, Job { id: 1, ..job() }, Job { id: 2, ..job() }];
+        let results = coordinator.run(jobs, |jobs| {
+            jobs.iter().map(|job| JobResult { id: job.id, taken: 10 * job.id, ..result() }).collect()
+        });
+        let ids: Vec<_> = results.iter().map(|result| (result.id, result.taken)).collect();
+        assert_eq!(ids, [(1, 10), (2, 20), (3, 30)]);
+    }
+
+    #[test]
+    fn job_round_trip() {
+        let job = job();
+        let data = encoded(|w| job.write_to(w));
+        let read = Job::read_from(&mut &data[..], &HANDSHAKE).unwrap();
+        assert_eq!((read.id, read.frame, read.view), (7, 2, 1));
+        assert_eq!((read.bounds, read.tile), (job.bounds, job.tile));
+        assert_eq!((read.aovs, read.transparent, read.samples.clone()), (true, true, 4..6));
+        assert_eq!(read.active, job.active);
+        truncations(&data, |r| Job::read_from(r, &HANDSHAKE));
+    }
+
+    #[test]
+    fn job_result_round_trip() {
+        let result = result();
+        let data = encoded(|w| result.write_to(w));
+        let read = JobResult::read_from(&mut &data[..], &job()).unwrap();
+        assert_eq!((read.id, read.taken, read.tile.bounds()), (7, 12, result.tile.bounds()));
+        assert_eq!(encoded(|w| read.write_to(w)), data);
+        truncations(&data, |r| JobResult::read_from(r, &job()));
+    }
+
+    #[test]
+    fn job_outside_of_the_image_or_the_samples_is_invalid() {
+        let huge = Bounds::new(0, 0, usize::MAX / 2, usize::MAX / 2);
+        let jobs = [
+            Job { bounds: huge, tile: huge, active: Vec::new(), ..job() },
+            Job { tile: Bounds::new(1, 0, 9, 5), ..job() },
+            Job { bounds: Bounds::new(0, 1, 5, 4), ..job() },
+            Job { bounds: Bounds::new(5, 1, 2, 4), active: Vec::new(), ..job() },
+            Job { samples: 6..9, ..job() },
+        ];
+        for job in &jobs {
+            let data = encoded(|w| job.write_to(w));
+            let err = Job::read_from(&mut &data[..], &HANDSHAKE).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", job.bounds);
+        }
+    }
+
+    #[test]
+    fn result_of_another_tile_or_job_is_invalid() {
+        let results = [
+            JobResult { tile: FilmTile::new(Bounds::new(0, 0, 8, 6), false), ..result() },
+            JobResult { id: 8, ..result() },
+        ];
+        for result in &results {
+            let data = encoded(|w| result.write_to(w));
+            let err = JobResult::read_from(&mut &data[..], &job()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn pending_job_goes_back_to_the_queue() {
+        let queue = SharedQueue::default();
+        let queued = |queue: &SharedQueue| queue.0.lock().unwrap().jobs.iter().map(|job| job.id).collect::<Vec<_>>();
+
+        Pending::new(&queue, job()).done();
+        assert!(queued(&queue).is_empty());
+
+        drop(Pending::new(&queue, job()));
+        assert_eq!(queued(&queue), [7]);
+        queue.0.lock().unwrap().jobs.clear();
+
+        let panicked = std::panic::catch_unwind(|| {
+            let _pending = Pending::new(&queue, job());
+            panic!("serving the worker");
+        });
+        assert!(panicked.is_err());
+        assert_eq!(queued(&queue), [7]);
+    }
+}
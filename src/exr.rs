@@ -0,0 +1,207 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// uncompressed scanline OpenEXR file with 32 bit float channels, layers being
+// channels named `layer.channel`
+pub fn write_exr<P: AsRef<Path>>(path: P, width: usize, height: usize, channels: &[(String, Vec<f32>)]) -> io::Result<()> {
+    for (name, values) in channels {
+        if values.len() != width * height {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("channel {} has the wrong size", name)));
+        }
+    }
+
+    // readers expect the channels in alphabetical order
+    let mut channels: Vec<&(String, Vec<f32>)> = channels.iter().collect();
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]);
+    header.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut chlist = Vec::new();
+    for (name, _) in &channels {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        // float pixels, not linear, reserved, no subsampling
+        chlist.extend_from_slice(&2i32.to_le_bytes());
+        chlist.extend_from_slice(&[0, 0, 0, 0]);
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+    attribute(&mut header, "channels", "chlist", &chlist);
+
+    attribute(&mut header, "compression", "compression", &[0]);
+    let mut window = Vec::new();
+    for &v in &[0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&i32::to_le_bytes(v));
+    }
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    let line_size = width * 4 * channels.len();
+    let first_line = header.len() + height * 8;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&header)?;
+    for y in 0..height {
+        let offset = first_line + y * (8 + line_size);
+        writer.write_all(&(offset as u64).to_le_bytes())?;
+    }
+    for y in 0..height {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_size as i32).to_le_bytes())?;
+        for (_, values) in &channels {
+            for &v in &values[y * width..(y + 1) * width] {
+                writer.write_all(&v.to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::convert::TryInto;
+    use std::fs;
+
+    use super::*;
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    fn name_at(data: &[u8], at: usize) -> (String, usize) {
+        let end = at + data[at..].iter().position(|&b| b == 0).unwrap();
+        (String::from_utf8(data[at..end].to_vec()).unwrap(), end + 1)
+    }
+
+    // attributes of the header, by name with their type and value, and the offset past it
+    fn header(data: &[u8]) -> (Vec<(String, String, Vec<u8>)>, usize) {
+        assert_eq!(&data[..4], &[0x76, 0x2f, 0x31, 0x01], "magic number");
+        assert_eq!(u32_at(data, 4), 2, "version, single part scanline");
+        let mut attributes = Vec::new();
+        let mut at = 8;
+        while data[at] != 0 {
+            let (name, next) = name_at(data, at);
+            let (kind, next) = name_at(data, next);
+            let size = u32_at(data, next) as usize;
+            attributes.push((name, kind, data[next + 4..next + 4 + size].to_vec()));
+            at = next + 4 + size;
+        }
+        (attributes, at + 1)
+    }
+
+    // size and channels of a file written by `write_exr`, checking its layout
+    pub(crate) fn read_exr(path: &Path) -> (usize, usize, Vec<(String, Vec<f32>)>) {
+        let data = fs::read(path).unwrap();
+        let (attributes, end) = header(&data);
+        let attribute = |name: &str| attributes.iter().find(|a| a.0 == name).unwrap_or_else(|| panic!("no {} attribute", name));
+
+        let window = &attribute("dataWindow").2;
+        let (width, height) = (u32_at(window, 8) as usize + 1, u32_at(window, 12) as usize + 1);
+        assert_eq!(attribute("displayWindow").2, *window);
+        assert_eq!(attribute("compression").2, vec![0], "uncompressed");
+
+        let chlist = &attribute("channels").2;
+        let mut names = Vec::new();
+        let mut at = 0;
+        while chlist[at] != 0 {
+            let (name, next) = name_at(chlist, at);
+            assert_eq!(u32_at(chlist, next), 2, "float channel {}", name);
+            names.push(name);
+            at = next + 16;
+        }
+
+        let line_size = width * 4 * names.len();
+        let mut channels: Vec<(String, Vec<f32>)> = names.into_iter().map(|name| (name, Vec::new())).collect();
+        for y in 0..height {
+            let offset = u64::from_le_bytes(data[end + y * 8..end + y * 8 + 8].try_into().unwrap()) as usize;
+            assert_eq!(u32_at(&data, offset) as usize, y, "line number");
+            assert_eq!(u32_at(&data, offset + 4) as usize, line_size, "line size");
+            for (c, (_, values)) in channels.iter_mut().enumerate() {
+                let start = offset + 8 + c * width * 4;
+                values.extend((0..width).map(|x| f32::from_bits(u32_at(&data, start + x * 4))));
+            }
+        }
+        assert_eq!(data.len(), end + height * 8 + height * (8 + line_size));
+        (width, height, channels)
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rt_exr_{}_{}.exr", name, std::process::id()))
+    }
+
+    #[test]
+    fn channels_are_written_line_by_line_in_alphabetical_order() {
+        let (width, height) = (3, 2);
+        let ramp = |offset: f32| (0..width * height).map(|i| offset + i as f32).collect::<Vec<f32>>();
+        let channels = vec![
+            ("R".to_string(), ramp(0.0)),
+            ("G".to_string(), ramp(10.0)),
+            ("B".to_string(), ramp(20.0)),
+            ("A".to_string(), ramp(30.0)),
+            ("depth.Z".to_string(), ramp(40.0)),
+        ];
+        let path = temp_path("layout");
+        write_exr(&path, width, height, &channels).unwrap();
+        let (read_width, read_height, read) = read_exr(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((read_width, read_height), (width, height));
+        let names: Vec<&str> = read.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["A", "B", "G", "R", "depth.Z"]);
+        for (name, values) in &read {
+            let written = &channels.iter().find(|c| c.0 == *name).unwrap().1;
+            assert_eq!(values, written, "values of {}", name);
+        }
+    }
+
+    #[test]
+    fn header_has_the_required_attributes() {
+        let path = temp_path("header");
+        write_exr(&path, 5, 4, &[("Y".to_string(), vec![0.5; 20])]).unwrap();
+        let (attributes, _) = header(&fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let kinds: Vec<(&str, &str)> = attributes.iter().map(|(name, kind, _)| (name.as_str(), kind.as_str())).collect();
+        for required in &[
+            ("channels", "chlist"),
+            ("compression", "compression"),
+            ("dataWindow", "box2i"),
+            ("displayWindow", "box2i"),
+            ("lineOrder", "lineOrder"),
+            ("pixelAspectRatio", "float"),
+            ("screenWindowCenter", "v2f"),
+            ("screenWindowWidth", "float"),
+        ] {
+            assert!(kinds.contains(required), "missing {:?}", required);
+        }
+        let window = &attributes.iter().find(|a| a.0 == "dataWindow").unwrap().2;
+        let corners: Vec<u32> = (0..4).map(|i| u32_at(window, i * 4)).collect();
+        assert_eq!(corners, [0, 0, 4, 3]);
+    }
+
+    #[test]
+    fn channel_of_the_wrong_size_is_refused() {
+        let path = temp_path("wrong_size");
+        let err = write_exr(&path, 2, 2, &[("R".to_string(), vec![0.0; 4]), ("G".to_string(), vec![0.0; 3])]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,930 @@
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::math::Vector;
+use crate::color::Color;
+use crate::filter::Filter;
+use crate::hitable::HitInfos;
+use crate::ray_image::RayImage;
+use crate::checkpoint::bytes;
+use crate::exr;
+
+// relative luminance of a linear rec. 709 color
+pub fn luminance(v: Vector) -> f32 {
+    0.2126 * v.x + 0.7152 * v.y + 0.0722 * v.z
+}
+
+// half open rectangle of pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Bounds {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Bounds {
+        Bounds { x0, y0, x1, y1 }
+    }
+
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    pub fn area(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x0 && x < self.x1 && y >= self.y0 && y < self.y1
+    }
+
+    // whether `other` is a valid rectangle lying inside this one
+    pub fn encloses(&self, other: Bounds) -> bool {
+        other.x0 <= other.x1 && other.y0 <= other.y1
+            && other.x0 >= self.x0 && other.x1 <= self.x1 && other.y0 >= self.y0 && other.y1 <= self.y1
+    }
+
+    pub fn intersect(&self, other: Bounds) -> Bounds {
+        let (x0, y0) = (self.x0.max(other.x0), self.y0.max(other.y0));
+        Bounds::new(x0, y0, self.x1.min(other.x1).max(x0), self.y1.min(other.y1).max(y0))
+    }
+}
+
+// part of the image to render, either in pixels or as fractions of the
+// image size, the camera still seeing the whole image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderRegion {
+    Pixels(Bounds),
+    Window { x0: f32, y0: f32, x1: f32, y1: f32 },
+}
+
+impl RenderRegion {
+    // pixels of a `width` by `height` image the region covers, a window
+    // taking every pixel it touches
+    pub fn bounds(&self, width: usize, height: usize) -> Bounds {
+        let bounds = match *self {
+            RenderRegion::Pixels(bounds) => bounds,
+            RenderRegion::Window { x0, y0, x1, y1 } => {
+                let scale = |t: f32, size: usize| t.clamp(0.0, 1.0) * size as f32;
+                Bounds::new(
+                    scale(x0, width).floor() as usize,
+                    scale(y0, height).floor() as usize,
+                    scale(x1, width).ceil() as usize,
+                    scale(y1, height).ceil() as usize,
+                )
+            },
+        };
+        bounds.intersect(Bounds::new(0, 0, width, height))
+    }
+}
+
+// running mean and variance of the luminance of the samples of a pixel,
+// following Welford
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PixelVariance {
+    count: usize,
+    mean: f32,
+    m2: f32,
+}
+
+impl PixelVariance {
+    pub fn add(&mut self, value: Vector) {
+        let value = luminance(value);
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.mean
+    }
+
+    // combines the statistics of two disjoint sets of samples (Chan et al.)
+    pub fn merge(&mut self, other: &PixelVariance) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f32 / count as f32;
+        self.m2 += other.m2 + delta * delta * (self.count * other.count) as f32 / count as f32;
+        self.count = count;
+    }
+
+    pub fn variance(&self) -> f32 {
+        if self.count > 1 { self.m2 / (self.count - 1) as f32 } else { 0.0 }
+    }
+
+    // standard error of the mean relative to the mean, dark pixels being
+    // compared to a small floor so they don't sample forever
+    pub fn relative_error(&self) -> f32 {
+        if self.count < 2 {
+            return f32::INFINITY;
+        }
+        (self.variance() / self.count as f32).sqrt() / self.mean.abs().max(1e-2)
+    }
+}
+
+// first surface seen by a camera sample, looking through mirrors, the sky
+// having an infinite depth and a zero normal
+#[derive(Debug, Clone, Copy)]
+pub struct Features {
+    pub albedo: Vector,
+    pub normal: Vector,
+    // distance along the camera ray
+    pub depth: f32,
+    pub position: Vector,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl Features {
+    pub fn surface(infos: &HitInfos, depth: f32) -> Features {
+        Features {
+            albedo: infos.material.albedo(infos),
+            normal: infos.normal,
+            depth,
+            position: infos.point.as_vector(),
+            object_id: infos.object_id,
+            material_id: infos.material_id,
+        }
+    }
+
+    pub fn sky(color: Vector) -> Features {
+        Features { albedo: color, ..Features::zero() }
+    }
+
+    pub fn zero() -> Features {
+        Features {
+            albedo: Vector::zero(),
+            normal: Vector::zero(),
+            depth: f32::INFINITY,
+            position: Vector::zero(),
+            object_id: 0,
+            material_id: 0,
+        }
+    }
+}
+
+// what a camera sample brings back
+#[derive(Debug, Clone, Copy)]
+pub struct FilmSample {
+    // premultiplied by the coverage
+    pub radiance: Vector,
+    // zero when the camera ray escaped to a transparent background
+    pub alpha: f32,
+    // part of the radiance coming from the sky directly or after one bounce
+    pub direct: Vector,
+    pub features: Features,
+}
+
+// passes that can be rendered besides the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    Depth,
+    Normal,
+    Albedo,
+    Position,
+    ObjectId,
+    MaterialId,
+    Direct,
+    Indirect,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 8] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Position,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Direct,
+        Aov::Indirect,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Aov> {
+        Aov::ALL.iter().cloned().find(|aov| aov.name() == name)
+    }
+
+    // channels of the pass in an EXR layer, read from x, y and z of `Film::get_aov`
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect => &["R", "G", "B"],
+        }
+    }
+}
+
+// filter weighted sum of the samples splatted on a pixel, the statistics of
+// the samples taken inside it and the sum of their albedos and normals, the
+// coverage being filtered like the radiance
+#[derive(Debug, Clone, Copy)]
+pub struct FilmPixel {
+    pub sum: Vector,
+    pub alpha: f32,
+    pub weight: f32,
+    pub variance: PixelVariance,
+    pub albedo: Vector,
+    pub normal: Vector,
+}
+
+impl FilmPixel {
+    fn empty() -> FilmPixel {
+        FilmPixel {
+            sum: Vector::zero(),
+            alpha: 0.0,
+            weight: 0.0,
+            variance: PixelVariance::default(),
+            albedo: Vector::zero(),
+            normal: Vector::zero(),
+        }
+    }
+
+    // filters with negative lobes can leave a pixel with no weight at all
+    fn has_weight(&self) -> bool {
+        self.weight.abs() > 1e-8
+    }
+
+    pub fn value(&self) -> Vector {
+        if self.has_weight() {
+            self.sum / self.weight
+        } else {
+            Vector::zero()
+        }
+    }
+
+    pub fn coverage(&self) -> f32 {
+        if self.has_weight() {
+            (self.alpha / self.weight).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+// what the passes need on top of a FilmPixel: the direct light is filtered
+// like the image, the depth is the closest one and the rest comes from the
+// first sample of the pixel, as ids can't be averaged
+#[derive(Debug, Clone, Copy)]
+struct AovPixel {
+    direct: Vector,
+    depth: f32,
+    position: Vector,
+    object_id: u32,
+    material_id: u32,
+}
+
+impl AovPixel {
+    fn empty() -> AovPixel {
+        AovPixel {
+            direct: Vector::zero(),
+            depth: f32::INFINITY,
+            position: Vector::zero(),
+            object_id: 0,
+            material_id: 0,
+        }
+    }
+
+    fn set_first(&mut self, other: &AovPixel) {
+        self.position = other.position;
+        self.object_id = other.object_id;
+        self.material_id = other.material_id;
+    }
+}
+
+// linear radiance accumulated for the whole image, y going down
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<FilmPixel>,
+    aovs: Option<Vec<AovPixel>>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Film {
+        Film {
+            width,
+            height,
+            pixels: vec![FilmPixel::empty(); width * height],
+            aovs: None,
+        }
+    }
+
+    // also keeps what the passes of `Aov` need
+    pub fn with_aovs(self) -> Film {
+        let aovs = Some(vec![AovPixel::empty(); self.width * self.height]);
+        Film { aovs, ..self }
+    }
+
+    pub fn has_aovs(&self) -> bool {
+        self.aovs.is_some()
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        Bounds::new(0, 0, self.width, self.height)
+    }
+
+    // film made of the pixels in `bounds` only
+    pub fn crop(&self, bounds: Bounds) -> Film {
+        fn rows<T: Copy>(pixels: &[T], width: usize, bounds: Bounds) -> Vec<T> {
+            (bounds.y0..bounds.y1)
+                .flat_map(|y| pixels[y * width + bounds.x0..y * width + bounds.x1].iter().cloned())
+                .collect()
+        }
+
+        let bounds = bounds.intersect(self.bounds());
+        Film {
+            width: bounds.width(),
+            height: bounds.height(),
+            pixels: rows(&self.pixels, self.width, bounds),
+            aovs: self.aovs.as_ref().map(|aovs| rows(aovs, self.width, bounds)),
+        }
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Vector {
+        assert!(x < self.width && y < self.height);
+        self.pixels[y * self.width + x].value()
+    }
+
+    // how much of the pixel is covered by the scene, one unless the
+    // background is transparent
+    pub fn get_alpha(&self, x: usize, y: usize) -> f32 {
+        assert!(x < self.width && y < self.height);
+        self.pixels[y * self.width + x].coverage()
+    }
+
+    // current premultiplied value of every pixel
+    pub fn radiance(&self) -> RayImage<Vector> {
+        RayImage::from_fn(self.width, self.height, |x, y| self.get_pixel(x, y))
+    }
+
+    pub fn alpha(&self) -> RayImage<f32> {
+        RayImage::from_fn(self.width, self.height, |x, y| self.get_alpha(x, y))
+    }
+
+    // replaces what was accumulated in the pixel by a final value, keeping
+    // its coverage
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: Vector) {
+        assert!(x < self.width && y < self.height);
+        let pixel = &mut self.pixels[y * self.width + x];
+        pixel.alpha = pixel.coverage();
+        pixel.sum = value;
+        pixel.weight = 1.0;
+    }
+
+    // average albedo and normal of the samples taken in the pixel, with the
+    // rest of the features when the film keeps them
+    pub fn get_features(&self, x: usize, y: usize) -> Features {
+        assert!(x < self.width && y < self.height);
+        let pixel = &self.pixels[y * self.width + x];
+        let count = pixel.variance.count().max(1) as f32;
+        let normal = pixel.normal / count;
+        let normal = if normal.norm() > 1e-4 { normal.normalized() } else { Vector::zero() };
+        let features = Features { albedo: pixel.albedo / count, normal, ..Features::zero() };
+
+        match &self.aovs {
+            Some(aovs) => {
+                let aov = &aovs[y * self.width + x];
+                Features {
+                    depth: aov.depth,
+                    position: aov.position,
+                    object_id: aov.object_id,
+                    material_id: aov.material_id,
+                    ..features
+                }
+            },
+            None => features,
+        }
+    }
+
+    // value of a pass, single channel ones being in x
+    pub fn get_aov(&self, aov: Aov, x: usize, y: usize) -> Vector {
+        let features = self.get_features(x, y);
+        let direct = || {
+            let pixel = &self.pixels[y * self.width + x];
+            let sum = self.aovs.as_ref().map_or(Vector::zero(), |aovs| aovs[y * self.width + x].direct);
+            if pixel.weight.abs() > 1e-8 { sum / pixel.weight } else { Vector::zero() }
+        };
+        let scalar = |v: f32| Vector::new(v, v, v);
+
+        match aov {
+            Aov::Depth => scalar(features.depth),
+            Aov::Normal => features.normal,
+            Aov::Albedo => features.albedo,
+            Aov::Position => features.position,
+            Aov::ObjectId => scalar(features.object_id as f32),
+            Aov::MaterialId => scalar(features.material_id as f32),
+            Aov::Direct => direct(),
+            Aov::Indirect => self.get_pixel(x, y) - direct(),
+        }
+    }
+
+    // pass made viewable: depth goes from white up close to black far away,
+    // normals and positions are remapped to colors and ids get random colors
+    pub fn aov_image(&self, aov: Aov) -> RayImage {
+        let mut image = RayImage::new(self.width, self.height);
+        let values: Vec<Vector> = (0..self.width * self.height)
+            .map(|i| self.get_aov(aov, i % self.width, i / self.width))
+            .collect();
+        let finite: Vec<Vector> = values.iter().cloned().filter(|v| v.x.is_finite()).collect();
+        let (min, max) = finite.iter().fold(
+            (Vector::new(f32::MAX, f32::MAX, f32::MAX), Vector::new(f32::MIN, f32::MIN, f32::MIN)),
+            |(min, max), v| {
+                (Vector::new(min.x.min(v.x), min.y.min(v.y), min.z.min(v.z)),
+                 Vector::new(max.x.max(v.x), max.y.max(v.y), max.z.max(v.z)))
+            },
+        );
+        let gamma = |c: f32| c.clamp(0.0, 1.0).sqrt();
+        let id_color = |id: f32| {
+            if id == 0.0 {
+                return Vector::zero();
+            }
+            let h = (id as u32).wrapping_mul(0x9e37_79b9);
+            let channel = |shift: u32| 0.2 + 0.8 * ((h >> shift) & 0xff) as f32 / 255.0;
+            Vector::new(channel(0), channel(8), channel(16))
+        };
+
+        for (i, &v) in values.iter().enumerate() {
+            let color = match aov {
+                Aov::Depth if v.x.is_finite() => {
+                    let d = 1.0 - (v.x - min.x) / (max.x - min.x).max(1e-6);
+                    Vector::new(d, d, d)
+                },
+                Aov::Depth => Vector::zero(),
+                Aov::Normal if v.norm_squared() > 0.0 => v * 0.5 + Vector::new(0.5, 0.5, 0.5),
+                Aov::Normal => Vector::zero(),
+                Aov::Position => {
+                    let extent = max - min;
+                    Vector::new(
+                        (v.x - min.x) / extent.x.max(1e-6),
+                        (v.y - min.y) / extent.y.max(1e-6),
+                        (v.z - min.z) / extent.z.max(1e-6),
+                    )
+                },
+                Aov::ObjectId | Aov::MaterialId => id_color(v.x),
+                Aov::Albedo => v,
+                Aov::Direct | Aov::Indirect => Vector::new(gamma(v.x), gamma(v.y), gamma(v.z)),
+            };
+            image.set_pixel(i % self.width, i / self.width, Color::from_floats(color.x, color.y, color.z));
+        }
+        image
+    }
+
+    // the image in R, G, B and A, premultiplied as EXR wants it, and every
+    // pass in a layer of its own
+    pub fn write_exr<P: AsRef<Path>>(&self, path: P, aovs: &[Aov]) -> io::Result<()> {
+        let count = self.width * self.height;
+        let coords = |i: usize| (i % self.width, i / self.width);
+        let mut channels = vec![
+            ("R".to_string(), (0..count).map(|i| self.get_pixel(coords(i).0, coords(i).1).x).collect()),
+            ("G".to_string(), (0..count).map(|i| self.get_pixel(coords(i).0, coords(i).1).y).collect()),
+            ("B".to_string(), (0..count).map(|i| self.get_pixel(coords(i).0, coords(i).1).z).collect()),
+            ("A".to_string(), (0..count).map(|i| self.get_alpha(coords(i).0, coords(i).1)).collect()),
+        ];
+        for &aov in aovs {
+            let values: Vec<Vector> = (0..count).map(|i| self.get_aov(aov, coords(i).0, coords(i).1)).collect();
+            for (c, channel) in aov.channels().iter().enumerate() {
+                let component = |v: &Vector| [v.x, v.y, v.z][c];
+                channels.push((format!("{}.{}", aov.name(), channel), values.iter().map(component).collect()));
+            }
+        }
+        exr::write_exr(path, self.width, self.height, &channels)
+    }
+
+    pub fn pixel_variance(&self, x: usize, y: usize) -> PixelVariance {
+        assert!(x < self.width && y < self.height);
+        self.pixels[y * self.width + x].variance
+    }
+
+    pub fn sample_count(&self, x: usize, y: usize) -> usize {
+        self.pixel_variance(x, y).count()
+    }
+
+    // samples per pixel going from black for none to blue, green, yellow and
+    // white for `max_samples` or more
+    pub fn sample_heatmap(&self, max_samples: usize) -> RayImage {
+        let ramp = [
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(1.0, 1.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+        ];
+        let mut image = RayImage::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let t = (self.sample_count(x, y) as f32 / max_samples.max(1) as f32).min(1.0);
+                let t = t * (ramp.len() - 1) as f32;
+                let i = (t as usize).min(ramp.len() - 2);
+                let c = ramp[i] * (1.0 - (t - i as f32)) + ramp[i + 1] * (t - i as f32);
+                image.set_pixel(x, y, Color::from_floats(c.x, c.y, c.z));
+            }
+        }
+        image
+    }
+
+    // splits `region` of the film in tiles of at most `size` pixels aside, row by row
+    pub fn tiles(&self, region: Bounds, size: usize) -> Vec<Bounds> {
+        let mut tiles = Vec::new();
+        for y0 in (region.y0..region.y1).step_by(size) {
+            for x0 in (region.x0..region.x1).step_by(size) {
+                tiles.push(Bounds::new(x0, y0, (x0 + size).min(region.x1), (y0 + size).min(region.y1)));
+            }
+        }
+        tiles
+    }
+
+    // tile receiving the samples taken in `bounds`, grown by the filter radius
+    // as they spill on the neighbouring pixels, but not outside of `region`
+    // so the pixels that aren't rendered stay untouched
+    pub fn tile(&self, bounds: Bounds, region: Bounds, filter: &dyn Filter) -> FilmTile {
+        FilmTile::new(self.tile_bounds(bounds, region, filter), self.has_aovs())
+    }
+
+    pub fn tile_bounds(&self, bounds: Bounds, region: Bounds, filter: &dyn Filter) -> Bounds {
+        let margin = filter.radius().ceil() as usize;
+        Bounds::new(
+            bounds.x0.saturating_sub(margin),
+            bounds.y0.saturating_sub(margin),
+            bounds.x1 + margin,
+            bounds.y1 + margin,
+        ).intersect(region.intersect(self.bounds()))
+    }
+
+    pub(crate) fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        bytes::write_u64(w, self.width as u64)?;
+        bytes::write_u64(w, self.height as u64)?;
+        write_pixels(w, &self.pixels, self.aovs.as_deref())
+    }
+
+    pub(crate) fn read_from(r: &mut dyn Read) -> io::Result<Film> {
+        let width = bytes::read_u64(r)? as usize;
+        let height = bytes::read_u64(r)? as usize;
+        let count = width.checked_mul(height)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid film size"))?;
+        let (pixels, aovs) = read_pixels(r, count)?;
+        Ok(Film { width, height, pixels, aovs })
+    }
+
+    pub fn merge_tile(&mut self, tile: &FilmTile) {
+        let bounds = tile.bounds;
+        for y in bounds.y0..bounds.y1 {
+            for x in bounds.x0..bounds.x1 {
+                let index = (y - bounds.y0) * bounds.width() + x - bounds.x0;
+                let from = tile.pixels[index];
+                let to = &mut self.pixels[y * self.width + x];
+                let first = to.variance.count() == 0 && from.variance.count() > 0;
+                to.sum = to.sum + from.sum;
+                to.alpha += from.alpha;
+                to.weight += from.weight;
+                to.variance.merge(&from.variance);
+                to.albedo = to.albedo + from.albedo;
+                to.normal = to.normal + from.normal;
+
+                if let (Some(aovs), Some(tile_aovs)) = (&mut self.aovs, &tile.aovs) {
+                    let (from, to) = (&tile_aovs[index], &mut aovs[y * self.width + x]);
+                    to.direct = to.direct + from.direct;
+                    to.depth = to.depth.min(from.depth);
+                    if first {
+                        to.set_first(from);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FilmTile {
+    bounds: Bounds,
+    pixels: Vec<FilmPixel>,
+    aovs: Option<Vec<AovPixel>>,
+}
+
+impl FilmTile {
+    // empty tile covering `bounds`, see `Film::tile` for the bounds to give
+    pub fn new(bounds: Bounds, aovs: bool) -> FilmTile {
+        FilmTile {
+            bounds,
+            pixels: vec![FilmPixel::empty(); bounds.area()],
+            aovs: if aovs { Some(vec![AovPixel::empty(); bounds.area()]) } else { None },
+        }
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    // adds a sample taken at the continuous film position (x, y), pixel
+    // centers being at half integer coordinates, the features only going to
+    // the pixel the sample is in
+    pub fn add_sample(&mut self, x: f32, y: f32, sample: &FilmSample, filter: &dyn Filter) {
+        let bounds = self.bounds;
+        let (sx, sy) = (x as usize, y as usize);
+        if sx >= bounds.x0 && sx < bounds.x1 && sy >= bounds.y0 && sy < bounds.y1 {
+            let index = (sy - bounds.y0) * bounds.width() + sx - bounds.x0;
+            let pixel = &mut self.pixels[index];
+            let first = pixel.variance.count() == 0;
+            let features = sample.features;
+            pixel.variance.add(sample.radiance);
+            pixel.albedo = pixel.albedo + features.albedo;
+            pixel.normal = pixel.normal + features.normal;
+
+            if let Some(aovs) = &mut self.aovs {
+                let aov = &mut aovs[index];
+                aov.depth = aov.depth.min(features.depth);
+                if first {
+                    aov.position = features.position;
+                    aov.object_id = features.object_id;
+                    aov.material_id = features.material_id;
+                }
+            }
+        }
+
+        let radius = filter.radius();
+        let (x, y) = (x - 0.5, y - 0.5);
+        let x0 = ((x - radius).ceil().max(bounds.x0 as f32)) as usize;
+        let y0 = ((y - radius).ceil().max(bounds.y0 as f32)) as usize;
+        let x1 = ((x + radius).floor() + 1.0).min(bounds.x1 as f32).max(0.0) as usize;
+        let y1 = ((y + radius).floor() + 1.0).min(bounds.y1 as f32).max(0.0) as usize;
+
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let weight = filter.evaluate(px as f32 - x, py as f32 - y);
+                if weight != 0.0 {
+                    let index = (py - bounds.y0) * bounds.width() + px - bounds.x0;
+                    let pixel = &mut self.pixels[index];
+                    pixel.sum = pixel.sum + sample.radiance * weight;
+                    pixel.alpha += sample.alpha * weight;
+                    pixel.weight += weight;
+                    if let Some(aovs) = &mut self.aovs {
+                        aovs[index].direct = aovs[index].direct + sample.direct * weight;
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        write_bounds(w, self.bounds)?;
+        write_pixels(w, &self.pixels, self.aovs.as_deref())
+    }
+
+    // the bounds are checked to be the `expected` ones before the pixels are
+    // read into a tile of that size
+    pub(crate) fn read_from(r: &mut dyn Read, expected: Bounds) -> io::Result<FilmTile> {
+        let bounds = read_bounds(r)?;
+        if bounds != expected {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected tile bounds"));
+        }
+        let (pixels, aovs) = read_pixels(r, bounds.area())?;
+        Ok(FilmTile { bounds, pixels, aovs })
+    }
+}
+
+pub(crate) fn write_bounds(w: &mut dyn Write, bounds: Bounds) -> io::Result<()> {
+    for &v in &[bounds.x0, bounds.y0, bounds.x1, bounds.y1] {
+        bytes::write_u64(w, v as u64)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_bounds(r: &mut dyn Read) -> io::Result<Bounds> {
+    Ok(Bounds::new(
+        bytes::read_u64(r)? as usize,
+        bytes::read_u64(r)? as usize,
+        bytes::read_u64(r)? as usize,
+        bytes::read_u64(r)? as usize,
+    ))
+}
+
+// pixels of a film or a tile, in the checkpoint format
+fn write_pixels(w: &mut dyn Write, pixels: &[FilmPixel], aovs: Option<&[AovPixel]>) -> io::Result<()> {
+    let write_vector = |w: &mut dyn Write, v: Vector| -> io::Result<()> {
+        bytes::write_f32(w, v.x)?;
+        bytes::write_f32(w, v.y)?;
+        bytes::write_f32(w, v.z)
+    };
+
+    for pixel in pixels {
+        write_vector(w, pixel.sum)?;
+        bytes::write_f32(w, pixel.alpha)?;
+        bytes::write_f32(w, pixel.weight)?;
+        let variance = pixel.variance;
+        bytes::write_u64(w, variance.count as u64)?;
+        bytes::write_f32(w, variance.mean)?;
+        bytes::write_f32(w, variance.m2)?;
+        write_vector(w, pixel.albedo)?;
+        write_vector(w, pixel.normal)?;
+    }
+
+    bytes::write_u32(w, aovs.is_some() as u32)?;
+    for aov in aovs.into_iter().flatten() {
+        write_vector(w, aov.direct)?;
+        bytes::write_f32(w, aov.depth)?;
+        write_vector(w, aov.position)?;
+        bytes::write_u32(w, aov.object_id)?;
+        bytes::write_u32(w, aov.material_id)?;
+    }
+    Ok(())
+}
+
+// the count comes from the stream, the vectors only growing as the pixels
+// are actually read so a corrupt count ends in an error rather than in a
+// huge allocation
+fn read_pixels(r: &mut dyn Read, count: usize) -> io::Result<(Vec<FilmPixel>, Option<Vec<AovPixel>>)> {
+    const PREALLOCATED: usize = 1 << 16;
+    let read_vector = |r: &mut dyn Read| -> io::Result<Vector> {
+        Ok(Vector::new(bytes::read_f32(r)?, bytes::read_f32(r)?, bytes::read_f32(r)?))
+    };
+
+    let mut pixels = Vec::with_capacity(count.min(PREALLOCATED));
+    for _ in 0..count {
+        let sum = read_vector(r)?;
+        let alpha = bytes::read_f32(r)?;
+        let weight = bytes::read_f32(r)?;
+        let variance = PixelVariance {
+            count: bytes::read_u64(r)? as usize,
+            mean: bytes::read_f32(r)?,
+            m2: bytes::read_f32(r)?,
+        };
+        let albedo = read_vector(r)?;
+        let normal = read_vector(r)?;
+        pixels.push(FilmPixel { sum, alpha, weight, variance, albedo, normal });
+    }
+
+    let aovs = if bytes::read_u32(r)? != 0 {
+        let mut aovs = Vec::with_capacity(count.min(PREALLOCATED));
+        for _ in 0..count {
+            aovs.push(AovPixel {
+                direct: read_vector(r)?,
+                depth: bytes::read_f32(r)?,
+                position: read_vector(r)?,
+                object_id: bytes::read_u32(r)?,
+                material_id: bytes::read_u32(r)?,
+            });
+        }
+        Some(aovs)
+    } else {
+        None
+    };
+    Ok((pixels, aovs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(value: f32) -> Vector {
+        Vector::new(value, value, value)
+    }
+
+    #[test]
+    fn variance_of_the_samples() {
+        let mut variance = PixelVariance::default();
+        for &value in &[1.0, 2.0, 3.0, 4.0] {
+            variance.add(grey(value));
+        }
+        assert_eq!(variance.count(), 4);
+        assert!((variance.mean() - 2.5).abs() < 1e-6);
+        assert!((variance.variance() - 5.0 / 3.0).abs() < 1e-5);
+        // standard error of the mean over the mean
+        let expected = (5.0f32 / 3.0 / 4.0).sqrt() / 2.5;
+        assert!((variance.relative_error() - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn merged_variance_is_the_variance_of_all_samples() {
+        let values = [0.1, 0.9, 0.4, 0.4, 2.0, 0.0, 0.3];
+        let (mut all, mut first, mut second) = (PixelVariance::default(), PixelVariance::default(), PixelVariance::default());
+        for (i, &value) in values.iter().enumerate() {
+            all.add(grey(value));
+            if i < 3 { first.add(grey(value)) } else { second.add(grey(value)) }
+        }
+        first.merge(&second);
+        assert_eq!(first.count(), all.count());
+        assert!((first.mean() - all.mean()).abs() < 1e-6);
+        assert!((first.variance() - all.variance()).abs() < 1e-5);
+
+        // merging nothing changes nothing
+        let before = first;
+        first.merge(&PixelVariance::default());
+        assert_eq!(first.count(), before.count());
+        assert_eq!(first.variance(), before.variance());
+    }
+
+    #[test]
+    fn too_few_samples_have_an_unknown_error() {
+        let mut variance = PixelVariance::default();
+        variance.add(grey(0.5));
+        assert_eq!(variance.relative_error(), f32::INFINITY);
+        // black pixels are compared to a floor rather than to their mean
+        let mut dark = PixelVariance::default();
+        dark.add(grey(0.0));
+        dark.add(grey(0.0));
+        assert_eq!(dark.relative_error(), 0.0);
+    }
+
+    // two samples in pixel (0, 0) of a 2x1 film keeping the passes, the other
+    // pixel seeing the sky
+    fn film_with_aovs() -> Film {
+        let filter = crate::filter::FilterKind::Box.build();
+        let mut tile = FilmTile::new(Bounds::new(0, 0, 2, 1), true);
+        let surface = |depth: f32, normal: Vector| Features {
+            albedo: Vector::new(0.5, 0.25, 1.0),
+            normal,
+            depth,
+            position: Vector::new(1.0, 2.0, 3.0),
+            object_id: 4,
+            material_id: 7,
+        };
+        for &(radiance, depth, normal) in &[(0.4, 3.0, Vector::new(0.0, 1.0, 0.0)), (0.8, 2.0, Vector::new(0.0, 0.0, 1.0))] {
+            let sample = FilmSample { radiance: grey(radiance), alpha: 1.0, direct: grey(0.25), features: surface(depth, normal) };
+            tile.add_sample(0.5, 0.5, &sample, filter.as_ref());
+        }
+        let sky = FilmSample { radiance: grey(0.9), alpha: 1.0, direct: grey(0.9), features: Features::sky(grey(0.9)) };
+        tile.add_sample(1.5, 0.5, &sky, filter.as_ref());
+
+        let mut film = Film::new(2, 1).with_aovs();
+        film.merge_tile(&tile);
+        film
+    }
+
+    #[test]
+    fn passes_of_the_first_surface() {
+        let film = film_with_aovs();
+        let close = |a: Vector, b: Vector| (a - b).norm() < 1e-5;
+        // nearest depth, first ids and position, averaged albedo and normal
+        assert_eq!(film.get_aov(Aov::Depth, 0, 0).x, 2.0);
+        assert_eq!(film.get_aov(Aov::ObjectId, 0, 0).x, 4.0);
+        assert_eq!(film.get_aov(Aov::MaterialId, 0, 0).x, 7.0);
+        assert_eq!(film.get_aov(Aov::Position, 0, 0), Vector::new(1.0, 2.0, 3.0));
+        assert!(close(film.get_aov(Aov::Albedo, 0, 0), Vector::new(0.5, 0.25, 1.0)));
+        assert!(close(film.get_aov(Aov::Normal, 0, 0), Vector::new(0.0, 1.0, 1.0).normalized()));
+        // the direct and indirect light add up to the pixel
+        assert!(close(film.get_aov(Aov::Direct, 0, 0), grey(0.25)));
+        assert!(close(film.get_aov(Aov::Indirect, 0, 0), grey(0.35)));
+        assert!(close(film.get_aov(Aov::Direct, 0, 0) + film.get_aov(Aov::Indirect, 0, 0), film.get_pixel(0, 0)));
+
+        // the sky is infinitely far, without a normal or an id
+        assert_eq!(film.get_aov(Aov::Depth, 1, 0).x, f32::INFINITY);
+        assert_eq!(film.get_aov(Aov::Normal, 1, 0), Vector::zero());
+        assert_eq!(film.get_aov(Aov::ObjectId, 1, 0).x, 0.0);
+    }
+
+    #[test]
+    fn exr_has_a_layer_per_pass() {
+        let film = film_with_aovs();
+        let path = std::env::temp_dir().join(format!("rt_film_passes_{}.exr", std::process::id()));
+        film.write_exr(&path, &[Aov::Depth, Aov::Normal, Aov::ObjectId, Aov::Direct]).unwrap();
+        let (width, height, channels) = crate::exr::tests::read_exr(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((width, height), (2, 1));
+        let names: Vec<&str> = channels.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, [
+            "A", "B", "G", "R",
+            "depth.Z", "direct.B", "direct.G", "direct.R",
+            "normal.X", "normal.Y", "normal.Z", "object_id.id",
+        ]);
+        let channel = |name: &str| channels.iter().find(|c| c.0 == name).unwrap().1.clone();
+        assert_eq!(channel("depth.Z"), vec![2.0, f32::INFINITY]);
+        assert_eq!(channel("object_id.id"), vec![4.0, 0.0]);
+        assert_eq!(channel("A"), vec![1.0, 1.0]);
+        assert!((channel("R")[0] - 0.6).abs() < 1e-5);
+        assert!((channel("normal.Y")[0] - channel("normal.Z")[0]).abs() < 1e-6);
+    }
+
+}
@@ -0,0 +1,257 @@
+use std::f32::consts::PI;
+
+// pixel reconstruction filter, weighting a sample by its offset in pixels
+// from the center of the pixel it contributes to
+pub trait Filter: Send + Sync {
+    // no sample further than this on either axis contributes to the pixel
+    fn radius(&self) -> f32;
+
+    fn evaluate(&self, dx: f32, dy: f32) -> f32;
+}
+
+// the filters with their default settings, to pick one by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    #[default]
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+impl FilterKind {
+    pub const ALL: [FilterKind; 5] = [
+        FilterKind::Box,
+        FilterKind::Tent,
+        FilterKind::Gaussian,
+        FilterKind::Mitchell,
+        FilterKind::Lanczos,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FilterKind::Box => "box",
+            FilterKind::Tent => "tent",
+            FilterKind::Gaussian => "gaussian",
+            FilterKind::Mitchell => "mitchell",
+            FilterKind::Lanczos => "lanczos",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FilterKind> {
+        FilterKind::ALL.iter().cloned().find(|kind| kind.name() == name)
+    }
+
+    pub fn build(self) -> Box<dyn Filter> {
+        match self {
+            FilterKind::Box => Box::new(BoxFilter::default()),
+            FilterKind::Tent => Box::new(TentFilter::new(1.0)),
+            FilterKind::Gaussian => Box::new(GaussianFilter::default()),
+            FilterKind::Mitchell => Box::new(MitchellFilter::default()),
+            FilterKind::Lanczos => Box::new(LanczosFilter::default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoxFilter {
+    pub radius: f32,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f32) -> BoxFilter {
+        BoxFilter { radius }
+    }
+}
+
+impl Default for BoxFilter {
+    // only the samples inside the pixel, a plain average
+    fn default() -> BoxFilter {
+        BoxFilter::new(0.5)
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        if dx.abs() <= self.radius && dy.abs() <= self.radius { 1.0 } else { 0.0 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TentFilter {
+    pub radius: f32,
+}
+
+impl TentFilter {
+    pub fn new(radius: f32) -> TentFilter {
+        TentFilter { radius }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        (self.radius - dx.abs()).max(0.0) * (self.radius - dy.abs()).max(0.0)
+    }
+}
+
+// gaussian shifted down to reach zero at the radius
+#[derive(Debug, Clone)]
+pub struct GaussianFilter {
+    pub radius: f32,
+    pub sigma: f32,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f32, sigma: f32) -> GaussianFilter {
+        GaussianFilter { radius, sigma }
+    }
+
+    fn gaussian(&self, d: f32) -> f32 {
+        (-d * d / (2.0 * self.sigma * self.sigma)).exp()
+    }
+
+    fn evaluate_1d(&self, d: f32) -> f32 {
+        (self.gaussian(d) - self.gaussian(self.radius)).max(0.0)
+    }
+}
+
+impl Default for GaussianFilter {
+    fn default() -> GaussianFilter {
+        GaussianFilter::new(1.5, 0.5)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+}
+
+// Mitchell-Netravali cubic, B = C = 1/3 being the recommended compromise
+// between blurring and ringing
+#[derive(Debug, Clone)]
+pub struct MitchellFilter {
+    pub radius: f32,
+    pub b: f32,
+    pub c: f32,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f32, b: f32, c: f32) -> MitchellFilter {
+        MitchellFilter { radius, b, c }
+    }
+
+    fn evaluate_1d(&self, d: f32) -> f32 {
+        let (b, c) = (self.b, self.c);
+        // the cubic is defined over [-2, 2]
+        let x = (2.0 * d / self.radius).abs();
+        let value = if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            (-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)
+        } else {
+            (12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b)
+        };
+        value / 6.0
+    }
+}
+
+impl Default for MitchellFilter {
+    fn default() -> MitchellFilter {
+        MitchellFilter::new(2.0, 1.0 / 3.0, 1.0 / 3.0)
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+}
+
+// sinc windowed by a wider sinc, `tau` being the number of lobes kept
+#[derive(Debug, Clone)]
+pub struct LanczosFilter {
+    pub radius: f32,
+    pub tau: f32,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f32, tau: f32) -> LanczosFilter {
+        LanczosFilter { radius, tau }
+    }
+
+    fn sinc(x: f32) -> f32 {
+        if x.abs() < 1e-5 {
+            1.0
+        } else {
+            (PI * x).sin() / (PI * x)
+        }
+    }
+
+    fn evaluate_1d(&self, d: f32) -> f32 {
+        if d.abs() > self.radius {
+            0.0
+        } else {
+            Self::sinc(d) * Self::sinc(d / self.tau)
+        }
+    }
+}
+
+impl Default for LanczosFilter {
+    fn default() -> LanczosFilter {
+        LanczosFilter::new(3.0, 3.0)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_are_zero_outside_their_radius() {
+        for kind in FilterKind::ALL.iter() {
+            let filter = kind.build();
+            let outside = filter.radius() + 1e-3;
+            assert!(filter.evaluate(0.0, 0.0) > 0.0, "{} is zero at its center", kind.name());
+            for &(dx, dy) in &[(outside, 0.0), (0.0, -outside), (-outside, outside), (outside + 1.0, 0.3), (0.2, outside + 5.0)] {
+                assert_eq!(filter.evaluate(dx, dy), 0.0, "{} at ({}, {})", kind.name(), dx, dy);
+            }
+        }
+    }
+
+    #[test]
+    fn filters_have_their_name() {
+        for &kind in FilterKind::ALL.iter() {
+            assert_eq!(FilterKind::from_name(kind.name()), Some(kind));
+        }
+    }
+}
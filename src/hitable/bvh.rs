@@ -1,7 +1,5 @@
 use super::*;
 
-use rand::{self, Rng};
-
 enum BVHNode {
     Empty,
     Leaf(Box<dyn Hitable>),
@@ -44,12 +42,16 @@ impl BVH {
 
         let mut children = children;
 
-        let axis_rand: u32 = rand::thread_rng().gen_range(0, 3);
-        let sort_func = match axis_rand {
-            0 => utils::box_x_compare,
-            1 => utils::box_y_compare,
-            2 => utils::box_z_compare,
-            _ => unreachable!(),
+        // splitting along the longest side instead of a random one keeps the
+        // tree, and so the rendered image, the same from one run to the other
+        let extent = children.iter()
+            .filter_map(|c| c.bounding_box())
+            .fold(None, |acc, bb| Some(acc.map_or(bb, |acc| AABB::surrounding(acc, bb))))
+            .map(|bb| bb.max - bb.min);
+        let sort_func = match extent {
+            Some(e) if e.y > e.x && e.y >= e.z => utils::box_y_compare,
+            Some(e) if e.z > e.x && e.z > e.y => utils::box_z_compare,
+            _ => utils::box_x_compare,
         };
         children.sort_by(sort_func);
 
@@ -8,22 +8,30 @@ mod sphere;
 mod bvh;
 mod aabb;
 mod triangle;
+mod tagged;
+mod transformed;
 pub use self::sphere::*;
 pub use self::bvh::*;
 pub use self::aabb::*;
 pub use self::triangle::*;
+pub use self::tagged::*;
+pub use self::transformed::*;
 
 pub struct HitInfos {
     pub t: f32,
     pub point: Point,
     pub normal: Vector,
-    pub material: Arc<dyn Material>
+    pub geometric_normal: Vector,
+    pub material: Arc<dyn Material>,
+    // given by `Tagged`, zero when untagged
+    pub object_id: u32,
+    pub material_id: u32,
 }
 
 impl HitInfos {
     pub fn min_max(t: f32, tmin: f32, tmax: f32, point: Point, normal: Vector, material: Arc<dyn Material>) -> Option<Self> {
         if tmin <= t && t <= tmax {
-            Some(HitInfos { t, point, normal, material })
+            Some(HitInfos { t, point, normal, geometric_normal: normal, material, object_id: 0, material_id: 0 })
         } else {
             None
         }
@@ -35,6 +43,27 @@ pub trait Hitable: Sync + Send {
     fn bounding_box(&self) -> Option<AABB>;
 }
 
+impl Hitable for Box<dyn Hitable> {
+    fn hit(&self, ray: Ray, tmin: f32, tmax: f32) -> Option<HitInfos> {
+        self.as_ref().hit(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.as_ref().bounding_box()
+    }
+}
+
+// lets an object built once, like a large mesh, be part of several worlds
+impl<H: Hitable + ?Sized> Hitable for Arc<H> {
+    fn hit(&self, ray: Ray, tmin: f32, tmax: f32) -> Option<HitInfos> {
+        self.as_ref().hit(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.as_ref().bounding_box()
+    }
+}
+
 impl Hitable for Vec<Box<dyn Hitable>> {
     fn hit(&self, ray: Ray, tmin: f32, tmax: f32) -> Option<HitInfos> {
         let mut infos = None;
@@ -54,11 +83,10 @@ impl Hitable for Vec<Box<dyn Hitable>> {
 
         for hitable in self {
             if let Some(next_bb) = hitable.bounding_box() {
-                final_box = if let Some(bb) = final_box {
-                    Some(AABB::surrounding(next_bb, bb))
-                } else {
-                    None 
-                };
+                final_box = Some(match final_box {
+                    Some(bb) => AABB::surrounding(next_bb, bb),
+                    None => next_bb,
+                });
             } else {
                 return None
             }
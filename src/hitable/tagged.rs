@@ -0,0 +1,33 @@
+use super::*;
+
+// gives ids to what is hit through it, for the object and material id
+// passes, the ids set closest to the primitives winning
+pub struct Tagged<H: Hitable> {
+    pub inner: H,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl<H: Hitable> Tagged<H> {
+    pub fn new(inner: H, object_id: u32, material_id: u32) -> Tagged<H> {
+        Tagged { inner, object_id, material_id }
+    }
+}
+
+impl<H: Hitable> Hitable for Tagged<H> {
+    fn hit(&self, ray: Ray, tmin: f32, tmax: f32) -> Option<HitInfos> {
+        self.inner.hit(ray, tmin, tmax).map(|mut infos| {
+            if infos.object_id == 0 {
+                infos.object_id = self.object_id;
+            }
+            if infos.material_id == 0 {
+                infos.material_id = self.material_id;
+            }
+            infos
+        })
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        self.inner.bounding_box()
+    }
+}
@@ -0,0 +1,94 @@
+use super::*;
+
+// object to world mapping: scaling, then rotating, then moving
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    linear: Matrix3,
+    inverse: Matrix3,
+    translation: Vector,
+}
+
+// smallest scale kept, an animated scale going through zero making the
+// object tiny for a moment instead of leaving no way back from the world
+const MIN_SCALE: f32 = 1e-3;
+
+impl Transform {
+    // `rotation` holds the angles in degrees around x, y and z, applied in that
+    // order, the scale is uniform, its size being at least `MIN_SCALE`
+    pub fn new(translation: Vector, rotation: Vector, scale: f32) -> Transform {
+        let scale = if scale.abs() < MIN_SCALE { MIN_SCALE.copysign(scale) } else { scale };
+        let rotation = Matrix3::rotation(Vector::new(0.0, 0.0, 1.0), rotation.z)
+            * Matrix3::rotation(Vector::new(0.0, 1.0, 0.0), rotation.y)
+            * Matrix3::rotation(Vector::new(1.0, 0.0, 0.0), rotation.x);
+        let linear = rotation * Matrix3::scale(scale);
+        let inverse = linear.inverse().expect("transform with a non finite scale");
+        Transform { linear, inverse, translation }
+    }
+
+    pub fn identity() -> Transform {
+        Transform::new(Vector::zero(), Vector::zero(), 1.0)
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        let v = self.linear.apply(p.as_vector()) + self.translation;
+        Point::new(v.x, v.y, v.z)
+    }
+
+    pub fn vector(&self, v: Vector) -> Vector {
+        self.linear.apply(v)
+    }
+
+    // normals go through the inverse transpose to stay orthogonal to the surface
+    pub fn normal(&self, n: Vector) -> Vector {
+        self.inverse.transpose().apply(n).normalized()
+    }
+
+    fn inverse_point(&self, p: Point) -> Point {
+        let v = self.inverse.apply(p.as_vector() - self.translation);
+        Point::new(v.x, v.y, v.z)
+    }
+}
+
+// places an object in the world, the ray being brought into the object
+// space so its parameter stays the same
+pub struct Transformed<H: Hitable> {
+    pub inner: H,
+    pub transform: Transform,
+}
+
+impl<H: Hitable> Transformed<H> {
+    pub fn new(inner: H, transform: Transform) -> Transformed<H> {
+        Transformed { inner, transform }
+    }
+}
+
+impl<H: Hitable> Hitable for Transformed<H> {
+    fn hit(&self, ray: Ray, tmin: f32, tmax: f32) -> Option<HitInfos> {
+        let local = Ray {
+            origin: self.transform.inverse_point(ray.origin),
+            direction: self.transform.inverse.apply(ray.direction),
+            ..ray
+        };
+        self.inner.hit(local, tmin, tmax).map(|infos| HitInfos {
+            point: self.transform.point(infos.point),
+            normal: self.transform.normal(infos.normal),
+            geometric_normal: self.transform.normal(infos.geometric_normal),
+            ..infos
+        })
+    }
+
+    fn bounding_box(&self) -> Option<AABB> {
+        let bb = self.inner.bounding_box()?;
+        let corners = (0..8).map(|i| {
+            let pick = |bit: usize, min: f32, max: f32| if i & bit == 0 { min } else { max };
+            let corner = Point::new(pick(1, bb.min.x, bb.max.x), pick(2, bb.min.y, bb.max.y), pick(4, bb.min.z, bb.max.z));
+            self.transform.point(corner).as_vector()
+        });
+        let (mut min, mut max) = (Vector::new(f32::MAX, f32::MAX, f32::MAX), Vector::new(f32::MIN, f32::MIN, f32::MIN));
+        for c in corners {
+            min = Vector::new(min.x.min(c.x), min.y.min(c.y), min.z.min(c.z));
+            max = Vector::new(max.x.max(c.x), max.y.max(c.y), max.z.max(c.z));
+        }
+        Some(AABB::new(min, max))
+    }
+}
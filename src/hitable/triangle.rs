@@ -6,6 +6,7 @@ pub struct Triangle {
     a: Point,
     b: Point,
     c: Point,
+    normals: Option<[Vector; 3]>,
     material: Arc<dyn Material>
 }
 
@@ -15,6 +16,7 @@ impl Triangle {
             a,
             b,
             c,
+            normals: None,
             material: Arc::new(material)
         }
     }
@@ -24,6 +26,18 @@ impl Triangle {
             a,
             b,
             c,
+            normals: None,
+            material
+        }
+    }
+
+    pub fn new_smooth_with_arc(a: Point, b: Point, c: Point, normals: [Vector; 3], material: Arc<dyn Material>) -> Self {
+        let [na, nb, nc] = normals;
+        Triangle {
+            a,
+            b,
+            c,
+            normals: Some([na.normalized(), nb.normalized(), nc.normalized()]),
             material
         }
     }
@@ -58,10 +72,20 @@ impl Hitable for Triangle {
 
         let t = f * edge2.dot(q);
         let point = ray.point_at(t);
-        let normal = edge1.cross(edge2).normalized();
-        // println!("{:?}", normal);
-        
-        HitInfos::min_max(t, tmin, tmax, point, normal, self.material.clone())
+        let geometric_normal = edge1.cross(edge2).normalized();
+
+        // the interpolated normal is only used for shading, and is kept in the
+        // same hemisphere as the real surface to avoid light leaking through it
+        let normal = match self.normals {
+            Some([na, nb, nc]) => {
+                let n = (na * (1.0 - u - v) + nb * u + nc * v).normalized();
+                if n.dot(geometric_normal) < 0.0 { -n } else { n }
+            },
+            None => geometric_normal,
+        };
+
+        HitInfos::min_max(t, tmin, tmax, point, geometric_normal, self.material.clone())
+            .map(|infos| HitInfos { normal, ..infos })
     }
 
     fn bounding_box(&self) -> Option<AABB> {
@@ -95,4 +119,62 @@ impl Hitable for Triangle {
         let max = Vector::new(max_x, max_y, max_z);
         Some(AABB { min, max })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    fn smooth_triangle() -> Triangle {
+        let material = Lambertian::new(ConstantTexture::new(Color::new(128, 128, 128)));
+        Triangle::new_smooth_with_arc(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+            [Vector::new(0.0, 0.0, 1.0), Vector::new(1.0, 0.0, 1.0), Vector::new(0.0, 1.0, 1.0)],
+            Arc::new(material),
+        )
+    }
+
+    fn hit_at(triangle: &Triangle, x: f32, y: f32, from_z: f32) -> HitInfos {
+        let ray = Ray::new(Point::new(x, y, from_z), Vector::new(0.0, 0.0, -from_z));
+        triangle.hit(ray, 0.0, f32::MAX).unwrap()
+    }
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).norm() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn normal_is_interpolated_with_the_barycentric_coordinates() {
+        let triangle = smooth_triangle();
+        let infos = hit_at(&triangle, 0.5, 0.25, 1.0);
+        let [na, nb, nc] = [
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(1.0, 0.0, 1.0).normalized(),
+            Vector::new(0.0, 1.0, 1.0).normalized(),
+        ];
+        assert_close(infos.normal, (na * 0.25 + nb * 0.5 + nc * 0.25).normalized());
+        assert_close(infos.geometric_normal, Vector::new(0.0, 0.0, 1.0));
+
+        // the normal of a vertex next to the vertex
+        assert!((hit_at(&triangle, 1.0 - 1e-4, 0.0, 1.0).normal - nb).norm() < 1e-3);
+    }
+
+    #[test]
+    fn shading_normal_stays_on_the_side_of_the_surface() {
+        let triangle = smooth_triangle();
+        let infos = hit_at(&triangle, 0.2, 0.2, -1.0);
+        assert!(infos.normal.dot(infos.geometric_normal) > 0.0);
+    }
+
+    #[test]
+    fn flat_triangle_uses_its_face_normal() {
+        let material = Lambertian::new(ConstantTexture::new(Color::new(128, 128, 128)));
+        let triangle = Triangle::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), material);
+        let infos = hit_at(&triangle, 0.5, 0.25, 1.0);
+        assert_close(infos.normal, infos.geometric_normal);
+    }
+}
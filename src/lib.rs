@@ -9,6 +9,16 @@ pub mod hitable;
 pub mod camera;
 pub mod material;
 pub mod texture;
+pub mod spectrum;
+pub mod sampler;
+pub mod filter;
+pub mod film;
+pub mod checkpoint;
+pub mod denoise;
+pub mod exr;
+pub mod post;
+pub mod animation;
+pub mod distributed;
 
 pub mod prelude {
     pub use super::color::Color;
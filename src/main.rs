@@ -4,9 +4,14 @@ extern crate image;
 extern crate raytracer;
 extern crate pbr;
 
+use std::fs;
 use std::io;
+use std::process;
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 use rand::prelude::*;
 use scoped_threadpool::Pool;
@@ -16,18 +21,65 @@ use raytracer::ray_image::RayImage;
 use raytracer::ray::Ray;
 use raytracer::math::*;
 use raytracer::hitable::*;
-use raytracer::camera::Camera;
+use raytracer::camera::{AnimatedCamera, Camera, CameraModel, StereoCamera};
 use raytracer::material::*;
 use raytracer::texture::*;
+use raytracer::spectrum::{self, Ior, WavelengthSampler};
+use raytracer::sampler::*;
+use raytracer::filter::*;
+use raytracer::film::{Bounds, Features, Film, FilmSample, FilmTile, PixelVariance};
+use raytracer::checkpoint::Checkpoint;
+use raytracer::distributed::{Coordinator, Handshake, Job, JobResult, Worker};
+use raytracer::animation::{AnimatedTransform, Interpolation, Track};
 
 mod obj_reader;
+mod cli;
+#[cfg(feature = "preview")]
+mod preview;
 
 const WIDTH: usize = 4096;
 const HEIGHT: usize = 2160;
+// rendering goes by passes of SAMPLES_PER_PASS samples per pixel, until
+// every pixel got MAX_RAYS samples or TIME_LIMIT is over
 const MAX_RAYS: usize = 10;
+const SAMPLES_PER_PASS: usize = 2;
+const TIME_LIMIT: Option<Duration> = None;
+// adaptive sampling stops a pixel once the relative standard error of its
+// luminance gets below the threshold, after at least MIN_RAYS samples, and
+// the render once all pixels are there
+const ADAPTIVE_THRESHOLD: Option<f32> = None;
+const MIN_RAYS: usize = 4;
+// the image being rendered is written at most this often
+const PREVIEW_INTERVAL: Option<Duration> = Some(Duration::from_secs(60));
+// the --preview page shows the image this many times smaller, to keep up
+// with the controls
+#[cfg(feature = "preview")]
+const PREVIEW_DOWNSCALE: usize = 4;
+// also writes the samples per pixel heatmap next to the image
+const SPP_HEATMAP: bool = false;
 const MAX_DEPTH: usize = 50;
+const TILE_SIZE: usize = 32;
+// every random choice of a sample derives from it, same seed same image
+const SEED: u32 = 0;
+// the film is saved this often next to the image, and picked up from there
+// when the render is started again, the file going away once it's done and
+// staying when TIME_LIMIT stops the render short
+const CHECKPOINT_INTERVAL: Option<Duration> = Some(Duration::from_secs(600));
+const SPECTRAL: bool = false;
+const STEREO: Option<StereoLayout> = None;
+// in scene units
+const INTEROCULAR: f32 = 6.5;
 const OUT_PATH: &str = "./output_test/out1.png";
 
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StereoLayout {
+    SideBySide,
+    TopBottom,
+    // one file per eye, suffixed with _left and _right
+    Separate,
+}
+
 fn emit_image_to_file<P: AsRef<Path>>(path: P, image: &RayImage) -> io::Result<()> {
     let (width, height) = image.get_dimensions();
 
@@ -39,7 +91,40 @@ fn emit_image_to_file<P: AsRef<Path>>(path: P, image: &RayImage) -> io::Result<(
     out_image.save(path)
 }
 
+fn emit_rgba_image_to_file<P: AsRef<Path>>(path: P, image: &RayImage<Rgba>) -> io::Result<()> {
+    let (width, height) = image.get_dimensions();
+
+    let out_image = image::ImageBuffer::from_fn(width as _, height as _, |x, y| {
+        let pixel = image.get_pixel(x as _, y as _);
+        image::Rgba([pixel.red, pixel.green, pixel.blue, pixel.alpha])
+    });
+
+    out_image.save(path)
+}
+
+fn suffixed_path(path: &str, suffix: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("out");
+    let file_name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext),
+        None => format!("{}_{}", stem, suffix),
+    };
+    path.with_file_name(file_name).to_string_lossy().into_owned()
+}
+
 fn main() {
+    let options = match cli::parse_args(WIDTH, HEIGHT) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        },
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
     // let lookfrom = Point::new(0.0, 1.0, 3.0);
     let lookfrom = Point::new(-800.0, 800.0, 500.0);
     let lookat = Point::new(-300.0, 800.0, 0.0);
@@ -47,7 +132,16 @@ fn main() {
     let dist_to_focus = (lookfrom - lookat).norm();
     let aperture = 0.0;
     let aspect = WIDTH as f32 / HEIGHT as f32;
-    let camera = Camera::new(lookfrom, lookat, vup, 90.0, aspect, aperture, dist_to_focus);
+    let vfov = 90.0;
+    let camera = Camera::new(lookfrom, lookat, vup, vfov, aspect, aperture, dist_to_focus);
+    // let camera = EquirectangularCamera::new(lookfrom, lookat, vup);
+    // keyframed camera taking over `camera` for the frames of an animation
+    let animated_camera: Option<AnimatedCamera> = None;
+    // let animated_camera = Some(flythrough_camera(lookfrom, lookat, aspect));
+
+    // let lens = PhysicalLens { units_per_meter: 100.0, ..PhysicalLens::full_frame(35.0, 2.8) };
+    // let camera = Camera::from_lens(lookfrom, lookat, vup, lens, aspect, Focus::Point(lookat))
+    //     .with_aperture(Aperture::Polygon { blades: 6, rotation: 0.0 });
 
 // let lookfrom = Point::new(-1000.0, 2000.0, 1000.0);
 //     let lookat = Point::new(0.0, 0.0, -1.0);
@@ -69,63 +163,722 @@ fn main() {
     let world = obj_reader::read_obj_file("./input_test/LAM.obj").unwrap();
     println!("Building BVh..");
     // let world = random_scene();
-    let bvh = BVH::new(world);
-    println!("Raytracing..");
-
-    let image = build_in_parallel(WIDTH, HEIGHT, |x, y, _| {
-        let y = HEIGHT - y - 1;
-        let mut rng = thread_rng();
-        let dx: f32 = rng.gen();
-        let dy: f32 = rng.gen();
-        let u = (x as f32 + dx) / (WIDTH as f32);
-        let v = (y as f32 + dy) / (HEIGHT as f32);
-        let ray = camera.get_ray(u, v);
-        color(ray, &bvh, 0)
+    let world = Arc::new(BVH::new(world));
+    // objects moving during an animation, placed again for every frame
+    let animated_objects: fn(f32) -> Vec<Box<dyn Hitable>> = |_| Vec::new();
+    // let animated_objects: fn(f32) -> Vec<Box<dyn Hitable>> = turntable_scene;
+    // the scene at a frame, and the cameras of its views, the left eye first
+    let frame_world = |frame: usize| {
+        let mut objects = animated_objects(frame as f32);
+        objects.push(Box::new(world.clone()));
+        BVH::new(objects)
+    };
+    let frame_views = |frame: usize| -> Vec<Box<dyn CameraModel>> {
+        let camera = match &animated_camera {
+            Some(animation) => animation.at(frame as f32),
+            None => camera.clone(),
+        };
+        match STEREO {
+            None => vec![Box::new(camera)],
+            Some(_) => {
+                let stereo = StereoCamera::off_axis(&camera, INTEROCULAR, dist_to_focus);
+                // let stereo = StereoCamera::equirectangular(lookfrom, lookat, vup, INTEROCULAR);
+                vec![stereo.left, stereo.right]
+            },
+        }
+    };
+
+    let sampler = options.sampler.build(MAX_RAYS, SEED);
+    let filter = options.filter.build();
+
+    let wavelengths = WavelengthSampler::new();
+    let settings = RenderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        wavelengths: &wavelengths,
+        sampler: sampler.as_ref(),
+        filter: filter.as_ref(),
+        sampler_kind: options.sampler,
+        filter_kind: options.filter,
+        region: options.region.map_or(Bounds::new(0, 0, WIDTH, HEIGHT), |region| region.bounds(WIDTH, HEIGHT)),
+        transparent: options.transparent,
+        time_limit: TIME_LIMIT,
+    };
+
+    // placing the camera from a browser page, starting from the one above
+    if let Some(address) = &options.preview {
+        #[cfg(feature = "preview")]
+        interactive(address, preview::View { lookfrom, lookat, vup, vfov, aperture }, &frame_world(0), &settings, &options.post);
+        #[cfg(not(feature = "preview"))]
+        {
+            eprintln!("--preview {} needs rt_driver built with --features preview", address);
+            process::exit(2);
+        }
+    }
+
+    // workers render what the coordinator asks for, with their own copy of
+    // the scene, and write nothing
+    let handshake = Handshake {
+        seed: SEED,
+        width: WIDTH,
+        height: HEIGHT,
+        sampler: options.sampler,
+        filter: options.filter,
+        max_samples: MAX_RAYS,
+        spectral: SPECTRAL,
+    };
+    if let Some(address) = &options.worker {
+        println!("Working for {}..", address);
+        work(address, handshake, &settings, frame_world, frame_views);
+        return;
+    }
+    let coordinator = options.coordinator.as_ref().map(|address| {
+        match Coordinator::bind(address, handshake) {
+            Ok(coordinator) => {
+                println!("Waiting for workers on {}..", address);
+                coordinator
+            },
+            Err(err) => {
+                eprintln!("Error listening on {}: {}", address, err);
+                process::exit(1);
+            },
+        }
     });
-    
-    emit_image_to_file(OUT_PATH, &image).expect("Error writing image")
+
+    // with an alpha channel only when the background is transparent
+    let write_image = |path: &str, image: &RayImage<Rgba>| {
+        let written = if options.transparent {
+            emit_rgba_image_to_file(path, image)
+        } else {
+            emit_image_to_file(path, &image.map(Rgba::color))
+        };
+        written.expect("Error writing image")
+    };
+    // the region alone, or the whole frame with the rest left empty
+    let output = |film: &Film| if options.crop { film.crop(settings.region) } else { film.clone() };
+    // previews of each eye go to their own file, whatever the stereo layout
+    let render_image = |frame: usize, view: usize, bvh: &BVH, camera: &dyn CameraModel, path: &str| {
+        let checkpoint_path = Path::new(path).with_extension("checkpoint");
+        let aovs = !options.aovs.is_empty();
+        let film = if aovs { Film::new(WIDTH, HEIGHT).with_aovs() } else { Film::new(WIDTH, HEIGHT) };
+        let take_samples = |film: &mut Film, active: &[bool], samples: Range<usize>| match &coordinator {
+            Some(coordinator) => {
+                let pixel_func = path_tracer(camera, bvh, &settings);
+                distribute(coordinator, film, &settings, frame, view, samples, active, pixel_func)
+            },
+            None => {
+                let pixel_func = path_tracer(camera, bvh, &settings);
+                build_in_parallel(film, settings.region, samples, active, settings.sampler, settings.filter, pixel_func)
+            },
+        };
+        let film = render(&settings, film, &checkpoint_path, take_samples, |film| {
+            write_image(path, &options.post.develop_rgba(&output(film)))
+        });
+        let film = output(&film);
+        match options.aov_format {
+            cli::AovFormat::Png => for &aov in &options.aovs {
+                emit_image_to_file(suffixed_path(path, aov.name()), &film.aov_image(aov)).expect("Error writing AOV")
+            },
+            cli::AovFormat::Exr => if aovs {
+                film.write_exr(Path::new(path).with_extension("exr"), &options.aovs).expect("Error writing EXR")
+            },
+        }
+        if SPP_HEATMAP {
+            let heatmap = film.sample_heatmap(MAX_RAYS);
+            emit_image_to_file(suffixed_path(path, "spp"), &heatmap).expect("Error writing heatmap")
+        }
+        match &options.denoise {
+            Some(denoiser) => {
+                println!("Denoising..");
+                let rendered = if options.crop { film.bounds() } else { settings.region };
+                options.post.develop_rgba(&denoiser.denoise(&film, rendered))
+            },
+            None => options.post.develop_rgba(&film),
+        }
+    };
+
+    // a still is frame 0 of an animation
+    for frame in options.frames.clone().unwrap_or(0..=0) {
+        let out_path = match options.frames {
+            Some(_) => suffixed_path(OUT_PATH, &format!("{:04}", frame)),
+            None => OUT_PATH.to_string(),
+        };
+        let bvh = frame_world(frame);
+        let views = frame_views(frame);
+        match options.frames {
+            Some(_) => println!("Raytracing frame {}..", frame),
+            None => println!("Raytracing.."),
+        }
+
+        match STEREO {
+            None => {
+                let image = render_image(frame, 0, &bvh, views[0].as_ref(), &out_path);
+                write_image(&out_path, &image)
+            },
+            Some(layout) => {
+                let left = render_image(frame, 0, &bvh, views[0].as_ref(), &suffixed_path(&out_path, "left"));
+                let right = render_image(frame, 1, &bvh, views[1].as_ref(), &suffixed_path(&out_path, "right"));
+
+                match layout {
+                    StereoLayout::SideBySide => {
+                        write_image(&out_path, &RayImage::side_by_side(&left, &right))
+                    },
+                    StereoLayout::TopBottom => {
+                        write_image(&out_path, &RayImage::top_bottom(&left, &right))
+                    },
+                    StereoLayout::Separate => {
+                        write_image(&suffixed_path(&out_path, "left"), &left);
+                        write_image(&suffixed_path(&out_path, "right"), &right)
+                    },
+                }
+            },
+        }
+    }
+}
+
+// how the pixels are sampled, shared by the renders of every eye
+struct RenderSettings<'a> {
+    // of the film, the camera covering all of it
+    width: usize,
+    height: usize,
+    wavelengths: &'a WavelengthSampler,
+    sampler: &'a dyn Sampler,
+    filter: &'a dyn Filter,
+    // what the sampler and filter were built from, kept in the checkpoints
+    sampler_kind: SamplerKind,
+    filter_kind: FilterKind,
+    // only these pixels get samples, the others staying empty
+    region: Bounds,
+    // camera rays missing the scene are left out
+    transparent: bool,
+    // TIME_LIMIT, kept here to stop test renders short
+    time_limit: Option<Duration>,
+}
+
+// radiance and features of a sample at a continuous film position
+fn path_tracer<'a, H: Hitable>(
+    camera: &'a dyn CameraModel,
+    world: &'a H,
+    settings: &RenderSettings<'a>,
+) -> impl Fn(f32, f32, SampleIndex) -> FilmSample + Send + Copy + 'a {
+    let RenderSettings { width, height, wavelengths, sampler, transparent, .. } = *settings;
+    let background = FilmSample {
+        radiance: Vector::zero(),
+        alpha: if transparent { 0.0 } else { 1.0 },
+        direct: Vector::zero(),
+        features: Features::zero(),
+    };
+    move |x: f32, y: f32, at: SampleIndex| {
+        // the film goes down while the camera v goes up
+        let mut rng = sample_rng(at, SEED);
+        let u = x / (width as f32);
+        let v = 1.0 - y / (height as f32);
+        let ray = match camera.generate_ray(u, v, &mut rng) {
+            Some(ray) => ray,
+            None => return background,
+        };
+
+        let mut path = PathInfos::default();
+        let radiance = if SPECTRAL {
+            // one wavelength per path, every channel of the result holds its radiance
+            let (wavelength, pdf) = wavelengths.sample(sampler.get_2d(at, 1).0);
+            let radiance = color(ray.with_wavelength(Some(wavelength)), world, 0, None, &mut rng, &mut path);
+            wavelengths.rgb(wavelength, radiance.x, pdf)
+        } else {
+            color(ray, world, 0, None, &mut rng, &mut path)
+        };
+        let features = path.features.unwrap_or_else(Features::zero);
+        if transparent && path.escaped {
+            return FilmSample { features, ..background };
+        }
+        let direct = if path.bounces <= 1 { radiance } else { Vector::zero() };
+        FilmSample { radiance, alpha: 1.0, direct, features }
+    }
 }
 
-fn color<H: Hitable>(ray: Ray, hitable: &H, depth: usize) -> Color {
-    if let Some(infos) = hitable.hit(ray, 0.001, std::f32::MAX) {
+// progressive rendering into `film`, unless a checkpoint of the same film is
+// found, `take_samples` taking a range of samples of the active pixels and
+// `preview` being handed the film between passes
+fn render<S, P>(settings: &RenderSettings, film: Film, checkpoint_path: &Path, mut take_samples: S, preview: P) -> Film
+    where S: FnMut(&mut Film, &[bool], Range<usize>) -> usize, P: Fn(&Film)
+{
+    let region = settings.region;
+    let resumed = CHECKPOINT_INTERVAL.and_then(|_| resume(checkpoint_path, &film, settings));
+    let (mut film, first_sample, previous_elapsed) = match resumed {
+        Some(checkpoint) => (checkpoint.film, checkpoint.next_sample, checkpoint.elapsed),
+        None => (film, 0, Duration::from_secs(0)),
+    };
+
+    let start = Instant::now();
+    let elapsed = || previous_elapsed + start.elapsed();
+    let mut last_preview = start;
+    let mut last_checkpoint = start;
+    // counting only the samples still to take
+    let mut progress_bar = pbr::ProgressBar::new((region.area() * (MAX_RAYS - first_sample.min(MAX_RAYS))) as u64);
+    // first sample still to take when TIME_LIMIT stopped the render short
+    let mut stopped_at = None;
+
+    for first in (first_sample..MAX_RAYS).step_by(SAMPLES_PER_PASS) {
+        let active = active_pixels(&film, region);
+        if !active.iter().any(|&a| a) {
+            break;
+        }
+
+        let samples_end = (first + SAMPLES_PER_PASS).min(MAX_RAYS);
+        let taken = take_samples(&mut film, &active, first..samples_end);
+        progress_bar.add(taken as u64);
+
+        if samples_end < MAX_RAYS && settings.time_limit.is_some_and(|limit| elapsed() >= limit) {
+            stopped_at = Some(samples_end);
+            break;
+        }
+        if PREVIEW_INTERVAL.is_some_and(|interval| last_preview.elapsed() >= interval) {
+            preview(&film);
+            last_preview = Instant::now();
+        }
+        if CHECKPOINT_INTERVAL.is_some_and(|interval| last_checkpoint.elapsed() >= interval) {
+            film = save_checkpoint(checkpoint_path, settings, film, samples_end, elapsed());
+            last_checkpoint = Instant::now();
+        }
+    }
+
+    progress_bar.finish();
+    // the checkpoint of a render cut short stays to go on with it later
+    match stopped_at {
+        Some(next_sample) => if CHECKPOINT_INTERVAL.is_some() {
+            film = save_checkpoint(checkpoint_path, settings, film, next_sample, elapsed());
+        },
+        None => if checkpoint_path.exists() {
+            fs::remove_file(checkpoint_path).expect("Error removing checkpoint");
+        },
+    }
+    film
+}
+
+fn save_checkpoint(path: &Path, settings: &RenderSettings, film: Film, next_sample: usize, elapsed: Duration) -> Film {
+    let checkpoint = Checkpoint {
+        film,
+        seed: SEED,
+        sampler: settings.sampler_kind,
+        filter: settings.filter_kind,
+        region: settings.region,
+        samples_per_pass: SAMPLES_PER_PASS,
+        max_samples: MAX_RAYS,
+        max_depth: MAX_DEPTH,
+        adaptive_threshold: ADAPTIVE_THRESHOLD,
+        spectral: SPECTRAL,
+        next_sample,
+        elapsed,
+    };
+    checkpoint.save(path).expect("Error writing checkpoint");
+    checkpoint.film
+}
+
+// checkpoint left by a previous run of the same render, if any
+fn resume(path: &Path, film: &Film, settings: &RenderSettings) -> Option<Checkpoint> {
+    if !path.exists() {
+        return None;
+    }
+
+    match Checkpoint::load(path) {
+        Ok(checkpoint) => {
+            let saved = &checkpoint.film;
+            if checkpoint.seed != SEED
+                || checkpoint.sampler != settings.sampler_kind
+                || checkpoint.filter != settings.filter_kind
+                || checkpoint.region != settings.region
+                || checkpoint.samples_per_pass != SAMPLES_PER_PASS
+                || checkpoint.max_samples != MAX_RAYS
+                || checkpoint.max_depth != MAX_DEPTH
+                || checkpoint.adaptive_threshold != ADAPTIVE_THRESHOLD
+                || checkpoint.spectral != SPECTRAL
+                || saved.get_dimensions() != film.get_dimensions()
+                || saved.has_aovs() != film.has_aovs()
+            {
+                println!("Ignoring checkpoint {}, it was made with other settings", path.display());
+                None
+            } else {
+                println!("Resuming from {} at sample {}", path.display(), checkpoint.next_sample);
+                Some(checkpoint)
+            }
+        },
+        Err(err) => {
+            println!("Ignoring checkpoint {}: {}", path.display(), err);
+            None
+        },
+    }
+}
+
+// what a path went through besides its radiance
+#[derive(Debug, Clone, Copy, Default)]
+struct PathInfos {
+    // of the first surface that isn't a mirror, which are seen through
+    features: Option<Features>,
+    // distance travelled until the features were found
+    distance: f32,
+    // number of surfaces hit before the path ended
+    bounces: usize,
+    // the camera ray went straight to the sky
+    escaped: bool,
+}
+
+// linear radiance along the ray, `medium` being the absorption coefficient of
+// the object the ray is travelling through
+fn color<H: Hitable>(
+    ray: Ray,
+    hitable: &H,
+    depth: usize,
+    medium: Option<Vector>,
+    rng: &mut dyn RngCore,
+    path: &mut PathInfos,
+) -> Vector {
+    path.bounces = depth;
+    if let Some(infos) = hitable.hit(ray, 0.001, f32::MAX) {
+        let distance = ray.direction.norm() * infos.t;
         if depth < MAX_DEPTH {
-            if let Some(mat_infos) = infos.material.scatter(ray, &infos) {
-                return color(mat_infos.scattered, hitable, depth + 1) * mat_infos.attenuation;
+            if let Some(mat_infos) = infos.material.sample(ray, &infos, rng) {
+                if path.features.is_none() {
+                    if mat_infos.flags.is_delta() {
+                        path.distance += distance;
+                    } else {
+                        path.features = Some(Features::surface(&infos, path.distance + distance));
+                    }
+                }
+
+                let scattered = mat_infos.scattered.with_wavelength(ray.wavelength);
+                let attenuation = spectrum::at_wavelength(mat_infos.attenuation, ray.wavelength);
+
+                // going through the surface enters or leaves the medium it encloses
+                let next_medium = if mat_infos.flags.contains(BsdfFlags::TRANSMISSION) {
+                    if ray.direction.dot(infos.geometric_normal) < 0.0 {
+                        infos.material.absorption()
+                    } else {
+                        None
+                    }
+                } else {
+                    medium
+                };
+
+                let transmittance = beer_lambert(medium, distance, ray.wavelength);
+                return color(scattered, hitable, depth + 1, next_medium, rng, path) * attenuation * transmittance;
             }
         }
-        Color::new(0, 0, 0)
+        if path.features.is_none() {
+            path.features = Some(Features::surface(&infos, path.distance + distance));
+        }
+        Vector::zero()
     } else {
         let unit_direction = ray.direction.normalized();
         let t = 0.5 * (unit_direction.y + 1.0);
         let v = Vector::new(1.0, 1.0, 1.0) * (1.0 - t) + Vector::new(0.5, 0.7, 1.0) * t;
-        Color::from_vector(v)
-    }    
+        if path.features.is_none() {
+            path.features = Some(Features::sky(v));
+        }
+        path.escaped = depth == 0;
+        spectrum::at_wavelength(v, ray.wavelength)
+    }
+}
+
+fn beer_lambert(medium: Option<Vector>, distance: f32, wavelength: Option<f32>) -> Vector {
+    match medium {
+        Some(sigma) => {
+            let sigma = spectrum::at_wavelength(sigma, wavelength);
+            Vector::new((-sigma.x * distance).exp(), (-sigma.y * distance).exp(), (-sigma.z * distance).exp())
+        },
+        None => Vector::new(1.0, 1.0, 1.0),
+    }
 }
 
-fn build_in_parallel<F>(width: usize, height: usize, pixel_func: F) -> RayImage
-    where F: Send + Copy + Fn(usize, usize, usize) -> Color
+// pixels of the region still needing samples, row by row over the whole film
+fn active_pixels(film: &Film, region: Bounds) -> Vec<bool> {
+    let (width, height) = film.get_dimensions();
+    let mut active = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            active.push(region.contains(x, y) && needs_samples(&film.pixel_variance(x, y), ADAPTIVE_THRESHOLD));
+        }
+    }
+    active
+}
+
+// a pixel keeps sampling until its relative error gets below the threshold,
+// and always takes MIN_RAYS samples first
+fn needs_samples(variance: &PixelVariance, threshold: Option<f32>) -> bool {
+    match threshold {
+        Some(threshold) => variance.count() < MIN_RAYS || variance.relative_error() >= threshold,
+        None => true,
+    }
+}
+
+// takes the `samples` of every active pixel of `region`, `pixel_func` giving
+// the radiance of a sample at a continuous film position, the samples being
+// splatted on the region through the reconstruction filter, returns the
+// number of samples
+fn build_in_parallel<F>(
+    film: &mut Film,
+    region: Bounds,
+    samples: Range<usize>,
+    active: &[bool],
+    sampler: &dyn Sampler,
+    filter: &dyn Filter,
+    pixel_func: F,
+) -> usize
+    where F: Send + Copy + Fn(f32, f32, SampleIndex) -> FilmSample
 {
-    let mut image = RayImage::new(width, height);
+    let (width, _) = film.get_dimensions();
+    let mut tiles: Vec<_> = film.tiles(region, TILE_SIZE)
+        .into_iter()
+        .map(|bounds| (bounds, film.tile(bounds, region, filter)))
+        .collect();
+    let taken = AtomicUsize::new(0);
     let mut pool = Pool::new(4);
-    let progress_bar = &Arc::new(Mutex::new(pbr::ProgressBar::new((width * height) as u64)));
 
     pool.scoped(|scoped| {
-        for (x, y, pixel) in image.pixel_mut_iter() {
+        for (bounds, tile) in &mut tiles {
+            let (bounds, taken) = (*bounds, &taken);
+            let samples = samples.clone();
             scoped.execute(move || {
-                let mut avger = ColorAverager::new();
-                for n in 0..MAX_RAYS {
-                    let color = pixel_func(x, y, n);
-                    avger.add(color);
+                let active = |x, y| active[y * width + x];
+                let count = sample_tile(tile, bounds, samples, active, sampler, filter, pixel_func);
+                taken.fetch_add(count, Ordering::Relaxed);
+            })
+        }
+    });
+
+    // always merged in the same order, the tiles overlap by the filter radius
+    // and float sums depend on their order
+    for (_, tile) in &tiles {
+        film.merge_tile(tile);
+    }
+    taken.into_inner()
+}
+
+// takes the `samples` of the active pixels of `bounds`, splatted on `tile`,
+// returns the number of samples
+fn sample_tile<A, F>(
+    tile: &mut FilmTile,
+    bounds: Bounds,
+    samples: Range<usize>,
+    active: A,
+    sampler: &dyn Sampler,
+    filter: &dyn Filter,
+    pixel_func: F,
+) -> usize
+    where A: Fn(usize, usize) -> bool, F: Fn(f32, f32, SampleIndex) -> FilmSample
+{
+    let mut count = 0;
+    for y in bounds.y0..bounds.y1 {
+        for x in bounds.x0..bounds.x1 {
+            if !active(x, y) {
+                continue;
+            }
+            for index in samples.clone() {
+                let at = SampleIndex { x, y, index };
+                let (dx, dy) = sampler.get_2d(at, 0);
+                let (fx, fy) = (x as f32 + dx, y as f32 + dy);
+                let sample = pixel_func(fx, fy, at);
+                tile.add_sample(fx, fy, &sample, filter);
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+// `build_in_parallel` done by the workers of `coordinator`, a job per tile
+// with active pixels, the tiles coming back being merged in the same order
+// so the film is the same as a local render, `pixel_func` rendering the
+// jobs here when no worker is connected
+#[allow(clippy::too_many_arguments)]
+fn distribute<F>(
+    coordinator: &Coordinator,
+    film: &mut Film,
+    settings: &RenderSettings,
+    frame: usize,
+    view: usize,
+    samples: Range<usize>,
+    active: &[bool],
+    pixel_func: F,
+) -> usize
+    where F: Send + Copy + Fn(f32, f32, SampleIndex) -> FilmSample
+{
+    let (width, _) = film.get_dimensions();
+    let region = settings.region;
+    let jobs = film.tiles(region, TILE_SIZE)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(id, bounds)| {
+            let active: Vec<bool> = (bounds.y0..bounds.y1)
+                .flat_map(|y| active[y * width + bounds.x0..y * width + bounds.x1].iter().copied())
+                .collect();
+            if !active.contains(&true) {
+                return None;
+            }
+            Some(Job {
+                id,
+                frame,
+                view,
+                bounds,
+                tile: film.tile_bounds(bounds, region, settings.filter),
+                aovs: film.has_aovs(),
+                transparent: settings.transparent,
+                samples: samples.clone(),
+                active,
+            })
+        })
+        .collect();
+
+    let results = coordinator.run(jobs, |jobs| {
+        println!("No worker, rendering {} tiles here..", jobs.len());
+        render_jobs(&jobs, settings, pixel_func)
+    });
+    for result in &results {
+        film.merge_tile(&result.tile);
+    }
+    results.iter().map(|result| result.taken).sum()
+}
+
+// the tiles of `jobs` as the workers render them
+fn render_jobs<F>(jobs: &[Job], settings: &RenderSettings, pixel_func: F) -> Vec<JobResult>
+    where F: Send + Copy + Fn(f32, f32, SampleIndex) -> FilmSample
+{
+    let mut results: Vec<JobResult> = jobs.iter()
+        .map(|job| JobResult { id: job.id, taken: 0, tile: FilmTile::new(job.tile, job.aovs) })
+        .collect();
+    let (sampler, filter) = (settings.sampler, settings.filter);
+    let mut pool = Pool::new(4);
+    pool.scoped(|scoped| {
+        for (job, result) in jobs.iter().zip(&mut results) {
+            scoped.execute(move || {
+                let active = |x, y| job.is_active(x, y);
+                result.taken = sample_tile(&mut result.tile, job.bounds, job.samples.clone(), active, sampler, filter, pixel_func);
+            })
+        }
+    });
+    results
+}
+
+// renders the view of the page at `address` progressively, a sample per
+// pixel at a time, starting over whenever the view moves
+#[cfg(feature = "preview")]
+fn interactive<H: Hitable>(
+    address: &str,
+    mut view: preview::View,
+    world: &H,
+    settings: &RenderSettings,
+    post: &raytracer::post::PostProcess,
+) -> ! {
+    let (width, height) = ((WIDTH / PREVIEW_DOWNSCALE).max(1), (HEIGHT / PREVIEW_DOWNSCALE).max(1));
+    let region = Bounds::new(0, 0, width, height);
+    let settings = RenderSettings { width, height, region, ..*settings };
+    let page = match preview::Preview::serve(address, view) {
+        Ok(page) => page,
+        Err(err) => {
+            eprintln!("Error listening on {}: {}", address, err);
+            process::exit(1);
+        },
+    };
+    println!("Previewing on http://{}/", address);
+
+    let active = vec![true; width * height];
+    loop {
+        let camera = view.camera(width as f32 / height as f32);
+        let pixel_func = path_tracer(&camera, world, &settings);
+        let mut film = Film::new(width, height);
+        let mut moved = false;
+        for index in 0..MAX_RAYS {
+            if page.update(&mut view, false) {
+                moved = true;
+                break;
+            }
+            build_in_parallel(&mut film, region, index..index + 1, &active, settings.sampler, settings.filter, pixel_func);
+            let (from, at) = (view.lookfrom, view.lookat);
+            let status = format!(
+                "{} / {} samples, lookfrom ({}, {}, {}), lookat ({}, {}, {})",
+                index + 1, MAX_RAYS, from.x, from.y, from.z, at.x, at.y, at.z,
+            );
+            page.publish(&post.develop_rgba(&film).map(Rgba::color), status).expect("Error encoding preview");
+        }
+        if !moved {
+            page.update(&mut view, true);
+        }
+    }
+}
+
+// renders the jobs of the coordinator at `address` until it's done, with a
+// connection per thread, the scene of a frame being built on its first job
+fn work<W, V>(address: &str, handshake: Handshake, settings: &RenderSettings, frame_world: W, frame_views: V)
+    where W: Fn(usize) -> BVH + Sync, V: Fn(usize) -> Vec<Box<dyn CameraModel>> + Sync
+{
+    let serve = |worker: &mut Worker| -> io::Result<()> {
+        let mut scene = None;
+        while let Some(job) = worker.next_job()? {
+            if scene.as_ref().map(|&(frame, _, _)| frame) != Some(job.frame) {
+                scene = Some((job.frame, frame_world(job.frame), frame_views(job.frame)));
+            }
+            let (_, bvh, views) = scene.as_ref().unwrap();
+            let camera = views.get(job.view)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "view of a stereo render"))?;
+
+            let settings = RenderSettings { transparent: job.transparent, ..*settings };
+            let pixel_func = path_tracer(camera.as_ref(), bvh, &settings);
+            let mut tile = FilmTile::new(job.tile, job.aovs);
+            let active = |x, y| job.is_active(x, y);
+            let taken = sample_tile(&mut tile, job.bounds, job.samples.clone(), active, settings.sampler, settings.filter, pixel_func);
+            worker.send(&JobResult { id: job.id, taken, tile })?;
+        }
+        Ok(())
+    };
+
+    let mut pool = Pool::new(4);
+    pool.scoped(|scoped| {
+        for _ in 0..4 {
+            scoped.execute(|| {
+                let served = Worker::connect(address, handshake).and_then(|mut worker| serve(&mut worker));
+                if let Err(err) = served {
+                    eprintln!("Error working for {}: {}", address, err);
                 }
-                let mut final_color = avger.average();
-                final_color.apply_func(|c| ((c as f64 / 255.0).sqrt() * 255.0) as u8);
-                *pixel = final_color;
-                progress_bar.lock().unwrap().inc();
             })
         }
     });
+}
 
-    image
+// orbit around `lookat` over 120 frames, going up and zooming in on the way
+#[allow(dead_code)]
+fn flythrough_camera(lookfrom: Point, lookat: Point, aspect: f32) -> AnimatedCamera {
+    let offset = lookfrom - lookat;
+    let orbit = |frame: f32, height: f32| {
+        let p = Matrix3::rotation(Vector::new(0.0, 1.0, 0.0), frame * 3.0).apply(offset);
+        lookat + Vector::new(p.x, p.y + height, p.z)
+    };
+    let mut path = Track::new(Interpolation::Spline);
+    for &frame in &[0.0, 30.0, 60.0, 90.0, 120.0] {
+        path = path.with_key(frame, orbit(frame, frame / 120.0 * offset.norm() * 0.5));
+    }
+    let vfov = Track::new(Interpolation::Linear).with_key(0.0, 90.0).with_key(120.0, 50.0);
+    AnimatedCamera::new(path, Track::constant(lookat), Vector::new(0.0, 1.0, 0.0), vfov, aspect)
+}
+
+// the three spheres turning once around the vertical axis in 120 frames,
+// the middle one bouncing
+#[allow(dead_code)]
+fn turntable_scene(frame: f32) -> Vec<Box<dyn Hitable>> {
+    let turn = AnimatedTransform::new()
+        .with_rotation(Track::new(Interpolation::Linear)
+            .with_key(0.0, Vector::zero())
+            .with_key(120.0, Vector::new(0.0, 360.0, 0.0)));
+    let bounce = AnimatedTransform::new()
+        .with_translation(Track::new(Interpolation::Spline)
+            .with_key(0.0, Vector::zero())
+            .with_key(30.0, Vector::new(0.0, 0.5, 0.0))
+            .with_key(60.0, Vector::zero())
+            .with_key(90.0, Vector::new(0.0, 0.5, 0.0))
+            .with_key(120.0, Vector::zero()));
+
+    let mut spheres = three_sphere_scene();
+    let middle = spheres.remove(1);
+    spheres.push(Box::new(Transformed::new(middle, bounce.at(frame))));
+    vec![Box::new(Transformed::new(BVH::new(spheres), turn.at(frame)))]
 }
 
 fn lambertian_from_float_comp(red: f32, green: f32, blue: f32) -> impl Material {
@@ -173,6 +926,80 @@ fn three_sphere_scene() -> Vec<Box<dyn Hitable>> {
     ]
 }
 
+#[allow(dead_code)]
+fn microfacet_scene() -> Vec<Box<dyn Hitable>> {
+    vec![
+        Box::new(Sphere::new(Point::new(0.0, -100.5, -1.0), 100.0, lambertian_from_float_comp(0.5, 0.5, 0.5))),
+        Box::new(Sphere::new(Point::new(-2.0, 0.0, -1.0), 0.5, RoughConductor::gold(GGX::isotropic(0.3)))),
+        Box::new(Sphere::new(Point::new(-1.0, 0.0, -1.0), 0.5, RoughConductor::copper(GGX::anisotropic(0.4, 0.8)))),
+        Box::new(Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, RoughConductor::aluminium(GGX::isotropic(0.1)))),
+        Box::new(Sphere::new(Point::new(1.0, 0.0, -1.0), 0.5, RoughDielectric::new(1.5, GGX::isotropic(0.2)))),
+    ]
+}
+
+#[allow(dead_code)]
+fn principled_scene() -> Vec<Box<dyn Hitable>> {
+    let base = |r, g, b| -> ConstantTexture { Color::from_floats(r, g, b).into() };
+    let plastic = Principled::new(base(0.8, 0.1, 0.1))
+        .with_roughness(ConstantTexture::scalar(0.3));
+    let car_paint = Principled::new(base(0.1, 0.2, 0.6))
+        .with_metallic(ConstantTexture::scalar(0.5))
+        .with_clearcoat(ConstantTexture::scalar(1.0), ConstantTexture::scalar(0.05));
+    let velvet = Principled::new(base(0.4, 0.1, 0.4))
+        .with_roughness(ConstantTexture::scalar(0.9))
+        .with_sheen(ConstantTexture::scalar(1.0));
+    let frosted_glass = Principled::new(base(0.9, 1.0, 0.9))
+        .with_roughness(ConstantTexture::scalar(0.2))
+        .with_transmission(ConstantTexture::scalar(1.0), 1.5);
+
+    vec![
+        Box::new(Sphere::new(Point::new(0.0, -100.5, -1.0), 100.0, lambertian_from_float_comp(0.5, 0.5, 0.5))),
+        Box::new(Sphere::new(Point::new(-2.0, 0.0, -1.0), 0.5, plastic)),
+        Box::new(Sphere::new(Point::new(-1.0, 0.0, -1.0), 0.5, car_paint)),
+        Box::new(Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, velvet)),
+        Box::new(Sphere::new(Point::new(1.0, 0.0, -1.0), 0.5, frosted_glass)),
+    ]
+}
+
+#[allow(dead_code)]
+fn layered_scene() -> Vec<Box<dyn Hitable>> {
+    let wood: ConstantTexture = Color::from_floats(0.5, 0.3, 0.1).into();
+    let varnished_wood = Coated::with_tint(Lambertian::new(wood), 1.5, Vector::new(0.95, 0.9, 0.8));
+    let dust: ConstantTexture = Color::from_floats(0.6, 0.55, 0.5).into();
+    let dusty_metal = MixMaterial::new(
+        RoughConductor::aluminium(GGX::isotropic(0.2)),
+        Lambertian::new(dust),
+        PerlinTexture::new(5.0)
+    );
+
+    vec![
+        Box::new(Sphere::new(Point::new(0.0, -100.5, -1.0), 100.0, lambertian_from_float_comp(0.5, 0.5, 0.5))),
+        Box::new(Sphere::new(Point::new(-0.6, 0.0, -1.0), 0.5, varnished_wood)),
+        Box::new(Sphere::new(Point::new(0.6, 0.0, -1.0), 0.5, dusty_metal)),
+    ]
+}
+
+#[allow(dead_code)]
+fn dispersion_scene() -> Vec<Box<dyn Hitable>> {
+    vec![
+        Box::new(Sphere::new(Point::new(0.0, -100.5, -1.0), 100.0, lambertian_from_float_comp(0.8, 0.8, 0.8))),
+        Box::new(Sphere::new(Point::new(-0.6, 0.0, -1.0), 0.5, Dielectric::dispersive(Ior::diamond()))),
+        Box::new(Sphere::new(Point::new(0.6, 0.0, -1.0), 0.5, Dielectric::dispersive(Ior::Cauchy { a: 1.5, b: 0.02 }))),
+    ]
+}
+
+#[allow(dead_code)]
+fn absorbing_glass_scene() -> Vec<Box<dyn Hitable>> {
+    let green_glass = Dielectric::new(1.5).with_absorption(Vector::new(0.2, 0.8, 0.3), 1.0);
+    let frosted_amber = RoughDielectric::new(1.5, GGX::isotropic(0.2)).with_absorption(Vector::new(0.9, 0.5, 0.1), 0.5);
+
+    vec![
+        Box::new(Sphere::new(Point::new(0.0, -100.5, -1.0), 100.0, lambertian_from_float_comp(0.8, 0.8, 0.8))),
+        Box::new(Sphere::new(Point::new(-0.6, 0.0, -1.0), 0.5, green_glass)),
+        Box::new(Sphere::new(Point::new(0.6, 0.0, -1.0), 0.5, frosted_amber)),
+    ]
+}
+
 #[allow(dead_code)]
 fn two_checker_sphere() -> Vec<Box<dyn Hitable>> {
     let odd_texture: ConstantTexture = Color::from_floats(0.2, 0.3, 0.1).into();
@@ -253,4 +1080,339 @@ fn random_scene() -> Vec<Box<dyn Hitable>> {
     )));
 
     spheres
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+
+    // radiance of a ray going through the center of a sphere of radius 1 of
+    // glass matching the index of the air, to the sky behind it
+    fn through_glass(glass: Dielectric, wavelength: Option<f32>) -> Vector {
+        let world = BVH::new(vec![Box::new(Sphere::new(Point::new(0.0, 0.0, -3.0), 1.0, glass))]);
+        let ray = Ray::new(Point::origin(), Vector::new(0.0, 0.0, -1.0)).with_wavelength(wavelength);
+        let mut rng = XorShiftRng::from_seed([1; 16]);
+        color(ray, &world, 0, None, &mut rng, &mut PathInfos::default())
+    }
+
+    #[test]
+    fn glass_absorbs_along_the_distance_inside() {
+        let sky = through_glass(Dielectric::new(1.0), None);
+        // the color is what is left after a distance of 1, the ray travels 2 inside
+        let tinted = through_glass(Dielectric::new(1.0).with_absorption(Vector::new(0.5, 0.8, 0.9), 1.0), None);
+        let expected = sky * Vector::new(0.25, 0.64, 0.81);
+        assert!((tinted - expected).norm() < 1e-4, "{:?} != {:?}", tinted, expected);
+
+        let thin = through_glass(Dielectric::new(1.0).with_absorption(Vector::new(0.5, 0.8, 0.9), 2.0), None);
+        assert!((thin - sky * Vector::new(0.5, 0.8, 0.9)).norm() < 1e-4, "{:?}", thin);
+    }
+
+    #[test]
+    fn absorption_is_taken_at_the_wavelength() {
+        let glass = || Dielectric::new(1.0).with_absorption(Vector::new(0.1, 0.1, 0.9), 1.0);
+        // blue glass lets blue through and stops red
+        let blue = through_glass(glass(), Some(450.0)).x / through_glass(Dielectric::new(1.0), Some(450.0)).x;
+        let red = through_glass(glass(), Some(650.0)).x / through_glass(Dielectric::new(1.0), Some(650.0)).x;
+        assert!(blue > 0.5 && red < 0.05, "blue {} red {}", blue, red);
+        assert_eq!(beer_lambert(None, 10.0, Some(500.0)), Vector::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn flat_pixels_stop_after_the_minimum() {
+        let mut variance = PixelVariance::default();
+        for count in 0..MIN_RAYS {
+            assert!(needs_samples(&variance, Some(0.05)), "stopped after {} samples", count);
+            variance.add(Vector::new(0.3, 0.3, 0.3));
+        }
+        assert!(!needs_samples(&variance, Some(0.05)));
+        // unless sampling isn't adaptive
+        assert!(needs_samples(&variance, None));
+    }
+
+    #[test]
+    fn noisy_pixels_sample_until_their_error_is_small() {
+        // alternating black and white, a relative error close to 1 / sqrt(count)
+        let mut variance = PixelVariance::default();
+        let mut stopped_at = None;
+        for count in 0..1000 {
+            if !needs_samples(&variance, Some(0.1)) {
+                stopped_at = Some(count);
+                break;
+            }
+            variance.add(Vector::new(1.0, 1.0, 1.0) * (count % 2) as f32);
+        }
+        let stopped_at = stopped_at.expect("noisy pixel never stopped");
+        assert!(stopped_at > 90 && stopped_at < 110, "stopped after {} samples", stopped_at);
+    }
+
+    #[test]
+    fn only_active_pixels_of_the_region_are_sampled() {
+        let (width, height) = (8, 4);
+        let world = BVH::new(vec![
+            Box::new(Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, Lambertian::new(ConstantTexture::new(Color::new(200, 100, 50))))),
+        ]);
+        let camera = Camera::new(Point::new(0.0, 0.0, 1.0), Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 0.0), 40.0, 2.0, 0.0, 2.0);
+        let wavelengths = WavelengthSampler::new();
+        let sampler = SamplerKind::Random.build(MAX_RAYS, SEED);
+        let filter = FilterKind::Box.build();
+        let settings = RenderSettings {
+            width,
+            height,
+            wavelengths: &wavelengths,
+            sampler: sampler.as_ref(),
+            filter: filter.as_ref(),
+            sampler_kind: SamplerKind::Random,
+            filter_kind: FilterKind::Box,
+            region: Bounds::new(2, 1, 6, 3),
+            transparent: false,
+            time_limit: None,
+        };
+
+        let mut film = Film::new(width, height);
+        let active = active_pixels(&film, settings.region);
+        assert_eq!(active.iter().filter(|&&a| a).count(), settings.region.area());
+
+        // every other pixel of the region has converged
+        let mask: Vec<bool> = active.iter().enumerate().map(|(i, &a)| a && i % 2 == 0).collect();
+        let taken = build_in_parallel(&mut film, settings.region, 0..3, &mask, settings.sampler, settings.filter, path_tracer(&camera, &world, &settings));
+        assert_eq!(taken, 3 * settings.region.area() / 2);
+        for y in 0..height {
+            for x in 0..width {
+                let expected = if mask[y * width + x] { 3 } else { 0 };
+                assert_eq!(film.sample_count(x, y), expected, "samples of ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn time_limit_stops_after_a_pass_and_resumes_from_it() {
+        let (width, height) = (4, 4);
+        let wavelengths = WavelengthSampler::new();
+        let sampler = SamplerKind::Random.build(MAX_RAYS, SEED);
+        let filter = FilterKind::Box.build();
+        let unlimited = RenderSettings {
+            width,
+            height,
+            wavelengths: &wavelengths,
+            sampler: sampler.as_ref(),
+            filter: filter.as_ref(),
+            sampler_kind: SamplerKind::Random,
+            filter_kind: FilterKind::Box,
+            region: Bounds::new(0, 0, width, height),
+            transparent: false,
+            time_limit: None,
+        };
+        // already over by the end of the first pass
+        let limited = RenderSettings { time_limit: Some(Duration::from_secs(0)), ..unlimited };
+
+        let passes = |settings: &RenderSettings, path: &Path| {
+            let mut passes = Vec::new();
+            render(settings, Film::new(width, height), path, |_: &mut Film, _: &[bool], samples: Range<usize>| {
+                passes.push(samples.clone());
+                samples.len() * width * height
+            }, |_| {});
+            passes
+        };
+        let every_pass: Vec<Range<usize>> = (0..MAX_RAYS).step_by(SAMPLES_PER_PASS)
+            .map(|first| first..(first + SAMPLES_PER_PASS).min(MAX_RAYS))
+            .collect();
+
+        let path = checkpoint_path("time_limit");
+        assert_eq!(passes(&unlimited, &path), every_pass);
+        assert!(!path.exists());
+
+        assert_eq!(passes(&limited, &path), every_pass[..1].to_vec());
+        assert!(path.exists(), "the checkpoint of a render stopped short stays");
+        // the next run goes on from the first pass it didn't take
+        assert_eq!(passes(&unlimited, &path), every_pass[1..].to_vec());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn checkpoint_of_other_render_constants_is_ignored() {
+        let (width, height) = (4, 4);
+        let wavelengths = WavelengthSampler::new();
+        let sampler = SamplerKind::Random.build(MAX_RAYS, SEED);
+        let filter = FilterKind::Box.build();
+        let settings = RenderSettings {
+            width,
+            height,
+            wavelengths: &wavelengths,
+            sampler: sampler.as_ref(),
+            filter: filter.as_ref(),
+            sampler_kind: SamplerKind::Random,
+            filter_kind: FilterKind::Box,
+            region: Bounds::new(0, 0, width, height),
+            transparent: false,
+            time_limit: None,
+        };
+        let path = checkpoint_path("constants");
+        let film = Film::new(width, height);
+        save_checkpoint(&path, &settings, film.clone(), SAMPLES_PER_PASS, Duration::from_secs(1));
+        assert!(resume(&path, &film, &settings).is_some());
+
+        let changes: [fn(&mut Checkpoint); 4] = [
+            |c| c.max_samples += 1,
+            |c| c.max_depth += 1,
+            |c| c.adaptive_threshold = Some(ADAPTIVE_THRESHOLD.map_or(0.05, |t| t * 2.0)),
+            |c| c.spectral = !c.spectral,
+        ];
+        for change in &changes {
+            save_checkpoint(&path, &settings, film.clone(), SAMPLES_PER_PASS, Duration::from_secs(1));
+            let mut checkpoint = Checkpoint::load(&path).unwrap();
+            change(&mut checkpoint);
+            checkpoint.save(&path).unwrap();
+            assert!(resume(&path, &film, &settings).is_none());
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn checkpoint_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rt_driver_{}_{}.checkpoint", name, process::id()))
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_one() {
+        let (width, height) = (16, 8);
+        let world = BVH::new(vec![
+            Box::new(Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, Lambertian::new(ConstantTexture::new(Color::new(200, 100, 50))))),
+        ]);
+        let camera = Camera::new(
+            Point::new(0.0, 0.0, 1.0),
+            Point::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            40.0,
+            width as f32 / height as f32,
+            0.0,
+            2.0,
+        );
+        let wavelengths = WavelengthSampler::new();
+        let sampler = SamplerKind::Sobol.build(MAX_RAYS, SEED);
+        let filter = FilterKind::Gaussian.build();
+        let settings = RenderSettings {
+            width,
+            height,
+            wavelengths: &wavelengths,
+            sampler: sampler.as_ref(),
+            filter: filter.as_ref(),
+            sampler_kind: SamplerKind::Sobol,
+            filter_kind: FilterKind::Gaussian,
+            region: Bounds::new(0, 0, width, height),
+            transparent: false,
+            time_limit: None,
+        };
+        let take_samples = |film: &mut Film, active: &[bool], samples: Range<usize>| {
+            let pixel_func = path_tracer(&camera, &world, &settings);
+            build_in_parallel(film, settings.region, samples, active, settings.sampler, settings.filter, pixel_func)
+        };
+
+        let uninterrupted_path = checkpoint_path("uninterrupted");
+        let uninterrupted = render(&settings, Film::new(width, height), &uninterrupted_path, take_samples, |_| {});
+
+        // stopped after the first pass, then started again
+        let resumed_path = checkpoint_path("resumed");
+        let mut film = Film::new(width, height);
+        let active = active_pixels(&film, settings.region);
+        take_samples(&mut film, &active, 0..SAMPLES_PER_PASS);
+        save_checkpoint(&resumed_path, &settings, film, SAMPLES_PER_PASS, Duration::from_secs(1));
+        let resumed = render(&settings, Film::new(width, height), &resumed_path, take_samples, |_| {});
+        assert!(!resumed_path.exists(), "the checkpoint of a finished render is removed");
+
+        // compared through their encoding, bit for bit
+        let encoded = |film: Film| {
+            save_checkpoint(&uninterrupted_path, &settings, film, MAX_RAYS, Duration::from_secs(0));
+            let data = fs::read(&uninterrupted_path).unwrap();
+            fs::remove_file(&uninterrupted_path).unwrap();
+            data
+        };
+        assert!(encoded(resumed) == encoded(uninterrupted));
+    }
+
+    // a sphere on a ground, seen by the camera of `tiled_camera`
+    fn tiled_world() -> BVH {
+        BVH::new(vec![
+            Box::new(Sphere::new(Point::new(0.0, 0.0, -1.0), 0.5, Lambertian::new(ConstantTexture::new(Color::new(200, 100, 50))))),
+            Box::new(Sphere::new(Point::new(0.0, -100.5, -1.0), 100.0, Metal::new(Vector::new(0.7, 0.7, 0.7), 0.3))),
+        ])
+    }
+
+    fn tiled_camera(width: usize, height: usize) -> Camera {
+        let aspect = width as f32 / height as f32;
+        Camera::new(Point::new(0.0, 0.3, 1.0), Point::new(0.0, 0.0, -1.0), Vector::new(0.0, 1.0, 0.0), 60.0, aspect, 0.0, 2.0)
+    }
+
+    // renders a region spanning several tiles through a coordinator on
+    // localhost, with workers or not, and locally, the films having to be the
+    // same bit for bit
+    fn distributed_matches_local(with_workers: bool, timeout: Duration) {
+        let (width, height) = (48, 40);
+        let (world, camera) = (tiled_world(), tiled_camera(width, height));
+        let wavelengths = WavelengthSampler::new();
+        let sampler = SamplerKind::Sobol.build(MAX_RAYS, SEED);
+        let filter = FilterKind::Gaussian.build();
+        let settings = RenderSettings {
+            width,
+            height,
+            wavelengths: &wavelengths,
+            sampler: sampler.as_ref(),
+            filter: filter.as_ref(),
+            sampler_kind: SamplerKind::Sobol,
+            filter_kind: FilterKind::Gaussian,
+            region: Bounds::new(5, 3, 45, 38),
+            transparent: false,
+            time_limit: None,
+        };
+        let handshake = Handshake {
+            seed: SEED,
+            width,
+            height,
+            sampler: SamplerKind::Sobol,
+            filter: FilterKind::Gaussian,
+            max_samples: MAX_RAYS,
+            spectral: SPECTRAL,
+        };
+        let coordinator = Coordinator::bind("127.0.0.1:0", handshake).unwrap().with_timeout(timeout);
+        let address = coordinator.local_addr().to_string();
+        let pixel_func = path_tracer(&camera, &world, &settings);
+
+        let (mut local, mut distributed) = (Film::new(width, height), Film::new(width, height));
+        std::thread::scope(|scope| {
+            if with_workers {
+                let (address, settings) = (&address, &settings);
+                scope.spawn(move || {
+                    let views = |_| vec![Box::new(tiled_camera(width, height)) as Box<dyn CameraModel>];
+                    work(address, handshake, settings, |_| tiled_world(), views)
+                });
+            }
+            for first in (0..MAX_RAYS).step_by(SAMPLES_PER_PASS) {
+                let samples = first..(first + SAMPLES_PER_PASS).min(MAX_RAYS);
+                let active = active_pixels(&local, settings.region);
+                let taken = build_in_parallel(&mut local, settings.region, samples.clone(), &active, settings.sampler, settings.filter, pixel_func);
+                let distributed_taken = distribute(&coordinator, &mut distributed, &settings, 0, 0, samples, &active, pixel_func);
+                assert_eq!(distributed_taken, taken);
+            }
+            // lets the workers go
+            drop(coordinator);
+        });
+
+        for y in 0..height {
+            for x in 0..width {
+                assert_eq!(distributed.sample_count(x, y), local.sample_count(x, y), "samples of ({}, {})", x, y);
+                assert_eq!(distributed.get_pixel(x, y), local.get_pixel(x, y), "pixel ({}, {})", x, y);
+                assert_eq!(distributed.get_alpha(x, y), local.get_alpha(x, y), "alpha of ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn coordinator_without_workers_renders_locally() {
+        distributed_matches_local(false, Duration::from_millis(10));
+    }
+
+    #[test]
+    fn workers_render_what_a_single_machine_does() {
+        // long enough for the workers to take every job
+        distributed_matches_local(true, Duration::from_secs(60));
+    }
+}
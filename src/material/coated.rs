@@ -0,0 +1,137 @@
+use rand::{Rng, RngCore};
+
+use super::*;
+
+// smooth dielectric clearcoat layered over any material: light is either
+// mirrored by the coat, or goes through it twice to reach the base, tinted by
+// the coat absorption on the way
+pub struct Coated<M: Material> {
+    pub base: M,
+    pub ref_index: f32,
+    pub tint: Vector,
+}
+
+impl<M: Material> Coated<M> {
+    pub fn new(base: M, ref_index: f32) -> Self {
+        Coated { base, ref_index, tint: Vector::new(1.0, 1.0, 1.0) }
+    }
+
+    pub fn with_tint(base: M, ref_index: f32, tint: Vector) -> Self {
+        Coated { base, ref_index, tint }
+    }
+
+    fn transmittance(&self, normal: Vector, wo: Vector, wi: Vector) -> Vector {
+        let out = 1.0 - fresnel_dielectric(wi.dot(normal), self.ref_index);
+        let inside = 1.0 - fresnel_dielectric(wo.dot(normal), self.ref_index);
+        self.tint * self.tint * (out * inside)
+    }
+}
+
+impl<M: Material> Material for Coated<M> {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos> {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        let wo = -ray.direction.normalized();
+        let fresnel = fresnel_dielectric(wo.dot(normal), self.ref_index);
+
+        if rng.gen::<f32>() < fresnel {
+            let reflected = utils::reflect(ray.direction.normalized(), normal);
+            if reflected.dot(geometric_normal) <= 0.0 {
+                return None;
+            }
+
+            return Some(MaterialInfos {
+                scattered: Ray::new(infos.point, reflected),
+                attenuation: Vector::new(1.0, 1.0, 1.0),
+                pdf: fresnel,
+                flags: BsdfFlags::DELTA,
+            });
+        }
+
+        // the (1 - fresnel) of entering the coat cancels with the lobe choice
+        let sample = self.base.sample(ray, infos, rng)?;
+        let wi = sample.scattered.direction.normalized();
+        let out = 1.0 - fresnel_dielectric(wi.dot(normal), self.ref_index);
+        Some(MaterialInfos {
+            attenuation: sample.attenuation * self.tint * self.tint * out,
+            pdf: sample.pdf * (1.0 - fresnel),
+            ..sample
+        })
+    }
+
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector {
+        let (normal, _) = utils::face_forward(ray, infos);
+        let wo = -ray.direction.normalized();
+        self.base.eval(ray, infos, direction) * self.transmittance(normal, wo, direction.normalized())
+    }
+
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32 {
+        let (normal, _) = utils::face_forward(ray, infos);
+        let wo = -ray.direction.normalized();
+        let fresnel = fresnel_dielectric(wo.dot(normal), self.ref_index);
+        self.base.pdf(ray, infos, direction) * (1.0 - fresnel)
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        self.base.flags() | BsdfFlags::DELTA
+    }
+
+    fn albedo(&self, infos: &HitInfos) -> Vector {
+        self.base.albedo(infos) * self.tint
+    }
+
+    fn absorption(&self) -> Option<Vector> {
+        self.base.absorption()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::material::tests::{albedos, flat_hit, incoming};
+    use crate::texture::ConstantTexture;
+
+    fn base() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::scalar(0.8))
+    }
+
+    #[test]
+    fn coat_samples_what_it_evaluates() {
+        let coated = albedos(Coated::with_tint(base(), 1.5, Vector::new(0.9, 0.8, 0.7)), 0.01);
+        for (coated, base) in coated.iter().zip(&albedos(base(), 0.01)) {
+            // the coat keeps light away from the base, and tints the rest
+            assert!(coated.x < base.x && coated.z < coated.x, "{:?} against {:?}", coated, base);
+        }
+    }
+
+    #[test]
+    fn coat_over_a_glossy_base_samples_what_it_evaluates() {
+        for albedo in albedos(Coated::new(RoughConductor::copper(GGX::isotropic(0.5)), 1.5), 0.01) {
+            assert!(albedo.x <= 1.0, "coated albedo {:?}", albedo);
+        }
+    }
+
+    #[test]
+    fn coat_of_air_is_its_base() {
+        let base: Arc<dyn Material> = Arc::new(base());
+        let coated = Coated::new(Lambertian::new(ConstantTexture::scalar(0.8)), 1.0);
+        let infos = flat_hit(base.clone());
+        for &angle in &[0.0, 45.0, 80.0] {
+            let ray = incoming(angle);
+            for direction in &[Vector::new(0.0, 0.0, 1.0), Vector::new(0.5, 0.2, 0.8), Vector::new(-0.7, 0.1, 0.3)] {
+                assert!((coated.eval(ray, &infos, *direction) - base.eval(ray, &infos, *direction)).norm() < 1e-6);
+                assert!((coated.pdf(ray, &infos, *direction) - base.pdf(ray, &infos, *direction)).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn absorption_of_the_base_is_kept() {
+        let glass = RoughDielectric::new(1.5, GGX::isotropic(0.3)).with_absorption(Vector::new(0.2, 0.8, 0.3), 1.0);
+        let sigma = glass.absorption();
+        assert!(sigma.is_some());
+        assert_eq!(Coated::new(glass, 1.5).absorption(), sigma);
+        assert_eq!(Coated::new(base(), 1.5).absorption(), None);
+    }
+}
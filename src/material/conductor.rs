@@ -0,0 +1,125 @@
+use rand::RngCore;
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub struct RoughConductor {
+    pub eta: Vector,
+    pub k: Vector,
+    pub distribution: GGX,
+}
+
+impl RoughConductor {
+    pub fn new(eta: Vector, k: Vector, distribution: GGX) -> RoughConductor {
+        RoughConductor { eta, k, distribution }
+    }
+
+    pub fn gold(distribution: GGX) -> RoughConductor {
+        RoughConductor::new(Vector::new(0.143, 0.374, 1.442), Vector::new(3.983, 2.385, 1.603), distribution)
+    }
+
+    pub fn copper(distribution: GGX) -> RoughConductor {
+        RoughConductor::new(Vector::new(0.200, 0.924, 1.102), Vector::new(3.912, 2.452, 2.142), distribution)
+    }
+
+    pub fn aluminium(distribution: GGX) -> RoughConductor {
+        RoughConductor::new(Vector::new(1.657, 0.880, 0.521), Vector::new(9.224, 6.270, 4.837), distribution)
+    }
+
+    fn fresnel(&self, cos_i: f32) -> Vector {
+        Vector::new(
+            fresnel_conductor(cos_i, self.eta.x, self.k.x),
+            fresnel_conductor(cos_i, self.eta.y, self.k.y),
+            fresnel_conductor(cos_i, self.eta.z, self.k.z),
+        )
+    }
+}
+
+impl Material for RoughConductor {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos> {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        let frame = Onb::from_w(normal);
+        let wo = frame.to_local(-ray.direction.normalized());
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let m = self.distribution.sample_visible(wo, rng);
+        let wi = reflect_local(wo, m);
+        if wi.z <= 0.0 {
+            return None;
+        }
+
+        let direction = frame.to_world(wi);
+        if direction.dot(geometric_normal) <= 0.0 {
+            return None;
+        }
+
+        // f * cos / pdf with visible normal sampling only leaves the masking term
+        let weight = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+        Some(MaterialInfos {
+            scattered: Ray::new(infos.point, direction),
+            attenuation: self.fresnel(wo.dot(m)) * weight,
+            pdf: self.distribution.pdf_visible(wo, m) / (4.0 * wo.dot(m)),
+            flags: BsdfFlags::GLOSSY,
+        })
+    }
+
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector {
+        let (normal, _) = utils::face_forward(ray, infos);
+        let frame = Onb::from_w(normal);
+        let wo = frame.to_local(-ray.direction.normalized());
+        let wi = frame.to_local(direction.normalized());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Vector::zero();
+        }
+
+        let m = (wo + wi).normalized();
+        let value = self.distribution.d(m) * self.distribution.g(wo, wi) / (4.0 * wo.z);
+        self.fresnel(wo.dot(m)) * value
+    }
+
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32 {
+        let (normal, _) = utils::face_forward(ray, infos);
+        let frame = Onb::from_w(normal);
+        let wo = frame.to_local(-ray.direction.normalized());
+        let wi = frame.to_local(direction.normalized());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+
+        let m = (wo + wi).normalized();
+        self.distribution.pdf_visible(wo, m) / (4.0 * wo.dot(m))
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::GLOSSY
+    }
+
+    fn albedo(&self, _infos: &HitInfos) -> Vector {
+        self.fresnel(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::albedos;
+
+    #[test]
+    fn anisotropic_gold_samples_what_it_evaluates() {
+        for albedo in albedos(RoughConductor::gold(GGX::anisotropic(0.5, 0.8)), 0.01) {
+            assert!(albedo.x <= 1.0 && albedo.y <= 1.0 && albedo.z <= 1.0, "gold albedo {:?}", albedo);
+            // gold reflects red more than blue
+            assert!(albedo.x > albedo.z);
+        }
+    }
+
+    #[test]
+    fn smoother_conductor_loses_less_energy() {
+        let smooth = albedos(RoughConductor::copper(GGX::isotropic(0.4)), 0.01);
+        let rough = albedos(RoughConductor::copper(GGX::isotropic(0.9)), 0.01);
+        // at grazing angles, where masking is strongest
+        assert!(smooth[3].x > rough[3].x, "{:?} <= {:?}", smooth[3], rough[3]);
+    }
+}
@@ -0,0 +1,128 @@
+use rand::{Rng, RngCore};
+
+use super::*;
+use crate::spectrum::Ior;
+
+#[derive(Debug, Clone)]
+pub struct Dielectric {
+    pub ref_index: f32,
+    // wavelength dependent index, only used when rendering spectrally
+    pub dispersion: Option<Ior>,
+    pub absorption: Option<Vector>,
+}
+
+impl Dielectric {
+    pub fn new(ref_index: f32) -> Dielectric {
+        Dielectric { ref_index, dispersion: None, absorption: None }
+    }
+
+    pub fn dispersive(ior: Ior) -> Dielectric {
+        Dielectric { ref_index: ior.nominal(), dispersion: Some(ior), absorption: None }
+    }
+
+    // colored glass, `color` is what remains of white light after `distance` inside
+    pub fn with_absorption(self, color: Vector, distance: f32) -> Dielectric {
+        Dielectric { absorption: Some(utils::absorption_coefficient(color, distance)), ..self }
+    }
+}
+
+impl Material for Dielectric {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos> {
+        let ref_index = utils::ref_index_at(self.ref_index, self.dispersion, ray.wavelength);
+        let reflected = utils::reflect(ray.direction, infos.normal);
+        let attenuation = Vector::new(1.0, 1.0, 1.0);
+        // the side of the surface is decided by the geometric normal, the
+        // shading normal can disagree with it on interpolated meshes
+        let (outward_normal, ni_over_nt, cosine) = if ray.direction.dot(infos.geometric_normal) > 0.0 {
+            let cosine = ref_index * ray.direction.dot(infos.normal).abs() / ray.direction.norm();
+            (-infos.normal, ref_index, cosine)
+        } else {
+            let cosine = ray.direction.dot(infos.normal).abs() / ray.direction.norm();
+            (infos.normal, 1.0 / ref_index, cosine)
+        };
+
+        let (reflect_prob, refracted) = if let Some(refracted) = utils::refract(ray.direction, outward_normal, ni_over_nt) {
+            (utils::schlick(cosine, ref_index), Some(Ray::new(infos.point, refracted)))
+        } else {
+            (1.0, None)
+        };
+
+        let (scattered, pdf, flags) = match refracted {
+            Some(refracted) if rng.gen::<f32>() >= reflect_prob => {
+                (refracted, 1.0 - reflect_prob, BsdfFlags::DELTA | BsdfFlags::TRANSMISSION)
+            },
+            _ => (Ray::new(infos.point, reflected), reflect_prob, BsdfFlags::DELTA),
+        };
+
+        Some(MaterialInfos {
+            scattered,
+            attenuation,
+            pdf,
+            flags,
+        })
+    }
+
+    fn eval(&self, _: Ray, _: &HitInfos, _: Vector) -> Vector {
+        Vector::zero()
+    }
+
+    fn pdf(&self, _: Ray, _: &HitInfos, _: Vector) -> f32 {
+        0.0
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::DELTA | BsdfFlags::TRANSMISSION
+    }
+
+    fn absorption(&self) -> Option<Vector> {
+        self.absorption
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::SeedableRng;
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+    use crate::material::tests::{flat_hit, incoming};
+
+    // sine of the refracted direction with the normal, for a ray at 45 degrees
+    fn refracted_sine(material: Dielectric, wavelength: Option<f32>) -> f32 {
+        let material: Arc<dyn Material> = Arc::new(material);
+        let infos = flat_hit(material.clone());
+        let ray = incoming(45.0).with_wavelength(wavelength);
+        let mut rng = XorShiftRng::from_seed([5; 16]);
+        loop {
+            let sample = material.sample(ray, &infos, &mut rng).unwrap();
+            if sample.flags.contains(BsdfFlags::TRANSMISSION) {
+                let direction = sample.scattered.direction.normalized();
+                assert!(direction.z < 0.0);
+                return direction.x;
+            }
+        }
+    }
+
+    #[test]
+    fn refraction_follows_the_index_at_the_wavelength() {
+        let ior = Ior::diamond();
+        let sin_i = 45.0f32.to_radians().sin();
+        for &wavelength in &[400.0, 550.0, 700.0] {
+            let sine = refracted_sine(Dielectric::dispersive(ior), Some(wavelength));
+            assert!((sine - sin_i / ior.at(wavelength)).abs() < 1e-5, "snell at {}nm", wavelength);
+        }
+        // blue bends more than red
+        assert!(refracted_sine(Dielectric::dispersive(ior), Some(400.0)) < refracted_sine(Dielectric::dispersive(ior), Some(700.0)));
+    }
+
+    #[test]
+    fn without_a_wavelength_the_nominal_index_is_used() {
+        let ior = Ior::diamond();
+        let sine = refracted_sine(Dielectric::dispersive(ior), None);
+        assert!((sine - 45.0f32.to_radians().sin() / ior.nominal()).abs() < 1e-5);
+        // a plain dielectric ignores the wavelength
+        assert_eq!(refracted_sine(Dielectric::new(1.5), Some(400.0)), refracted_sine(Dielectric::new(1.5), None));
+    }
+}
@@ -0,0 +1,62 @@
+use std::f32::consts::PI;
+
+use rand::RngCore;
+
+use super::*;
+
+pub struct Lambertian<T: Texture> {
+    pub albedo: T
+}
+
+impl<T: Texture> Lambertian<T> {
+    pub fn new(albedo: T) -> Lambertian<T> {
+        Lambertian { albedo }
+    }
+}
+
+impl<T: Texture> Material for Lambertian<T> {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos> {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        let frame = Onb::from_w(normal);
+        let local = Vector::rand_cosine_direction(rng);
+        let direction = frame.to_world(local);
+        if direction.dot(geometric_normal) <= 0.0 {
+            return None;
+        }
+
+        // cosine weighted sampling cancels both the cosine and the 1 / pi
+        Some(MaterialInfos {
+            scattered: Ray::new(infos.point, direction),
+            attenuation: self.albedo.value(0.0, 0.0, infos.point).as_vector(),
+            pdf: local.z / PI,
+            flags: BsdfFlags::DIFFUSE,
+        })
+    }
+
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        let direction = direction.normalized();
+        let cosine = direction.dot(normal);
+        if cosine <= 0.0 || direction.dot(geometric_normal) <= 0.0 {
+            return Vector::zero();
+        }
+
+        self.albedo.value(0.0, 0.0, infos.point).as_vector() * (cosine / PI)
+    }
+
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32 {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        if direction.dot(geometric_normal) <= 0.0 {
+            return 0.0;
+        }
+        (direction.normalized().dot(normal) / PI).max(0.0)
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::DIFFUSE
+    }
+
+    fn albedo(&self, infos: &HitInfos) -> Vector {
+        self.albedo.value(0.0, 0.0, infos.point).as_vector()
+    }
+}
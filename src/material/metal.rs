@@ -0,0 +1,81 @@
+use std::f32::consts::PI;
+
+use rand::RngCore;
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub struct Metal {
+    pub albedo: Vector,
+    pub fuzz: f32,
+}
+
+impl Metal {
+    pub fn new(albedo: Vector, fuzz: f32) -> Metal {
+        Metal { albedo, fuzz }
+    }
+
+    // density of normalize(reflected + fuzz * p) for p uniform in the unit ball:
+    // the ball volume crossed by the direction, seen from the origin
+    fn fuzz_pdf(&self, reflected: Vector, direction: Vector) -> f32 {
+        let c = direction.dot(reflected);
+        let disc = c * c - 1.0 + self.fuzz * self.fuzz;
+        if disc <= 0.0 {
+            return 0.0;
+        }
+
+        let t0 = (c - disc.sqrt()).max(0.0);
+        let t1 = c + disc.sqrt();
+        if t1 <= 0.0 {
+            return 0.0;
+        }
+
+        (t1 * t1 * t1 - t0 * t0 * t0) / (4.0 * PI * self.fuzz * self.fuzz * self.fuzz)
+    }
+}
+
+impl Material for Metal {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos> {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        let reflected = utils::reflect(ray.direction.normalized(), normal);
+        let scattered = Ray::new(infos.point, reflected + Vector::rand_in_unit_sphere(rng) * self.fuzz);
+        if scattered.direction.dot(normal) <= 0.0 || scattered.direction.dot(geometric_normal) <= 0.0 {
+            return None;
+        }
+
+        let (pdf, flags) = if self.fuzz > 0.0 {
+            (self.fuzz_pdf(reflected, scattered.direction.normalized()), BsdfFlags::GLOSSY)
+        } else {
+            (1.0, BsdfFlags::DELTA)
+        };
+
+        Some(MaterialInfos {
+            scattered,
+            attenuation: self.albedo,
+            pdf,
+            flags,
+        })
+    }
+
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector {
+        self.albedo * self.pdf(ray, infos, direction)
+    }
+
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32 {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        if self.fuzz <= 0.0 || direction.dot(normal) <= 0.0 || direction.dot(geometric_normal) <= 0.0 {
+            return 0.0;
+        }
+
+        let reflected = utils::reflect(ray.direction.normalized(), normal);
+        self.fuzz_pdf(reflected, direction.normalized())
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        if self.fuzz > 0.0 { BsdfFlags::GLOSSY } else { BsdfFlags::DELTA }
+    }
+
+    fn albedo(&self, _infos: &HitInfos) -> Vector {
+        self.albedo
+    }
+}
@@ -0,0 +1,268 @@
+use std::f32::consts::PI;
+
+use rand::Rng;
+
+use crate::math::*;
+
+const MIN_ALPHA: f32 = 0.001;
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals, expressed in a
+// local frame where the macro surface normal is +z
+#[derive(Debug, Clone, Copy)]
+pub struct GGX {
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl GGX {
+    pub fn new(alpha_x: f32, alpha_y: f32) -> GGX {
+        GGX {
+            alpha_x: alpha_x.max(MIN_ALPHA),
+            alpha_y: alpha_y.max(MIN_ALPHA),
+        }
+    }
+
+    // roughness is perceptual, alpha = roughness^2
+    pub fn isotropic(roughness: f32) -> GGX {
+        let alpha = roughness * roughness;
+        GGX::new(alpha, alpha)
+    }
+
+    // anisotropy in [0, 1] stretches the highlight along the tangent
+    pub fn anisotropic(roughness: f32, anisotropy: f32) -> GGX {
+        let aspect = (1.0 - 0.9 * anisotropy).sqrt();
+        let alpha = roughness * roughness;
+        GGX::new(alpha / aspect, alpha * aspect)
+    }
+
+    pub fn d(&self, m: Vector) -> f32 {
+        if m.z <= 0.0 {
+            return 0.0;
+        }
+
+        let x = m.x / self.alpha_x;
+        let y = m.y / self.alpha_y;
+        let e = x * x + y * y + m.z * m.z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    fn lambda(&self, w: Vector) -> f32 {
+        if w.z == 0.0 {
+            return 0.0;
+        }
+
+        let x = self.alpha_x * w.x;
+        let y = self.alpha_y * w.y;
+        let tan2 = (x * x + y * y) / (w.z * w.z);
+        ((1.0 + tan2).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: Vector) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    pub fn g(&self, wo: Vector, wi: Vector) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // density of `sample_visible` over microfacet normals, wo must be in the upper hemisphere
+    pub fn pdf_visible(&self, wo: Vector, m: Vector) -> f32 {
+        self.g1(wo) * wo.dot(m).max(0.0) * self.d(m) / wo.z
+    }
+
+    // samples the distribution of normals visible from wo (Heitz 2018)
+    pub fn sample_visible<R: Rng + ?Sized>(&self, wo: Vector, rng: &mut R) -> Vector {
+        let vh = Vector::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).normalized();
+
+        let len_sq = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len_sq > 0.0 {
+            Vector::new(-vh.y, vh.x, 0.0) / len_sq.sqrt()
+        } else {
+            Vector::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        let r = rng.gen::<f32>().sqrt();
+        let phi = 2.0 * PI * rng.gen::<f32>();
+        let p1 = r * phi.cos();
+        let p2 = r * phi.sin();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * p2;
+
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        Vector::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(0.0)).normalized()
+    }
+}
+
+pub(crate) fn reflect_local(wo: Vector, m: Vector) -> Vector {
+    m * (2.0 * wo.dot(m)) - wo
+}
+
+// refracts wo through the microfacet m, eta being the relative index eta_t / eta_i
+pub(crate) fn refract_local(wo: Vector, m: Vector, eta: f32) -> Option<Vector> {
+    let cos_i = wo.dot(m);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + m * (cos_i / eta - cos_t))
+}
+
+// unpolarized fresnel reflectance of a dielectric interface, eta = eta_t / eta_i
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_i.abs().min(1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (rs * rs + rp * rp) / 2.0
+}
+
+pub fn fresnel_schlick(f0: Vector, cos_i: f32) -> Vector {
+    let w = (1.0 - cos_i.abs().min(1.0)).powi(5);
+    f0 + (Vector::new(1.0, 1.0, 1.0) - f0) * w
+}
+
+// unpolarized fresnel reflectance of a conductor with complex index eta + i k
+pub fn fresnel_conductor(cos_i: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    (rp + rs) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+
+    // cells of the quadrature along theta and phi
+    const GRID: usize = 400;
+
+    fn distributions() -> [GGX; 3] {
+        [GGX::isotropic(0.5), GGX::isotropic(0.8), GGX::anisotropic(0.5, 0.8)]
+    }
+
+    // integral of `f` over the upper hemisphere by the midpoint rule
+    fn hemisphere_integral<F: Fn(Vector) -> f32>(f: F) -> f32 {
+        let (d_theta, d_phi) = (PI / 2.0 / GRID as f32, 2.0 * PI / GRID as f32);
+        let mut sum = 0.0;
+        for i in 0..GRID {
+            let (sin_theta, cos_theta) = ((i as f32 + 0.5) * d_theta).sin_cos();
+            for j in 0..GRID {
+                let (sin_phi, cos_phi) = ((j as f32 + 0.5) * d_phi).sin_cos();
+                sum += f(Vector::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta)) * sin_theta;
+            }
+        }
+        sum * d_theta * d_phi
+    }
+
+    fn assert_close(a: f32, b: f32, tolerance: f32, what: &str) {
+        assert!((a - b).abs() <= tolerance, "{}: {} != {}", what, a, b);
+    }
+
+    fn outgoing(angle: f32) -> Vector {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Vector::new(sin, 0.0, cos)
+    }
+
+    #[test]
+    fn projected_normals_cover_the_surface() {
+        for ggx in &distributions() {
+            assert_close(hemisphere_integral(|m| ggx.d(m) * m.z), 1.0, 1e-3, "projected area");
+        }
+    }
+
+    #[test]
+    fn visible_normals_cover_the_projected_surface() {
+        for ggx in &distributions() {
+            for &angle in &[0.0, 45.0, 80.0] {
+                let wo = outgoing(angle);
+                let pdf = hemisphere_integral(|m| ggx.pdf_visible(wo, m));
+                assert_close(pdf, 1.0, 2e-3, &format!("visible normals at {} degrees", angle));
+            }
+        }
+    }
+
+    #[test]
+    fn visible_normals_are_sampled_with_their_density() {
+        let mut rng = XorShiftRng::from_seed([3; 16]);
+        for ggx in &distributions() {
+            let wo = outgoing(60.0);
+            // moments of the sampled normals against the ones of the density
+            let moments = |m: Vector| [m.x, m.y * m.y, m.z];
+            let expected: Vec<f32> = (0..3).map(|i| hemisphere_integral(|m| moments(m)[i] * ggx.pdf_visible(wo, m))).collect();
+
+            const SAMPLES: usize = 100_000;
+            let mut sum = [0.0; 3];
+            for _ in 0..SAMPLES {
+                let m = ggx.sample_visible(wo, &mut rng);
+                assert!(m.z >= 0.0 && wo.dot(m) >= 0.0, "normal {:?} isn't visible", m);
+                for (sum, moment) in sum.iter_mut().zip(&moments(m)) {
+                    *sum += moment / SAMPLES as f32;
+                }
+            }
+            for (&mean, &expected) in sum.iter().zip(&expected) {
+                assert_close(mean, expected, 5e-3, "moment of the sampled normals");
+            }
+        }
+    }
+
+    #[test]
+    fn masking_is_symmetric_and_at_most_one() {
+        let ggx = GGX::anisotropic(0.6, 0.5);
+        let (a, b) = (outgoing(30.0), Vector::new(0.0, 0.8, 0.6));
+        assert_close(ggx.g(a, b), ggx.g(b, a), 1e-6, "symmetry");
+        assert!(ggx.g(a, b) <= ggx.g1(a).min(ggx.g1(b)));
+        assert_close(ggx.g1(Vector::new(0.0, 0.0, 1.0)), 1.0, 1e-6, "masking at normal incidence");
+    }
+
+    #[test]
+    fn dielectric_fresnel_limits() {
+        assert_close(fresnel_dielectric(1.0, 1.5), 0.04, 1e-6, "normal incidence");
+        assert_close(fresnel_dielectric(1e-4, 1.5), 1.0, 1e-3, "grazing incidence");
+        assert_close(fresnel_dielectric(0.5, 1.0), 0.0, 1e-6, "matched indices");
+        // past the critical angle of glass, asin(1 / 1.5) = 41.8 degrees
+        assert_eq!(fresnel_dielectric(45.0f32.to_radians().cos(), 1.0 / 1.5), 1.0);
+        assert!(fresnel_dielectric(40.0f32.to_radians().cos(), 1.0 / 1.5) < 1.0);
+    }
+
+    #[test]
+    fn schlick_goes_from_f0_to_white() {
+        let f0 = Vector::new(0.9, 0.6, 0.3);
+        assert_close((fresnel_schlick(f0, 1.0) - f0).norm(), 0.0, 1e-6, "normal incidence");
+        assert_close((fresnel_schlick(f0, 0.0) - Vector::new(1.0, 1.0, 1.0)).norm(), 0.0, 1e-6, "grazing incidence");
+    }
+
+    #[test]
+    fn conductor_without_extinction_is_a_dielectric() {
+        for &cos in &[1.0, 0.7, 0.3] {
+            assert_close(fresnel_conductor(cos, 1.5, 0.0), fresnel_dielectric(cos, 1.5), 1e-5, "fresnel");
+        }
+        // ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2) at normal incidence
+        let (n, k) = (0.2f32, 3.9f32);
+        let expected = ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+        assert_close(fresnel_conductor(1.0, n, k), expected, 1e-5, "normal incidence");
+    }
+}
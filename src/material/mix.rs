@@ -0,0 +1,138 @@
+use rand::{Rng, RngCore};
+
+use super::*;
+
+// picks `second` with a probability given by the weight texture, and `first` otherwise
+pub struct MixMaterial<A: Material, B: Material, W: Texture> {
+    pub first: A,
+    pub second: B,
+    pub weight: W,
+}
+
+impl<A: Material, B: Material, W: Texture> MixMaterial<A, B, W> {
+    pub fn new(first: A, second: B, weight: W) -> Self {
+        MixMaterial { first, second, weight }
+    }
+
+    fn weight(&self, infos: &HitInfos) -> f32 {
+        self.weight.scalar_value(0.0, 0.0, infos.point).clamp(0.0, 1.0)
+    }
+}
+
+impl<A: Material, B: Material, W: Texture> Material for MixMaterial<A, B, W> {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos> {
+        let w = self.weight(infos);
+        let (sample, prob) = if rng.gen::<f32>() < w {
+            (self.second.sample(ray, infos, rng)?, w)
+        } else {
+            (self.first.sample(ray, infos, rng)?, 1.0 - w)
+        };
+
+        // the lobe choice cancels out of a delta sample, otherwise both
+        // materials could have produced the direction
+        if sample.flags.is_delta() {
+            return Some(MaterialInfos { pdf: sample.pdf * prob, ..sample });
+        }
+
+        let direction = sample.scattered.direction;
+        let pdf = self.pdf(ray, infos, direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(MaterialInfos {
+            attenuation: self.eval(ray, infos, direction) / pdf,
+            pdf,
+            ..sample
+        })
+    }
+
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector {
+        let w = self.weight(infos);
+        self.first.eval(ray, infos, direction) * (1.0 - w) + self.second.eval(ray, infos, direction) * w
+    }
+
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32 {
+        let w = self.weight(infos);
+        self.first.pdf(ray, infos, direction) * (1.0 - w) + self.second.pdf(ray, infos, direction) * w
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        self.first.flags() | self.second.flags()
+    }
+
+    fn albedo(&self, infos: &HitInfos) -> Vector {
+        let w = self.weight(infos);
+        self.first.albedo(infos) * (1.0 - w) + self.second.albedo(infos) * w
+    }
+
+    // the weight depends on the hit point, two media are mixed half and half
+    fn absorption(&self) -> Option<Vector> {
+        match (self.first.absorption(), self.second.absorption()) {
+            (Some(a), Some(b)) => Some((a + b) / 2.0),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::material::tests::{albedos, flat_hit, incoming};
+    use crate::texture::ConstantTexture;
+
+    fn mix(weight: f32) -> MixMaterial<Lambertian<ConstantTexture>, RoughConductor, ConstantTexture> {
+        MixMaterial::new(
+            Lambertian::new(ConstantTexture::scalar(0.8)),
+            RoughConductor::gold(GGX::isotropic(0.5)),
+            ConstantTexture::scalar(weight),
+        )
+    }
+
+    #[test]
+    fn mix_samples_what_it_evaluates() {
+        let (first, second) = (albedos(mix(0.0), 0.01), albedos(mix(1.0), 0.01));
+        let mixed = albedos(mix(0.2), 0.01);
+        for ((first, second), mixed) in first.iter().zip(&second).zip(&mixed) {
+            let expected = *first * 0.8 + *second * 0.2;
+            assert!((*mixed - expected).norm() < 1e-3, "{:?} != {:?}", mixed, expected);
+        }
+    }
+
+    #[test]
+    fn extreme_weights_are_one_of_the_materials() {
+        let lambertian: Arc<dyn Material> = Arc::new(Lambertian::new(ConstantTexture::scalar(0.8)));
+        let gold: Arc<dyn Material> = Arc::new(RoughConductor::gold(GGX::isotropic(0.5)));
+        let infos = flat_hit(lambertian.clone());
+        let ray = incoming(30.0);
+        for direction in &[Vector::new(0.0, 0.0, 1.0), Vector::new(0.5, 0.2, 0.8), Vector::new(-0.7, 0.1, 0.3)] {
+            for (weight, material) in [(0.0, &lambertian), (1.0, &gold)] {
+                let mix = mix(weight);
+                assert_eq!(mix.eval(ray, &infos, *direction), material.eval(ray, &infos, *direction));
+                assert_eq!(mix.pdf(ray, &infos, *direction), material.pdf(ray, &infos, *direction));
+            }
+        }
+    }
+
+    #[test]
+    fn absorption_of_the_components_is_kept() {
+        let glass = || Dielectric::new(1.5).with_absorption(Vector::new(0.5, 0.5, 0.5), 1.0);
+        let sigma = glass().absorption().unwrap();
+        let half = ConstantTexture::scalar(0.4);
+        assert_eq!(MixMaterial::new(Lambertian::new(ConstantTexture::scalar(0.8)), glass(), half.clone()).absorption(), Some(sigma));
+        assert_eq!(MixMaterial::new(glass(), Dielectric::new(1.5), half.clone()).absorption(), Some(sigma));
+        assert_eq!(MixMaterial::new(glass(), Dielectric::new(1.5).with_absorption(Vector::new(1.0, 1.0, 1.0), 1.0), half.clone()).absorption(), Some(sigma / 2.0));
+        assert_eq!(mix(0.4).absorption(), None);
+    }
+
+    #[test]
+    fn mix_with_a_delta_material_samples_what_it_evaluates() {
+        // only the diffuse part is checked, glass having no density to compare
+        let material = MixMaterial::new(Lambertian::new(ConstantTexture::scalar(0.8)), Dielectric::new(1.5), ConstantTexture::scalar(0.4));
+        for albedo in albedos(material, 0.02) {
+            assert!((albedo.x - 0.48).abs() < 1e-3, "mix albedo {}", albedo.x);
+        }
+    }
+}
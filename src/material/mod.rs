@@ -0,0 +1,323 @@
+use std::ops::BitOr;
+
+use rand::RngCore;
+
+use crate::prelude::*;
+use crate::hitable::HitInfos;
+
+mod lambertian;
+mod oren_nayar;
+mod metal;
+mod dielectric;
+mod microfacet;
+mod conductor;
+mod rough_dielectric;
+mod principled;
+mod mix;
+mod coated;
+pub use self::lambertian::*;
+pub use self::oren_nayar::*;
+pub use self::metal::*;
+pub use self::dielectric::*;
+pub use self::microfacet::*;
+pub use self::conductor::*;
+pub use self::rough_dielectric::*;
+pub use self::principled::*;
+pub use self::mix::*;
+pub use self::coated::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BsdfFlags(u8);
+
+impl BsdfFlags {
+    pub const NONE: BsdfFlags = BsdfFlags(0);
+    pub const DIFFUSE: BsdfFlags = BsdfFlags(1);
+    pub const GLOSSY: BsdfFlags = BsdfFlags(1 << 1);
+    // perfectly specular, `eval` and `pdf` are zero everywhere for these lobes
+    pub const DELTA: BsdfFlags = BsdfFlags(1 << 2);
+    pub const TRANSMISSION: BsdfFlags = BsdfFlags(1 << 3);
+
+    pub fn contains(self, other: BsdfFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_delta(self) -> bool {
+        self.contains(BsdfFlags::DELTA)
+    }
+}
+
+impl BitOr for BsdfFlags {
+    type Output = BsdfFlags;
+
+    fn bitor(self, other: BsdfFlags) -> BsdfFlags {
+        BsdfFlags(self.0 | other.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialInfos {
+    pub scattered: Ray,
+    // bsdf times cosine over pdf, what the incoming radiance is multiplied by
+    pub attenuation: Vector,
+    // solid angle density, or the probability of picking the lobe for delta lobes
+    pub pdf: f32,
+    pub flags: BsdfFlags,
+}
+
+pub trait Material: Send + Sync {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos>;
+
+    // bsdf value times the cosine term, for light leaving the surface along `direction`
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector;
+
+    // solid angle density with which `sample` picks `direction`
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32;
+
+    // union of the lobes the material can sample
+    fn flags(&self) -> BsdfFlags;
+
+    // rough color of the surface, guiding the denoiser, white when it has none
+    fn albedo(&self, _infos: &HitInfos) -> Vector {
+        Vector::new(1.0, 1.0, 1.0)
+    }
+
+    // absorption coefficient of the medium enclosed by the surface, per unit distance
+    fn absorption(&self) -> Option<Vector> {
+        None
+    }
+}
+
+mod utils {
+    use crate::math::*;
+    use crate::ray::Ray;
+    use crate::hitable::HitInfos;
+    use crate::spectrum::Ior;
+
+    // returns the shading and geometric normals, flipped to face the incoming ray
+    pub fn face_forward(ray: Ray, infos: &HitInfos) -> (Vector, Vector) {
+        if ray.direction.dot(infos.geometric_normal) > 0.0 {
+            (-infos.normal, -infos.geometric_normal)
+        } else {
+            (infos.normal, infos.geometric_normal)
+        }
+    }
+
+    pub fn ref_index_at(ref_index: f32, dispersion: Option<Ior>, wavelength: Option<f32>) -> f32 {
+        match (dispersion, wavelength) {
+            (Some(ior), Some(wavelength)) => ior.at(wavelength),
+            _ => ref_index,
+        }
+    }
+
+    // coefficient that leaves `color` of the light after travelling `distance`
+    pub fn absorption_coefficient(color: Vector, distance: f32) -> Vector {
+        let sigma = |c: f32| -c.max(1e-4).ln() / distance;
+        Vector::new(sigma(color.x), sigma(color.y), sigma(color.z))
+    }
+
+    pub fn mean(v: Vector) -> f32 {
+        (v.x + v.y + v.z) / 3.0
+    }
+
+    pub fn reflect(v: Vector, n: Vector) -> Vector {
+        v - n * 2.0 * v.dot(n)
+    }
+
+    pub fn refract(v: Vector, n: Vector, ni_over_nt: f32) -> Option<Vector> {
+        let uv = v.normalized();
+        let dt = uv.dot(n);
+        let disc = 1.0 - ni_over_nt * ni_over_nt * (1.0 - dt * dt);
+
+        if disc > 0.0 {
+            Some((uv - n * dt) * ni_over_nt - n * disc.sqrt())
+        } else {
+            None
+        }
+    }
+
+    pub fn schlick(cosine: f32, ref_index: f32) -> f32 {
+        let r0 = (1.0 - ref_index) / (1.0 + ref_index);
+        let r0 = r0 * r0;
+        r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::sync::Arc;
+
+    use rand::SeedableRng;
+    use rand::prng::XorShiftRng;
+
+    use super::*;
+    use crate::texture::ConstantTexture;
+
+    const SAMPLES: usize = 20_000;
+    // cells of the quadrature along theta and phi
+    const GRID: usize = 400;
+    // of the incoming ray with the normal, in degrees
+    const ANGLES: [f32; 4] = [0.0, 30.0, 60.0, 80.0];
+
+    // flat surface facing +z at the origin
+    pub(super) fn flat_hit(material: Arc<dyn Material>) -> HitInfos {
+        facing_hit(material, Vector::new(0.0, 0.0, 1.0))
+    }
+
+    fn facing_hit(material: Arc<dyn Material>, normal: Vector) -> HitInfos {
+        HitInfos::min_max(1.0, 0.0, 2.0, Point::origin(), normal, material).unwrap()
+    }
+
+    pub(super) fn incoming(angle: f32) -> Ray {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Ray::new(Point::new(-sin, 0.0, cos), Vector::new(sin, 0.0, -cos))
+    }
+
+    // integral of `eval` over the sphere, directional albedo of the material
+    // leaving out its delta lobes, by the midpoint rule in spherical
+    // coordinates, independently of `sample`
+    fn eval_integral(material: &dyn Material, infos: &HitInfos, ray: Ray) -> Vector {
+        let (d_theta, d_phi) = (PI / GRID as f32, 2.0 * PI / GRID as f32);
+        let mut sum = Vector::zero();
+        for i in 0..GRID {
+            let (sin_theta, cos_theta) = ((i as f32 + 0.5) * d_theta).sin_cos();
+            for j in 0..GRID {
+                let (sin_phi, cos_phi) = ((j as f32 + 0.5) * d_phi).sin_cos();
+                let direction = Vector::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+                sum = sum + material.eval(ray, infos, direction) * sin_theta;
+            }
+        }
+        sum * (d_theta * d_phi)
+    }
+
+    fn assert_close(a: f32, b: f32, tolerance: f32, what: &str) {
+        assert!((a - b).abs() <= tolerance * b.abs().max(1.0), "{}: {} != {}", what, a, b);
+    }
+
+    // mean weight of the samples that aren't delta, after checking that each
+    // of them is what `eval` over `pdf` gives in its direction
+    fn sample_mean(material: &dyn Material, infos: &HitInfos, ray: Ray, rng: &mut XorShiftRng) -> Vector {
+        let mut sum = Vector::zero();
+        for _ in 0..SAMPLES {
+            let sample = match material.sample(ray, infos, rng) {
+                Some(sample) if !sample.flags.is_delta() => sample,
+                _ => continue,
+            };
+            let direction = sample.scattered.direction;
+            let pdf = material.pdf(ray, infos, direction);
+            assert_close(pdf, sample.pdf, 1e-3, "pdf of a sampled direction");
+            let expected = material.eval(ray, infos, direction) / pdf;
+            for &(a, b) in &[(sample.attenuation.x, expected.x), (sample.attenuation.y, expected.y), (sample.attenuation.z, expected.z)] {
+                assert_close(a, b, 1e-3, "weight against eval over pdf");
+            }
+            sum = sum + sample.attenuation;
+        }
+        sum / SAMPLES as f32
+    }
+
+    // directional albedo from `eval` alone at every angle of ANGLES, after
+    // checking that the samples agree with it
+    pub(super) fn albedos<M: Material + 'static>(material: M, tolerance: f32) -> Vec<Vector> {
+        albedos_facing(material, Vector::new(0.0, 0.0, 1.0), tolerance)
+    }
+
+    // same as `albedos`, for rays leaving the inside of a closed surface
+    pub(super) fn albedos_from_inside<M: Material + 'static>(material: M, tolerance: f32) -> Vec<Vector> {
+        albedos_facing(material, Vector::new(0.0, 0.0, -1.0), tolerance)
+    }
+
+    fn albedos_facing<M: Material + 'static>(material: M, normal: Vector, tolerance: f32) -> Vec<Vector> {
+        let material: Arc<dyn Material> = Arc::new(material);
+        let infos = facing_hit(material.clone(), normal);
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+
+        ANGLES.iter()
+            .map(|&angle| {
+                let ray = incoming(angle);
+                let integral = eval_integral(material.as_ref(), &infos, ray);
+                let mean = sample_mean(material.as_ref(), &infos, ray, &mut rng);
+                for &(a, b) in &[(mean.x, integral.x), (mean.y, integral.y), (mean.z, integral.z)] {
+                    assert_close(a, b, tolerance, &format!("mean weight against the integral at {} degrees", angle));
+                }
+                integral
+            })
+            .collect()
+    }
+
+    // shading normal tilted away from the geometric one, as on smooth meshes
+    fn tilted_hit(material: Arc<dyn Material>) -> HitInfos {
+        let mut infos = flat_hit(material);
+        infos.normal = Vector::new(0.5, 0.0, 1.0).normalized();
+        infos
+    }
+
+    // what `sample` can't pick has neither a value nor a density
+    fn below_geometric_normal_is_zero(material: Arc<dyn Material>) {
+        let infos = tilted_hit(material.clone());
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, -1.0));
+        // above the shading normal, below the geometric one
+        let direction = Vector::new(1.0, 0.0, -0.2);
+        assert!(direction.dot(infos.normal) > 0.0);
+        assert_eq!(material.eval(ray, &infos, direction).norm(), 0.0);
+        assert_eq!(material.pdf(ray, &infos, direction), 0.0);
+    }
+
+    #[test]
+    fn lambertian_below_geometric_normal_is_zero() {
+        below_geometric_normal_is_zero(Arc::new(Lambertian::new(ConstantTexture::scalar(1.0))));
+    }
+
+    #[test]
+    fn oren_nayar_below_geometric_normal_is_zero() {
+        below_geometric_normal_is_zero(Arc::new(OrenNayar::new(ConstantTexture::scalar(1.0), 0.3)));
+    }
+
+    #[test]
+    fn absorption_leaves_the_color_after_its_distance() {
+        let color = Vector::new(0.2, 0.8, 0.5);
+        let sigma = utils::absorption_coefficient(color, 2.0);
+        let left = |distance: f32| Vector::new((-sigma.x * distance).exp(), (-sigma.y * distance).exp(), (-sigma.z * distance).exp());
+        assert!((left(2.0) - color).norm() < 1e-5);
+        // twice as far is the square, half as far the square root
+        assert!((left(4.0) - Vector::new(0.04, 0.64, 0.25)).norm() < 1e-5);
+        assert!((left(1.0) - Vector::new(0.2f32.sqrt(), 0.8f32.sqrt(), 0.5f32.sqrt())).norm() < 1e-5);
+        // white absorbs nothing, black is kept finite
+        assert_eq!(utils::absorption_coefficient(Vector::new(1.0, 1.0, 1.0), 1.0).norm(), 0.0);
+        assert!(utils::absorption_coefficient(Vector::zero(), 1.0).x.is_finite());
+    }
+
+    #[test]
+    fn lambertian_reflects_its_albedo() {
+        for albedo in albedos(Lambertian::new(ConstantTexture::scalar(0.8)), 0.01) {
+            assert_close(albedo.x, 0.8, 1e-3, "lambertian albedo");
+        }
+    }
+
+    #[test]
+    fn oren_nayar_without_roughness_reflects_its_albedo() {
+        for albedo in albedos(OrenNayar::new(ConstantTexture::scalar(0.8), 0.0), 0.01) {
+            assert_close(albedo.x, 0.8, 1e-3, "oren-nayar albedo");
+        }
+    }
+
+    #[test]
+    fn rough_oren_nayar_loses_energy() {
+        for albedo in albedos(OrenNayar::new(ConstantTexture::scalar(0.8), 20.0), 0.01) {
+            assert!(albedo.x <= 0.8 && albedo.x > 0.6, "oren-nayar albedo {}", albedo.x);
+        }
+    }
+
+    #[test]
+    fn rough_conductor_loses_energy() {
+        for albedo in albedos(RoughConductor::aluminium(GGX::isotropic(0.5)), 0.01) {
+            assert!(albedo.x <= 1.0 && albedo.y <= 1.0 && albedo.z <= 1.0, "conductor albedo {:?}", albedo);
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_loses_energy() {
+        // reflection and transmission together, less the masked light
+        for albedo in albedos(RoughDielectric::new(1.5, GGX::isotropic(0.5)), 0.02) {
+            assert!(albedo.x <= 1.0 && albedo.x > 0.8, "dielectric albedo {}", albedo.x);
+        }
+    }
+}
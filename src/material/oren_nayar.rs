@@ -0,0 +1,96 @@
+use std::f32::consts::PI;
+
+use rand::RngCore;
+
+use super::*;
+
+// rough diffuse surface made of lambertian v-cavities, sigma being the standard
+// deviation of the facet angles in degrees (0 gives back a lambertian)
+pub struct OrenNayar<T: Texture> {
+    pub albedo: T,
+    a: f32,
+    b: f32,
+}
+
+impl<T: Texture> OrenNayar<T> {
+    pub fn new(albedo: T, sigma: f32) -> OrenNayar<T> {
+        let sigma = sigma * PI / 180.0;
+        let sigma2 = sigma * sigma;
+        OrenNayar {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    // reflectance over albedo, for local directions in the upper hemisphere
+    fn factor(&self, wo: Vector, wi: Vector) -> f32 {
+        let sin_o = (1.0 - wo.z * wo.z).max(0.0).sqrt();
+        let sin_i = (1.0 - wi.z * wi.z).max(0.0).sqrt();
+
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wo.x * wi.x + wo.y * wi.y) / (sin_o * sin_i)).max(0.0)
+        } else {
+            0.0
+        };
+
+        // alpha is the largest of the two polar angles, beta the smallest
+        let (sin_alpha, tan_beta) = if wi.z < wo.z {
+            (sin_i, sin_o / wo.z)
+        } else {
+            (sin_o, sin_i / wi.z)
+        };
+
+        self.a + self.b * cos_phi * sin_alpha * tan_beta
+    }
+}
+
+impl<T: Texture> Material for OrenNayar<T> {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos> {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        let frame = Onb::from_w(normal);
+        let wo = frame.to_local(-ray.direction.normalized());
+        let wi = Vector::rand_cosine_direction(rng);
+        let direction = frame.to_world(wi);
+        if wo.z <= 0.0 || direction.dot(geometric_normal) <= 0.0 {
+            return None;
+        }
+
+        let albedo = self.albedo.value(0.0, 0.0, infos.point).as_vector();
+        Some(MaterialInfos {
+            scattered: Ray::new(infos.point, direction),
+            attenuation: albedo * self.factor(wo, wi),
+            pdf: wi.z / PI,
+            flags: BsdfFlags::DIFFUSE,
+        })
+    }
+
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        let frame = Onb::from_w(normal);
+        let wo = frame.to_local(-ray.direction.normalized());
+        let wi = frame.to_local(direction.normalized());
+        if wo.z <= 0.0 || wi.z <= 0.0 || direction.dot(geometric_normal) <= 0.0 {
+            return Vector::zero();
+        }
+
+        let albedo = self.albedo.value(0.0, 0.0, infos.point).as_vector();
+        albedo * (self.factor(wo, wi) * wi.z / PI)
+    }
+
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32 {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        if ray.direction.dot(normal) >= 0.0 || direction.dot(geometric_normal) <= 0.0 {
+            return 0.0;
+        }
+        (direction.normalized().dot(normal) / PI).max(0.0)
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::DIFFUSE
+    }
+
+    fn albedo(&self, infos: &HitInfos) -> Vector {
+        self.albedo.value(0.0, 0.0, infos.point).as_vector()
+    }
+}
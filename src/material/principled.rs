@@ -0,0 +1,309 @@
+use std::f32::consts::PI;
+
+use rand::{Rng, RngCore};
+
+use super::*;
+use crate::texture::ConstantTexture;
+
+// Disney style uber material: a Burley diffuse base with sheen, a GGX specular
+// layer, a rough glass lobe for transmission and a clearcoat on top, every
+// parameter being read from a texture at the hit point
+pub struct Principled {
+    pub base_color: Box<dyn Texture>,
+    pub metallic: Box<dyn Texture>,
+    pub roughness: Box<dyn Texture>,
+    pub specular: Box<dyn Texture>,
+    pub sheen: Box<dyn Texture>,
+    pub clearcoat: Box<dyn Texture>,
+    pub clearcoat_roughness: Box<dyn Texture>,
+    pub transmission: Box<dyn Texture>,
+    pub ref_index: f32,
+}
+
+impl Principled {
+    pub fn new<T: Texture + 'static>(base_color: T) -> Principled {
+        Principled {
+            base_color: Box::new(base_color),
+            metallic: Box::new(ConstantTexture::scalar(0.0)),
+            roughness: Box::new(ConstantTexture::scalar(0.5)),
+            specular: Box::new(ConstantTexture::scalar(0.5)),
+            sheen: Box::new(ConstantTexture::scalar(0.0)),
+            clearcoat: Box::new(ConstantTexture::scalar(0.0)),
+            clearcoat_roughness: Box::new(ConstantTexture::scalar(0.1)),
+            transmission: Box::new(ConstantTexture::scalar(0.0)),
+            ref_index: 1.5,
+        }
+    }
+
+    pub fn with_metallic<T: Texture + 'static>(mut self, metallic: T) -> Principled {
+        self.metallic = Box::new(metallic);
+        self
+    }
+
+    pub fn with_roughness<T: Texture + 'static>(mut self, roughness: T) -> Principled {
+        self.roughness = Box::new(roughness);
+        self
+    }
+
+    pub fn with_specular<T: Texture + 'static>(mut self, specular: T) -> Principled {
+        self.specular = Box::new(specular);
+        self
+    }
+
+    pub fn with_sheen<T: Texture + 'static>(mut self, sheen: T) -> Principled {
+        self.sheen = Box::new(sheen);
+        self
+    }
+
+    pub fn with_clearcoat<T: Texture + 'static, R: Texture + 'static>(mut self, clearcoat: T, roughness: R) -> Principled {
+        self.clearcoat = Box::new(clearcoat);
+        self.clearcoat_roughness = Box::new(roughness);
+        self
+    }
+
+    pub fn with_transmission<T: Texture + 'static>(mut self, transmission: T, ref_index: f32) -> Principled {
+        self.transmission = Box::new(transmission);
+        self.ref_index = ref_index;
+        self
+    }
+
+    fn lobes(&self, ray: Ray, infos: &HitInfos) -> Lobes {
+        let p = infos.point;
+        let base_color = self.base_color.value(0.0, 0.0, p).as_vector();
+        let metallic = self.metallic.scalar_value(0.0, 0.0, p);
+        let roughness = self.roughness.scalar_value(0.0, 0.0, p);
+        let specular = self.specular.scalar_value(0.0, 0.0, p);
+        let sheen = self.sheen.scalar_value(0.0, 0.0, p);
+        let clearcoat = self.clearcoat.scalar_value(0.0, 0.0, p);
+        let clearcoat_roughness = self.clearcoat_roughness.scalar_value(0.0, 0.0, p);
+        let transmission = self.transmission.scalar_value(0.0, 0.0, p);
+
+        let dielectric_f0 = Vector::new(1.0, 1.0, 1.0) * (0.08 * specular);
+        let specular_f0 = dielectric_f0 * (1.0 - metallic) + base_color * metallic;
+
+        let diffuse_weight = (1.0 - metallic) * (1.0 - transmission);
+        let glass_weight = (1.0 - metallic) * transmission;
+        let specular_weight = 1.0 - glass_weight;
+        let clearcoat_weight = 0.25 * clearcoat;
+
+        // lobes are picked in proportion of their rough contribution
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        let frame = Onb::from_w(normal);
+        let wo = frame.to_local(-ray.direction.normalized());
+        let specular_albedo = utils::mean(fresnel_schlick(specular_f0, wo.z));
+        let probs = [
+            diffuse_weight * (utils::mean(base_color) + sheen),
+            specular_weight * specular_albedo,
+            clearcoat_weight * utils::mean(fresnel_schlick(Vector::new(0.04, 0.04, 0.04), wo.z)),
+            glass_weight * utils::mean(base_color),
+        ];
+        let total: f32 = probs.iter().sum();
+        let probs = if total > 0.0 {
+            [probs[0] / total, probs[1] / total, probs[2] / total, probs[3] / total]
+        } else {
+            [1.0, 0.0, 0.0, 0.0]
+        };
+
+        Lobes {
+            frame,
+            geometric_normal,
+            wo,
+            base_color,
+            roughness,
+            sheen,
+            specular_f0,
+            diffuse_weight,
+            specular_weight,
+            clearcoat_weight,
+            glass_weight,
+            probs,
+            specular: GGX::isotropic(roughness),
+            clearcoat: GGX::isotropic(clearcoat_roughness),
+            glass: RoughDielectric::new(self.ref_index, GGX::isotropic(roughness)),
+            inside: transmission > 0.0 && ray.direction.dot(infos.geometric_normal) > 0.0,
+        }
+    }
+}
+
+struct Lobes {
+    frame: Onb,
+    geometric_normal: Vector,
+    wo: Vector,
+    base_color: Vector,
+    roughness: f32,
+    sheen: f32,
+    specular_f0: Vector,
+    diffuse_weight: f32,
+    specular_weight: f32,
+    clearcoat_weight: f32,
+    glass_weight: f32,
+    // diffuse, specular, clearcoat, glass
+    probs: [f32; 4],
+    specular: GGX,
+    clearcoat: GGX,
+    glass: RoughDielectric,
+    // inside a transmissive object only the glass interface is seen
+    inside: bool,
+}
+
+impl Lobes {
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector {
+        let wo = self.wo;
+        let wi = self.frame.to_local(direction.normalized());
+        let glass = self.glass.eval(ray, infos, direction) * self.glass_weight;
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            // only transmitted light is tinted
+            return glass * self.base_color;
+        }
+
+        let h = (wo + wi).normalized();
+        let cos_d = wi.dot(h);
+
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let burley = (1.0 + (fd90 - 1.0) * (1.0 - wi.z).powi(5)) * (1.0 + (fd90 - 1.0) * (1.0 - wo.z).powi(5));
+        let diffuse = self.base_color * (burley / PI) + Vector::new(1.0, 1.0, 1.0) * (self.sheen * (1.0 - cos_d).powi(5));
+
+        let specular = fresnel_schlick(self.specular_f0, cos_d)
+            * (self.specular.d(h) * self.specular.g(wo, wi) / (4.0 * wo.z));
+        let clearcoat = fresnel_schlick(Vector::new(0.04, 0.04, 0.04), cos_d)
+            * (self.clearcoat.d(h) * self.clearcoat.g(wo, wi) / (4.0 * wo.z));
+
+        diffuse * (self.diffuse_weight * wi.z)
+            + specular * self.specular_weight
+            + clearcoat * self.clearcoat_weight
+            + glass
+    }
+
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32 {
+        let wo = self.wo;
+        let wi = self.frame.to_local(direction.normalized());
+        let glass = self.probs[3] * self.glass.pdf(ray, infos, direction);
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return glass;
+        }
+
+        let h = (wo + wi).normalized();
+        self.probs[0] * wi.z / PI
+            + self.probs[1] * self.specular.pdf_visible(wo, h) / (4.0 * wo.dot(h))
+            + self.probs[2] * self.clearcoat.pdf_visible(wo, h) / (4.0 * wo.dot(h))
+            + glass
+    }
+}
+
+impl Material for Principled {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos> {
+        let lobes = self.lobes(ray, infos);
+        if lobes.inside {
+            return lobes.glass.sample(ray, infos, rng);
+        }
+        if lobes.wo.z <= 0.0 {
+            return None;
+        }
+
+        let choice: f32 = rng.gen();
+        let (direction, flags) = if choice < lobes.probs[0] {
+            (lobes.frame.to_world(Vector::rand_cosine_direction(rng)), BsdfFlags::DIFFUSE)
+        } else if choice < lobes.probs[0] + lobes.probs[1] {
+            let m = lobes.specular.sample_visible(lobes.wo, rng);
+            (lobes.frame.to_world(reflect_local(lobes.wo, m)), BsdfFlags::GLOSSY)
+        } else if choice < lobes.probs[0] + lobes.probs[1] + lobes.probs[2] {
+            let m = lobes.clearcoat.sample_visible(lobes.wo, rng);
+            (lobes.frame.to_world(reflect_local(lobes.wo, m)), BsdfFlags::GLOSSY)
+        } else {
+            let sample = lobes.glass.sample(ray, infos, rng)?;
+            (sample.scattered.direction, sample.flags)
+        };
+
+        let reflected = lobes.frame.to_local(direction).z > 0.0;
+        if (direction.dot(lobes.geometric_normal) > 0.0) != reflected {
+            return None;
+        }
+
+        let pdf = lobes.pdf(ray, infos, direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        Some(MaterialInfos {
+            scattered: Ray::new(infos.point, direction),
+            attenuation: lobes.eval(ray, infos, direction) / pdf,
+            pdf,
+            flags,
+        })
+    }
+
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector {
+        let lobes = self.lobes(ray, infos);
+        if lobes.inside {
+            lobes.glass.eval(ray, infos, direction)
+        } else {
+            lobes.eval(ray, infos, direction)
+        }
+    }
+
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32 {
+        let lobes = self.lobes(ray, infos);
+        if lobes.inside {
+            lobes.glass.pdf(ray, infos, direction)
+        } else {
+            lobes.pdf(ray, infos, direction)
+        }
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::DIFFUSE | BsdfFlags::GLOSSY | BsdfFlags::TRANSMISSION
+    }
+
+    fn albedo(&self, infos: &HitInfos) -> Vector {
+        self.base_color.value(0.0, 0.0, infos.point).as_vector()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::albedos;
+
+    fn scalar(value: f32) -> ConstantTexture {
+        ConstantTexture::scalar(value)
+    }
+
+    #[test]
+    fn dielectric_base_samples_what_it_evaluates() {
+        for albedo in albedos(Principled::new(scalar(0.5)), 0.02) {
+            assert!(albedo.x > 0.3 && albedo.x < 1.0, "principled albedo {}", albedo.x);
+        }
+    }
+
+    #[test]
+    fn metal_samples_what_it_evaluates() {
+        let dark = albedos(Principled::new(scalar(0.2)).with_metallic(scalar(1.0)), 0.02);
+        let bright = albedos(Principled::new(scalar(0.8)).with_metallic(scalar(1.0)), 0.02);
+        for (dark, bright) in dark.iter().zip(&bright) {
+            assert!(dark.x < bright.x && bright.x <= 1.0, "metal albedos {} and {}", dark.x, bright.x);
+        }
+    }
+
+    #[test]
+    fn sheen_and_clearcoat_add_to_the_base() {
+        let base = albedos(Principled::new(scalar(0.5)), 0.02);
+        let layered = albedos(Principled::new(scalar(0.5)).with_sheen(scalar(1.0)).with_clearcoat(scalar(1.0), scalar(0.3)), 0.02);
+        for (base, layered) in base.iter().zip(&layered) {
+            assert!(layered.x > base.x, "{} <= {}", layered.x, base.x);
+        }
+    }
+
+    #[test]
+    fn transmission_samples_what_it_evaluates() {
+        for albedo in albedos(Principled::new(scalar(1.0)).with_transmission(scalar(1.0), 1.5), 0.02) {
+            assert!(albedo.x > 0.8 && albedo.x <= 1.0, "principled albedo {}", albedo.x);
+        }
+    }
+
+    #[test]
+    fn rough_white_base_samples_what_it_evaluates() {
+        // the Burley diffuse retro reflects at grazing angles and isn't energy
+        // conserving there, only the sampling is checked
+        albedos(Principled::new(scalar(1.0)).with_roughness(scalar(1.0)), 0.02);
+    }
+}
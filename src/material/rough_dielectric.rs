@@ -0,0 +1,172 @@
+use rand::{Rng, RngCore};
+
+use super::*;
+use crate::spectrum::Ior;
+
+// rough glass following Walter et al. 2007, reflecting and transmitting through
+// GGX distributed microfacets
+#[derive(Debug, Clone)]
+pub struct RoughDielectric {
+    pub ref_index: f32,
+    pub dispersion: Option<Ior>,
+    pub absorption: Option<Vector>,
+    pub distribution: GGX,
+}
+
+struct LocalSetup {
+    frame: Onb,
+    geometric_normal: Vector,
+    wo: Vector,
+    eta: f32,
+}
+
+impl RoughDielectric {
+    pub fn new(ref_index: f32, distribution: GGX) -> RoughDielectric {
+        RoughDielectric { ref_index, dispersion: None, absorption: None, distribution }
+    }
+
+    pub fn dispersive(ior: Ior, distribution: GGX) -> RoughDielectric {
+        RoughDielectric { ref_index: ior.nominal(), dispersion: Some(ior), absorption: None, distribution }
+    }
+
+    pub fn with_absorption(self, color: Vector, distance: f32) -> RoughDielectric {
+        RoughDielectric { absorption: Some(utils::absorption_coefficient(color, distance)), ..self }
+    }
+
+    // local frame on the side of the incoming ray, with the relative index of refraction
+    fn setup(&self, ray: Ray, infos: &HitInfos) -> LocalSetup {
+        let (normal, geometric_normal) = utils::face_forward(ray, infos);
+        let entering = ray.direction.dot(infos.geometric_normal) <= 0.0;
+        let ref_index = utils::ref_index_at(self.ref_index, self.dispersion, ray.wavelength);
+        let eta = if entering { ref_index } else { 1.0 / ref_index };
+        let frame = Onb::from_w(normal);
+        let wo = frame.to_local(-ray.direction.normalized());
+        LocalSetup { frame, geometric_normal, wo, eta }
+    }
+
+    // half vector of a transmission, oriented towards the side of wo
+    fn transmission_half_vector(wo: Vector, wi: Vector, eta: f32) -> Vector {
+        let m = (wo + wi * eta).normalized();
+        if m.z < 0.0 { -m } else { m }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn sample(&self, ray: Ray, infos: &HitInfos, rng: &mut dyn RngCore) -> Option<MaterialInfos> {
+        let LocalSetup { frame, geometric_normal, wo, eta } = self.setup(ray, infos);
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let m = self.distribution.sample_visible(wo, rng);
+        let fresnel = fresnel_dielectric(wo.dot(m), eta);
+
+        // picking the lobe with the fresnel probability cancels it from the weight
+        let wi = if rng.gen::<f32>() < fresnel {
+            let wi = reflect_local(wo, m);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let wi = refract_local(wo, m, eta)?;
+            if wi.z >= 0.0 {
+                return None;
+            }
+            wi
+        };
+
+        let direction = frame.to_world(wi);
+        if (direction.dot(geometric_normal) > 0.0) != (wi.z > 0.0) {
+            return None;
+        }
+
+        let weight = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+        let flags = if wi.z > 0.0 { BsdfFlags::GLOSSY } else { BsdfFlags::GLOSSY | BsdfFlags::TRANSMISSION };
+        Some(MaterialInfos {
+            scattered: Ray::new(infos.point, direction),
+            attenuation: Vector::new(weight, weight, weight),
+            pdf: self.pdf(ray, infos, direction),
+            flags,
+        })
+    }
+
+    fn eval(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> Vector {
+        let LocalSetup { frame, wo, eta, .. } = self.setup(ray, infos);
+        let wi = frame.to_local(direction.normalized());
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return Vector::zero();
+        }
+
+        let value = if wi.z > 0.0 {
+            let m = (wo + wi).normalized();
+            let fresnel = fresnel_dielectric(wo.dot(m), eta);
+            fresnel * self.distribution.d(m) * self.distribution.g(wo, wi) / (4.0 * wo.z)
+        } else {
+            let m = Self::transmission_half_vector(wo, wi, eta);
+            let (wo_m, wi_m) = (wo.dot(m), wi.dot(m));
+            if wo_m <= 0.0 || wi_m >= 0.0 {
+                return Vector::zero();
+            }
+
+            let fresnel = fresnel_dielectric(wo_m, eta);
+            let denom = wo_m + eta * wi_m;
+            (1.0 - fresnel) * self.distribution.d(m) * self.distribution.g(wo, wi)
+                * eta * eta * wo_m * -wi_m / (wo.z * denom * denom)
+        };
+
+        Vector::new(value, value, value)
+    }
+
+    fn pdf(&self, ray: Ray, infos: &HitInfos, direction: Vector) -> f32 {
+        let LocalSetup { frame, wo, eta, .. } = self.setup(ray, infos);
+        let wi = frame.to_local(direction.normalized());
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return 0.0;
+        }
+
+        if wi.z > 0.0 {
+            let m = (wo + wi).normalized();
+            let fresnel = fresnel_dielectric(wo.dot(m), eta);
+            fresnel * self.distribution.pdf_visible(wo, m) / (4.0 * wo.dot(m))
+        } else {
+            let m = Self::transmission_half_vector(wo, wi, eta);
+            let (wo_m, wi_m) = (wo.dot(m), wi.dot(m));
+            if wo_m <= 0.0 || wi_m >= 0.0 {
+                return 0.0;
+            }
+
+            let fresnel = fresnel_dielectric(wo_m, eta);
+            let denom = wo_m + eta * wi_m;
+            (1.0 - fresnel) * self.distribution.pdf_visible(wo, m) * eta * eta * -wi_m / (denom * denom)
+        }
+    }
+
+    fn flags(&self) -> BsdfFlags {
+        BsdfFlags::GLOSSY | BsdfFlags::TRANSMISSION
+    }
+
+    fn absorption(&self) -> Option<Vector> {
+        self.absorption
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::tests::{albedos, albedos_from_inside};
+
+    #[test]
+    fn leaving_glass_samples_what_it_evaluates() {
+        for albedo in albedos_from_inside(RoughDielectric::new(1.5, GGX::isotropic(0.5)), 0.02) {
+            assert!(albedo.x <= 1.0, "dielectric albedo {}", albedo.x);
+        }
+    }
+
+    #[test]
+    fn anisotropic_glass_samples_what_it_evaluates() {
+        for albedo in albedos(RoughDielectric::new(1.33, GGX::anisotropic(0.6, 0.7)), 0.02) {
+            assert!(albedo.x <= 1.0 && albedo.x > 0.7, "dielectric albedo {}", albedo.x);
+        }
+    }
+}
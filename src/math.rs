@@ -1,3 +1,4 @@
+use std::f32::consts::PI;
 use std::ops::{Add, Neg, Mul, Sub, Div};
 
 use rand::Rng;
@@ -81,6 +82,15 @@ impl Vector {
         }
     }
 
+    // direction in the +z hemisphere with a density of cos(theta) / pi
+    pub fn rand_cosine_direction<R: Rng + ?Sized>(rng: &mut R) -> Vector {
+        let r = rng.gen::<f32>().sqrt();
+        let phi = 2.0 * PI * rng.gen::<f32>();
+        let x = r * phi.cos();
+        let y = r * phi.sin();
+        Vector::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+    }
+
     pub fn rand_in_unit_disk<R: Rng + ?Sized>(rng: &mut R) -> Vector {
         loop {
             let v = Vector::new(rng.gen(), rng.gen(), 0.0) * 2.0 - Vector::new(1.0, 1.0, 0.0);
@@ -91,6 +101,35 @@ impl Vector {
     }
 }
 
+// orthonormal basis around w, to move directions in and out of a local space
+// where w is +z
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub u: Vector,
+    pub v: Vector,
+    pub w: Vector,
+}
+
+impl Onb {
+    pub fn from_w(w: Vector) -> Onb {
+        // Duff et al. 2017, branchless and continuous except across z = 0
+        let sign = 1.0f32.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        let u = Vector::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x);
+        let v = Vector::new(b, sign + w.y * w.y * a, -w.y);
+        Onb { u, v, w }
+    }
+
+    pub fn to_local(self, a: Vector) -> Vector {
+        Vector::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+
+    pub fn to_world(self, a: Vector) -> Vector {
+        self.u * a.x + self.v * a.y + self.w * a.z
+    }
+}
+
 impl Add<Vector> for Point {
     type Output = Point;
 
@@ -167,6 +206,18 @@ impl Mul<f32> for Vector {
     }
 }
 
+impl Mul<Vector> for Vector {
+    type Output = Vector;
+
+    fn mul(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x * other.x,
+            y: self.y * other.y,
+            z: self.z * other.z,
+        }
+    }
+}
+
 impl Div<f32> for Vector {
     type Output = Vector;
 
@@ -177,4 +228,122 @@ impl Div<f32> for Vector {
             z: self.z / other,
         }
     }
-}
\ No newline at end of file
+}
+
+// row major 3x3 matrix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix3([[f32; 3]; 3]);
+
+impl Matrix3 {
+    pub const fn new(rows: [[f32; 3]; 3]) -> Matrix3 {
+        Matrix3(rows)
+    }
+
+    pub fn identity() -> Matrix3 {
+        Matrix3::scale(1.0)
+    }
+
+    pub fn scale(s: f32) -> Matrix3 {
+        Matrix3([[s, 0.0, 0.0], [0.0, s, 0.0], [0.0, 0.0, s]])
+    }
+
+    // counterclockwise rotation around `axis`, angle in degrees
+    pub fn rotation(axis: Vector, angle: f32) -> Matrix3 {
+        let Vector { x, y, z } = axis.normalized();
+        let (sin, cos) = (angle * PI / 180.0).sin_cos();
+        let t = 1.0 - cos;
+        Matrix3([
+            [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y],
+            [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x],
+            [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos],
+        ])
+    }
+
+    pub fn apply(&self, v: Vector) -> Vector {
+        let row = |r: [f32; 3]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+        Vector::new(row(self.0[0]), row(self.0[1]), row(self.0[2]))
+    }
+
+    pub fn transpose(&self) -> Matrix3 {
+        let m = self.0;
+        Matrix3([[m[0][0], m[1][0], m[2][0]], [m[0][1], m[1][1], m[2][1]], [m[0][2], m[1][2], m[2][2]]])
+    }
+
+    // None for singular matrices
+    pub fn inverse(&self) -> Option<Matrix3> {
+        let m = self.0;
+        // rows of the inverse are the cross products of the columns over the determinant
+        let (c0, c1, c2) = (
+            Vector::new(m[0][0], m[1][0], m[2][0]),
+            Vector::new(m[0][1], m[1][1], m[2][1]),
+            Vector::new(m[0][2], m[1][2], m[2][2]),
+        );
+        let det = c0.dot(c1.cross(c2));
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        let row = |v: Vector| [v.x / det, v.y / det, v.z / det];
+        Some(Matrix3([row(c1.cross(c2)), row(c2.cross(c0)), row(c0.cross(c1))]))
+    }
+}
+
+impl Mul for Matrix3 {
+    type Output = Matrix3;
+
+    fn mul(self, other: Matrix3) -> Matrix3 {
+        let (a, b) = (self.0, other.0);
+        let entry = |i: usize, j: usize| a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j];
+        Matrix3([
+            [entry(0, 0), entry(0, 1), entry(0, 2)],
+            [entry(1, 0), entry(1, 1), entry(1, 2)],
+            [entry(2, 0), entry(2, 1), entry(2, 2)],
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).norm() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_identity(m: Matrix3) {
+        for (i, row) in m.0.iter().enumerate() {
+            for (j, &v) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((v - expected).abs() < 1e-5, "{:?}", m);
+            }
+        }
+    }
+
+    #[test]
+    fn rotation_turns_counterclockwise() {
+        let quarter = Matrix3::rotation(Vector::new(0.0, 0.0, 2.0), 90.0);
+        assert_close(quarter.apply(Vector::new(1.0, 0.0, 0.0)), Vector::new(0.0, 1.0, 0.0));
+        assert_close(quarter.apply(Vector::new(0.0, 0.0, 3.0)), Vector::new(0.0, 0.0, 3.0));
+    }
+
+    #[test]
+    fn rotation_inverse_round_trip() {
+        let rotation = Matrix3::rotation(Vector::new(1.0, -2.0, 0.5), 37.0);
+        let inverse = rotation.inverse().unwrap();
+        assert_identity(rotation * inverse);
+        assert_identity(inverse * rotation);
+        // orthonormal, its inverse is its transpose
+        assert_identity(rotation * rotation.transpose());
+
+        let v = Vector::new(0.3, 4.0, -2.0);
+        assert_close(inverse.apply(rotation.apply(v)), v);
+        assert_close(Matrix3::rotation(Vector::new(1.0, -2.0, 0.5), -37.0).apply(rotation.apply(v)), v);
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert_eq!(Matrix3::scale(0.0).inverse(), None);
+        let scale = Matrix3::scale(4.0).inverse().unwrap();
+        assert_identity(scale * Matrix3::scale(4.0));
+    }
+}
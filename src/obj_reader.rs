@@ -4,71 +4,177 @@ use std::fs::File;
 use std::sync::Arc;
 
 use raytracer::prelude::*;
-use raytracer::hitable::Triangle;
+use raytracer::hitable::{Tagged, Triangle};
 use raytracer::material::Lambertian;
 
+// faces meeting at a sharper angle than this, in degrees, keep a hard edge
+// between them when the normals are generated
+const CREASE_ANGLE: f32 = 60.0;
+
 pub fn read_obj_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<Box<dyn Hitable>>> {
     let file = File::open(path)?;
     let buf_reader = io::BufReader::new(file);
 
     let mut points = Vec::new();
-    let mut triangles: Vec<Box<dyn Hitable>> = Vec::new();
+    let mut normals = Vec::new();
+    let mut faces = Vec::new();
+    // ids of the `o` or `g` groups and `usemtl` materials, for the id passes
+    let mut groups = Vec::new();
+    let mut material_names: Vec<String> = Vec::new();
+    let mut ids = (0, 0);
     let texture: raytracer::texture::ConstantTexture = Color::from_floats(0.9, 0.2, 0.1).into();
     let material: Arc<dyn Material> = Arc::new(Lambertian::new(texture));
 
     for line in buf_reader.lines() {
         match parse_line(line?) {
             Some(LineItem::Vertex(p)) => points.push(p),
+            Some(LineItem::Normal(n)) => normals.push(n),
             Some(LineItem::Triangle(a, b, c)) => {
-                let p0 = points[a - 1];
-                let p1 = points[b - 1];
-                let p2 = points[c - 1];
+                faces.push([a, b, c]);
+                groups.push(ids);
+            },
+            Some(LineItem::Quad(a, b, c, d)) => {
+                faces.push([a, b, c]);
+                faces.push([a, c, d]);
+                groups.push(ids);
+                groups.push(ids);
+            },
+            Some(LineItem::Group) => ids.0 += 1,
+            Some(LineItem::UseMaterial(name)) => {
+                let index = match material_names.iter().position(|n| *n == name) {
+                    Some(index) => index,
+                    None => {
+                        material_names.push(name);
+                        material_names.len() - 1
+                    },
+                };
+                ids.1 = index as u32 + 1;
+            },
+            _ => {}
+        }
+    }
 
-                let t0 = Triangle::new_with_arc(p0, p1, p2, material.clone());
+    // without normals in the file, smooth the mesh with area weighted vertex
+    // normals, except across its creases
+    let generated_normals = if normals.is_empty() {
+        Some(generate_vertex_normals(&points, &faces, CREASE_ANGLE))
+    } else {
+        None
+    };
 
-                triangles.push(Box::new(t0));
-            }
-            Some(LineItem::Quad(a, b, c, d)) => {
-                let p0 = points[a - 1];
-                let p1 = points[b - 1];
-                let p2 = points[c - 1];
-                let p3 = points[d - 1];
+    let mut triangles: Vec<Box<dyn Hitable>> = Vec::with_capacity(faces.len());
+    for (index, (face, (object_id, material_id))) in faces.into_iter().zip(groups).enumerate() {
+        let [p0, p1, p2] = [points[face[0].point - 1], points[face[1].point - 1], points[face[2].point - 1]];
 
-                let t0 = Triangle::new_with_arc(p0, p1, p2, material.clone());
-                let t1 = Triangle::new_with_arc(p0, p2, p3, material.clone());
+        let vertex_normals = match (&generated_normals, face[0].normal, face[1].normal, face[2].normal) {
+            (Some(generated), _, _, _) => Some(generated[index]),
+            (None, Some(n0), Some(n1), Some(n2)) => Some([normals[n0 - 1], normals[n1 - 1], normals[n2 - 1]]),
+            _ => None,
+        };
 
-                triangles.push(Box::new(t0));
-                triangles.push(Box::new(t1));
+        let triangle = match vertex_normals {
+            Some(vertex_normals) if vertex_normals.iter().all(|n| n.norm_squared() > 0.0) => {
+                Triangle::new_smooth_with_arc(p0, p1, p2, vertex_normals, material.clone())
             },
-            _ => {}
+            _ => Triangle::new_with_arc(p0, p1, p2, material.clone()),
+        };
+        if object_id == 0 && material_id == 0 {
+            triangles.push(Box::new(triangle));
+        } else {
+            triangles.push(Box::new(Tagged::new(triangle, object_id, material_id)));
         }
     }
 
     Ok(triangles)
 }
 
+// normals of the corners of every face, averaging the faces around each
+// vertex weighted by their area, leaving out the ones making an angle
+// larger than `crease_angle` degrees with the face of the corner
+fn generate_vertex_normals(points: &[Point], faces: &[[FaceVertex; 3]], crease_angle: f32) -> Vec<[Vector; 3]> {
+    // the cross product norm is twice the face area
+    let face_normals: Vec<Vector> = faces.iter()
+        .map(|face| {
+            let [p0, p1, p2] = [points[face[0].point - 1], points[face[1].point - 1], points[face[2].point - 1]];
+            (p1 - p0).cross(p2 - p0)
+        })
+        .collect();
+    let unit = |n: Vector| if n.norm_squared() > 0.0 { n.normalized() } else { n };
+
+    let mut vertex_faces = vec![Vec::new(); points.len()];
+    for (index, face) in faces.iter().enumerate() {
+        for vertex in face {
+            vertex_faces[vertex.point - 1].push(index);
+        }
+    }
+
+    let min_cos = crease_angle.to_radians().cos();
+    faces.iter().zip(&face_normals)
+        .map(|(face, &face_normal)| {
+            let corner = |vertex: FaceVertex| {
+                let sum = vertex_faces[vertex.point - 1].iter()
+                    .map(|&other| face_normals[other])
+                    .filter(|&other| unit(other).dot(unit(face_normal)) >= min_cos)
+                    .fold(Vector::zero(), |sum, other| sum + other);
+                unit(sum)
+            };
+            [corner(face[0]), corner(face[1]), corner(face[2])]
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FaceVertex {
+    point: usize,
+    normal: Option<usize>,
+}
+
 enum LineItem {
     Vertex(Point),
-    Triangle(usize, usize, usize),
-    Quad(usize, usize, usize, usize),
+    Normal(Vector),
+    Triangle(FaceVertex, FaceVertex, FaceVertex),
+    Quad(FaceVertex, FaceVertex, FaceVertex, FaceVertex),
+    Group,
+    UseMaterial(String),
+}
+
+fn parse_floats(rest: &str) -> Vec<f32> {
+    rest.split(' ')
+        .filter(|a| !a.trim().is_empty())
+        .take(3)
+        .map(|a| a.trim().parse().unwrap())
+        .collect()
+}
+
+fn parse_face_vertex(item: &str) -> FaceVertex {
+    // v, v/vt, v//vn or v/vt/vn
+    let mut indices = item.trim().split('/');
+    let point = indices.next().unwrap().parse().unwrap();
+    let normal = indices.nth(1)
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse().unwrap());
+
+    FaceVertex { point, normal }
 }
 
 fn parse_line(line: String) -> Option<LineItem> {
-    if let Some(index) = line.find("v ") {
-        let rest = &line[(index + 2)..];
-        let c: Vec<f32> = rest.split(' ')
-            .filter(|a| !a.trim().is_empty())
-            .take(3)
-            .map(|a| a.parse().unwrap())
-            .collect();
+    let trimmed = line.trim_start();
+    if trimmed.starts_with("o ") || trimmed.starts_with("g ") {
+        Some(LineItem::Group)
+    } else if let Some(name) = trimmed.strip_prefix("usemtl ") {
+        Some(LineItem::UseMaterial(name.trim().to_string()))
+    } else if let Some(index) = line.find("vn ") {
+        let c = parse_floats(&line[(index + 3)..]);
+        Some(LineItem::Normal(Vector::new(c[0], c[1], c[2])))
+    } else if let Some(index) = line.find("v ") {
+        let c = parse_floats(&line[(index + 2)..]);
         let point = Point::new(c[0], c[1], c[2]);
         Some(LineItem::Vertex(point))
     } else if let Some(index) = line.find("f ") {
         let rest = &line[(index + 2)..];
-        let c: Vec<usize> = rest.split(' ')
+        let c: Vec<FaceVertex> = rest.split(' ')
             .filter(|a| !a.trim().is_empty())
-            .map(|a| a.trim().split('/').next().unwrap())
-            .map(|a| a.parse().unwrap())
+            .map(parse_face_vertex)
             .collect();
 
         if c.len() == 3 {
@@ -81,4 +187,53 @@ fn parse_line(line: String) -> Option<LineItem> {
     } else {
         None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(point: usize) -> FaceVertex {
+        FaceVertex { point, normal: None }
+    }
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).norm() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    // two faces along the x axis, the second one tilted by `angle` degrees
+    fn fold(angle: f32) -> Vec<[Vector; 3]> {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let points = [
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, -1.0),
+            Point::new(0.0, sin, cos),
+        ];
+        let faces = [[vertex(1), vertex(2), vertex(3)], [vertex(2), vertex(1), vertex(4)]];
+        generate_vertex_normals(&points, &faces, CREASE_ANGLE)
+    }
+
+    #[test]
+    fn gentle_fold_is_smoothed() {
+        let normals = fold(20.0);
+        let shared = Vector::new(0.0, (10.0f32).to_radians().cos(), -(10.0f32).to_radians().sin());
+        // corners on the fold get the same normal, halfway between the faces
+        assert_close(normals[0][0], shared);
+        assert_close(normals[0][1], shared);
+        assert_close(normals[1][0], shared);
+        // the corners off the fold only see their own face
+        assert_close(normals[0][2], Vector::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn sharp_fold_keeps_a_hard_edge() {
+        let normals = fold(90.0);
+        for &corner in &normals[0] {
+            assert_close(corner, Vector::new(0.0, 1.0, 0.0));
+        }
+        for &corner in &normals[1] {
+            assert_close(corner, Vector::new(0.0, 0.0, -1.0));
+        }
+    }
+}
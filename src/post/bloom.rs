@@ -0,0 +1,135 @@
+use crate::math::Vector;
+use crate::film::luminance;
+use crate::ray_image::RayImage;
+use super::{utils, Effect};
+
+// glow around bright pixels, as if some of their light was scattered by the
+// lens: the light above `threshold` is spread with a gaussian, `intensity`
+// being the part of it that is moved, `size` the standard deviation as a
+// fraction of the image width
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bloom {
+    pub threshold: f32,
+    pub intensity: f32,
+    pub size: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Bloom {
+        Bloom { threshold: 1.0, intensity: 0.1, size: 0.01 }
+    }
+}
+
+// the blur happens at a resolution where the deviation is about that many pixels
+const BLUR_PIXELS: f32 = 4.0;
+
+impl Effect for Bloom {
+    fn apply(&self, image: &RayImage<Vector>) -> RayImage<Vector> {
+        let (width, _) = image.get_dimensions();
+        let bright = image.map(|v| {
+            let l = luminance(v);
+            if l > self.threshold { v * ((l - self.threshold) / l) } else { Vector::zero() }
+        });
+
+        let sigma = (self.size * width as f32).max(f32::EPSILON);
+        let factor = ((sigma / BLUR_PIXELS) as usize).max(1);
+        let blurred = gaussian_blur(&downsample(&bright, factor), sigma / factor as f32);
+
+        let mut result = image.clone();
+        utils::for_each_pixel(&mut result, |x, y, pixel| {
+            // centers of the full resolution pixels in the downsampled image
+            let sx = (x as f32 + 0.5) / factor as f32 - 0.5;
+            let sy = (y as f32 + 0.5) / factor as f32 - 0.5;
+            let glow = utils::bilinear(&blurred, sx, sy);
+            *pixel = *pixel + (glow - bright.get_pixel(x, y)) * self.intensity;
+        });
+        result
+    }
+}
+
+// averages of `factor` by `factor` blocks
+fn downsample(image: &RayImage<Vector>, factor: usize) -> RayImage<Vector> {
+    let (width, height) = image.get_dimensions();
+    RayImage::from_fn(width.div_ceil(factor), height.div_ceil(factor), |sx, sy| {
+        let (x1, y1) = (((sx + 1) * factor).min(width), ((sy + 1) * factor).min(height));
+        let block = image.view(sx * factor, sy * factor, x1 - sx * factor, y1 - sy * factor);
+        let (block_width, block_height) = block.get_dimensions();
+        let sum = (0..block_height).flat_map(|y| block.row(y)).fold(Vector::zero(), |sum, &v| sum + v);
+        sum / (block_width * block_height) as f32
+    })
+}
+
+// separable, the weights being renormalized at the edges of the image
+fn gaussian_blur(image: &RayImage<Vector>, sigma: f32) -> RayImage<Vector> {
+    let (width, height) = image.get_dimensions();
+    let radius = (3.0 * sigma).ceil() as isize;
+    let kernel: Vec<f32> = (-radius..=radius).map(|d| (-(d * d) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+    let pass = |image: &RayImage<Vector>, horizontal: bool| {
+        let mut blurred = RayImage::new(width, height);
+        utils::for_each_pixel(&mut blurred, |x, y, pixel| {
+            let mut sum = Vector::zero();
+            let mut total = 0.0;
+            for (k, weight) in (-radius..=radius).zip(&kernel) {
+                let (qx, qy) = if horizontal { (x as isize + k, y as isize) } else { (x as isize, y as isize + k) };
+                if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                    continue;
+                }
+                sum = sum + image.get_pixel(qx as usize, qy as usize) * *weight;
+                total += weight;
+            }
+            *pixel = sum / total;
+        });
+        blurred
+    };
+    pass(&pass(image, true), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 64;
+
+    fn total(image: &RayImage<Vector>) -> Vector {
+        image.pixels().iter().fold(Vector::zero(), |sum, &v| sum + v)
+    }
+
+    #[test]
+    fn light_under_the_threshold_is_kept() {
+        let image = RayImage::from_fn(SIZE, SIZE, |x, y| Vector::new(0.9, 0.5 * (x % 2) as f32, 0.1 * (y % 3) as f32));
+        assert!(Bloom::default().apply(&image).pixels() == image.pixels());
+    }
+
+    #[test]
+    fn bright_pixel_glows_without_adding_light() {
+        let (cx, cy) = (SIZE / 2, SIZE / 2);
+        let mut image = RayImage::from_fn(SIZE, SIZE, |_, _| Vector::new(0.5, 0.5, 0.5));
+        image.set_pixel(cx, cy, Vector::new(101.0, 101.0, 101.0));
+        let bloom = Bloom { threshold: 1.0, intensity: 0.2, size: 0.05 };
+        let result = bloom.apply(&image);
+
+        // a part of the light over the threshold leaves the pixel
+        let center = result.get_pixel(cx, cy).x;
+        assert!(center < 101.0 - 0.15 * 100.0 && center > 101.0 - 0.2 * 100.0, "center {}", center);
+        // and lands around it, less and less far away, the same in every direction
+        let glow = |dx: usize| result.get_pixel(cx + dx, cy).x - 0.5;
+        assert!(glow(1) > glow(3) && glow(3) > glow(6) && glow(6) > 0.0, "glow {} {} {}", glow(1), glow(3), glow(6));
+        for d in 1..8 {
+            let left = result.get_pixel(cx - d, cy).x;
+            let up = result.get_pixel(cx, cy - d).x;
+            assert!((left - up).abs() < 1e-3 * left, "{} against {} at {}", left, up, d);
+        }
+        // far from the edges, moving light leaves the total unchanged
+        let (before, after) = (total(&image).x, total(&result).x);
+        assert!((after - before).abs() < 1e-3 * before, "total went from {} to {}", before, after);
+    }
+
+    #[test]
+    fn intensity_scales_the_glow() {
+        let mut image = RayImage::new(SIZE, SIZE);
+        image.set_pixel(20, 30, Vector::new(11.0, 11.0, 11.0));
+        let glow = |intensity: f32| Bloom { threshold: 1.0, intensity, size: 0.05 }.apply(&image).get_pixel(22, 30).x;
+        assert!(glow(0.0) == 0.0);
+        assert!((glow(0.4) - 2.0 * glow(0.2)).abs() < 1e-5, "{} against {}", glow(0.4), glow(0.2));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::math::Vector;
+use crate::ray_image::RayImage;
+use super::{utils, Effect};
+
+// lateral chromatic aberration, the lens magnifying red a bit more and blue a
+// bit less than green, `strength` being the relative change of magnification
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChromaticAberration {
+    pub strength: f32,
+}
+
+impl ChromaticAberration {
+    pub fn new(strength: f32) -> ChromaticAberration {
+        ChromaticAberration { strength }
+    }
+}
+
+impl Effect for ChromaticAberration {
+    fn apply(&self, image: &RayImage<Vector>) -> RayImage<Vector> {
+        let (width, height) = image.get_dimensions();
+        let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+        let mut result = RayImage::new(width, height);
+        utils::for_each_pixel(&mut result, |x, y, pixel| {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            // the channel seen at a point comes from closer to the center
+            // when it is magnified more
+            let at = |scale: f32| utils::bilinear(image, cx + dx / scale - 0.5, cy + dy / scale - 0.5);
+            *pixel = Vector::new(at(1.0 + self.strength).x, image.get_pixel(x, y).y, at(1.0 - self.strength).z);
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 32;
+
+    // white disk at the center of a black image
+    fn disk(radius: f32) -> RayImage<Vector> {
+        RayImage::from_fn(SIZE, SIZE, |x, y| {
+            let r = utils::radius(x as f32 + 0.5, y as f32 + 0.5, SIZE, SIZE) * SIZE as f32 / 2f32.sqrt();
+            if r < radius { Vector::new(1.0, 1.0, 1.0) } else { Vector::zero() }
+        })
+    }
+
+    #[test]
+    fn red_grows_and_blue_shrinks() {
+        let image = disk(10.0);
+        let result = ChromaticAberration::new(0.1).apply(&image);
+        let count = |channel: fn(Vector) -> f32| result.pixels().iter().map(|&v| channel(v)).sum::<f32>();
+        let (red, green, blue) = (count(|v| v.x), count(|v| v.y), count(|v| v.z));
+        assert!(red > 1.1 * green && blue < 0.9 * green, "red {}, green {}, blue {}", red, green, blue);
+        // green is left where it was
+        for (&before, &after) in image.pixels().iter().zip(result.pixels()) {
+            assert_eq!(before.y, after.y);
+        }
+        // and the center doesn't move
+        assert_eq!(result.get_pixel(SIZE / 2, SIZE / 2), Vector::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn no_strength_or_flat_image_changes_nothing() {
+        let image = disk(10.0);
+        let result = ChromaticAberration::new(0.0).apply(&image);
+        for (&before, &after) in image.pixels().iter().zip(result.pixels()) {
+            assert!((before - after).norm() < 1e-5);
+        }
+        let flat = RayImage::from_fn(SIZE, SIZE, |_, _| Vector::new(0.2, 0.4, 0.6));
+        for &v in ChromaticAberration::new(0.2).apply(&flat).pixels() {
+            assert!((v - Vector::new(0.2, 0.4, 0.6)).norm() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::math::{Matrix3, Vector};
+
+// linear rgb spaces, all going through CIE XYZ with a D65 white point, the
+// D60 white of ACEScg being adapted with the Bradford transform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    // Rec.709 primaries, what the renderer works in by default
+    Srgb,
+    DisplayP3,
+    Rec2020,
+    AcesCg,
+}
+
+const SRGB_TO_XYZ: Matrix3 = Matrix3::new([
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175],
+    [0.019_333_9, 0.119_192, 0.950_304_1],
+]);
+const XYZ_TO_SRGB: Matrix3 = Matrix3::new([
+    [3.240_455, -1.537_138_9, -0.498_531_5],
+    [-0.969_266_4, 1.876_010_9, 0.041_556_1],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+]);
+const DISPLAY_P3_TO_XYZ: Matrix3 = Matrix3::new([
+    [0.486_570_9, 0.265_667_7, 0.198_217_3],
+    [0.228_974_6, 0.691_738_5, 0.079_286_9],
+    [0.0, 0.045_113_4, 1.043_944_4],
+]);
+const XYZ_TO_DISPLAY_P3: Matrix3 = Matrix3::new([
+    [2.493_497_3, -0.931_383_8, -0.402_710_9],
+    [-0.829_489_3, 1.762_664_2, 0.023_624_8],
+    [0.035_845_9, -0.076_172_4, 0.956_884_5],
+]);
+const REC2020_TO_XYZ: Matrix3 = Matrix3::new([
+    [0.636_958, 0.144_616_9, 0.168_881],
+    [0.262_700_2, 0.677_998_1, 0.059_301_7],
+    [0.0, 0.028_072_7, 1.060_985_1],
+]);
+const XYZ_TO_REC2020: Matrix3 = Matrix3::new([
+    [1.716_651_3, -0.355_670_8, -0.253_366_3],
+    [-0.666_684_3, 1.616_481_2, 0.015_768_6],
+    [0.017_639_9, -0.042_770_6, 0.942_103_1],
+]);
+const ACESCG_TO_XYZ: Matrix3 = Matrix3::new([
+    [0.652_237_5, 0.128_236_1, 0.169_982_2],
+    [0.267_672_2, 0.674_34, 0.057_987_8],
+    [-0.005_381_8, 0.001_369_1, 1.093_070_5],
+]);
+const XYZ_TO_ACESCG: Matrix3 = Matrix3::new([
+    [1.660_585_3, -0.315_295_6, -0.241_509_3],
+    [-0.659_926_1, 1.608_391_5, 0.017_298_6],
+    [0.009_002_6, -0.003_566_9, 0.913_643_3],
+]);
+
+impl ColorSpace {
+    pub const ALL: [ColorSpace; 4] = [ColorSpace::Srgb, ColorSpace::DisplayP3, ColorSpace::Rec2020, ColorSpace::AcesCg];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::DisplayP3 => "display-p3",
+            ColorSpace::Rec2020 => "rec2020",
+            ColorSpace::AcesCg => "acescg",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ColorSpace> {
+        ColorSpace::ALL.iter().cloned().find(|space| space.name() == name)
+    }
+
+    pub fn to_xyz(self, rgb: Vector) -> Vector {
+        match self {
+            ColorSpace::Srgb => SRGB_TO_XYZ,
+            ColorSpace::DisplayP3 => DISPLAY_P3_TO_XYZ,
+            ColorSpace::Rec2020 => REC2020_TO_XYZ,
+            ColorSpace::AcesCg => ACESCG_TO_XYZ,
+        }.apply(rgb)
+    }
+
+    pub fn from_xyz(self, xyz: Vector) -> Vector {
+        match self {
+            ColorSpace::Srgb => XYZ_TO_SRGB,
+            ColorSpace::DisplayP3 => XYZ_TO_DISPLAY_P3,
+            ColorSpace::Rec2020 => XYZ_TO_REC2020,
+            ColorSpace::AcesCg => XYZ_TO_ACESCG,
+        }.apply(xyz)
+    }
+
+    // same color expressed in another space
+    pub fn convert(self, rgb: Vector, to: ColorSpace) -> Vector {
+        if self == to {
+            rgb
+        } else {
+            to.from_xyz(self.to_xyz(rgb))
+        }
+    }
+}
+
+// encoding of linear values into what is written to 8 bit images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transfer {
+    // the piecewise sRGB curve, also used by Display P3
+    Srgb,
+    Gamma(f32),
+    Linear,
+}
+
+impl Transfer {
+    pub fn encode(self, c: f32) -> f32 {
+        match self {
+            Transfer::Srgb => {
+                if c <= 0.003_130_8 {
+                    12.92 * c
+                } else {
+                    1.055 * c.powf(1.0 / 2.4) - 0.055
+                }
+            },
+            Transfer::Gamma(gamma) => c.powf(1.0 / gamma),
+            Transfer::Linear => c,
+        }
+    }
+
+    pub fn decode(self, c: f32) -> f32 {
+        match self {
+            Transfer::Srgb => {
+                if c <= 0.040_45 {
+                    c / 12.92
+                } else {
+                    ((c + 0.055) / 1.055).powf(2.4)
+                }
+            },
+            Transfer::Gamma(gamma) => c.powf(gamma),
+            Transfer::Linear => c,
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::color::{Color, Rgba};
+use crate::math::Vector;
+use crate::film::Film;
+use crate::ray_image::RayImage;
+
+mod color_space;
+mod tone_map;
+mod bloom;
+mod vignette;
+mod chromatic_aberration;
+pub use self::color_space::*;
+pub use self::tone_map::*;
+pub use self::bloom::*;
+pub use self::vignette::*;
+pub use self::chromatic_aberration::*;
+
+// image space effect on the linear radiance of a film
+pub trait Effect: Debug + Send + Sync {
+    fn apply(&self, image: &RayImage<Vector>) -> RayImage<Vector>;
+}
+
+// how the color of partly covered pixels is stored next to their alpha
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    // multiplied by the alpha, ready to be composited
+    Premultiplied,
+    // as PNG readers expect it
+    Straight,
+}
+
+// turns the linear radiance of a film into a displayable image: the effects
+// in order, exposure, then tone mapping in Rec.709, then conversion to the
+// display primaries and encoding with the transfer function
+#[derive(Debug, Clone)]
+pub struct PostProcess {
+    pub effects: Vec<Arc<dyn Effect>>,
+    // in stops, each one doubling the radiance
+    pub exposure: f32,
+    pub tone_map: ToneMap,
+    // space the rgb values of the scene, and so of the film, are expressed in
+    pub working_space: ColorSpace,
+    pub display_space: ColorSpace,
+    pub transfer: Transfer,
+    pub alpha_mode: AlphaMode,
+}
+
+impl PostProcess {
+    pub fn new() -> PostProcess {
+        PostProcess::default()
+    }
+
+    pub fn with_effect<E: Effect + 'static>(mut self, effect: E) -> PostProcess {
+        self.effects.push(Arc::new(effect));
+        self
+    }
+
+    pub fn with_exposure(self, exposure: f32) -> PostProcess {
+        PostProcess { exposure, ..self }
+    }
+
+    pub fn with_tone_map(self, tone_map: ToneMap) -> PostProcess {
+        PostProcess { tone_map, ..self }
+    }
+
+    pub fn with_working_space(self, working_space: ColorSpace) -> PostProcess {
+        PostProcess { working_space, ..self }
+    }
+
+    pub fn with_display_space(self, display_space: ColorSpace) -> PostProcess {
+        PostProcess { display_space, ..self }
+    }
+
+    pub fn with_transfer(self, transfer: Transfer) -> PostProcess {
+        PostProcess { transfer, ..self }
+    }
+
+    pub fn with_alpha_mode(self, alpha_mode: AlphaMode) -> PostProcess {
+        PostProcess { alpha_mode, ..self }
+    }
+
+    // encoded display value of a linear radiance, each channel in [0, 1]
+    pub fn apply(&self, radiance: Vector) -> Vector {
+        let v = self.working_space.convert(radiance, ColorSpace::Srgb) * self.exposure.exp2();
+        let v = ColorSpace::Srgb.convert(self.tone_map.apply(v), self.display_space);
+        let encode = |c: f32| self.transfer.encode(c.clamp(0.0, 1.0));
+        Vector::new(encode(v.x), encode(v.y), encode(v.z))
+    }
+
+    pub fn develop(&self, film: &Film) -> RayImage {
+        self.effects(film).map(|radiance| Color::from_vector(self.apply(radiance)))
+    }
+
+    // the tone mapping goes on the color of the covered part of each pixel,
+    // which is then premultiplied again unless the alpha mode is straight,
+    // uncovered pixels being transparent black in both modes
+    pub fn develop_rgba(&self, film: &Film) -> RayImage<Rgba> {
+        let image = self.effects(film);
+        let (width, height) = image.get_dimensions();
+        RayImage::from_fn(width, height, |x, y| {
+            let alpha = film.get_alpha(x, y);
+            if alpha <= 0.0 {
+                return Rgba::new(0, 0, 0, 0);
+            }
+
+            let color = self.apply(image.get_pixel(x, y) / alpha);
+            let color = match self.alpha_mode {
+                AlphaMode::Premultiplied => color * alpha,
+                AlphaMode::Straight => color,
+            };
+            Rgba::from_color(Color::from_vector(color), (alpha * 255.0).round() as u8)
+        })
+    }
+
+    fn effects(&self, film: &Film) -> RayImage<Vector> {
+        let mut image = film.radiance();
+        for effect in &self.effects {
+            image = effect.apply(&image);
+        }
+        image
+    }
+}
+
+impl Default for PostProcess {
+    fn default() -> PostProcess {
+        PostProcess {
+            effects: Vec::new(),
+            exposure: 0.0,
+            tone_map: ToneMap::Clamp,
+            working_space: ColorSpace::Srgb,
+            display_space: ColorSpace::Srgb,
+            transfer: Transfer::Srgb,
+            alpha_mode: AlphaMode::Premultiplied,
+        }
+    }
+}
+
+mod utils {
+    use std::thread;
+
+    use crate::math::Vector;
+    use crate::ray_image::{Pixel, RayImage};
+
+    // `func` on every pixel with its coordinates, bands of the image going to
+    // different threads
+    pub fn for_each_pixel<P: Pixel, F: Fn(usize, usize, &mut P) + Sync>(image: &mut RayImage<P>, func: F) {
+        let (width, height) = image.get_dimensions();
+        let threads = thread::available_parallelism().map_or(4, |n| n.get());
+        let func = &func;
+        thread::scope(|scope| {
+            for mut band in image.tiles_mut(width, height.div_ceil(threads)) {
+                scope.spawn(move || {
+                    for (x, y, pixel) in band.pixels_mut() {
+                        func(x, y, pixel);
+                    }
+                });
+            }
+        });
+    }
+
+    // interpolated value at a continuous position, pixel centers being at
+    // integer coordinates and the edges extending outside
+    pub fn bilinear(image: &RayImage<Vector>, x: f32, y: f32) -> Vector {
+        let (width, height) = image.get_dimensions();
+        let values = image.pixels();
+        let x = x.clamp(0.0, (width - 1) as f32);
+        let y = y.clamp(0.0, (height - 1) as f32);
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+        let top = values[y0 * width + x0] * (1.0 - tx) + values[y0 * width + x1] * tx;
+        let bottom = values[y1 * width + x0] * (1.0 - tx) + values[y1 * width + x1] * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    // distance to the center of the image, one at the corners
+    pub fn radius(x: f32, y: f32, width: usize, height: usize) -> f32 {
+        let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+        let (dx, dy) = (x - half_width, y - half_height);
+        (dx * dx + dy * dy).sqrt() / (half_width * half_width + half_height * half_height).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::{Bounds, Features, FilmSample, FilmTile};
+    use crate::filter::FilterKind;
+
+    // grey of 0.5 covering a pixel, half of the next one and none of the last
+    fn partly_covered() -> Film {
+        let filter = FilterKind::Box.build();
+        let mut tile = FilmTile::new(Bounds::new(0, 0, 3, 1), false);
+        for &(x, alpha) in &[(0, 1.0), (1, 0.5), (2, 0.0)] {
+            let sample = FilmSample {
+                radiance: Vector::new(0.5, 0.5, 0.5) * alpha,
+                alpha,
+                direct: Vector::zero(),
+                features: Features::zero(),
+            };
+            tile.add_sample(x as f32 + 0.5, 0.5, &sample, filter.as_ref());
+        }
+        let mut film = Film::new(3, 1);
+        film.merge_tile(&tile);
+        film
+    }
+
+    #[test]
+    fn rgba_follows_the_alpha_mode() {
+        let film = partly_covered();
+        let post = PostProcess::new().with_transfer(Transfer::Linear);
+        let straight = post.clone().with_alpha_mode(AlphaMode::Straight).develop_rgba(&film);
+        let premultiplied = post.with_alpha_mode(AlphaMode::Premultiplied).develop_rgba(&film);
+
+        let grey = Color::from_vector(Vector::new(0.5, 0.5, 0.5));
+        let half_grey = Color::from_vector(Vector::new(0.25, 0.25, 0.25));
+        assert_eq!(straight.get_pixel(0, 0), Rgba::from_color(grey, 255));
+        assert_eq!(premultiplied.get_pixel(0, 0), Rgba::from_color(grey, 255));
+        // the color of the covered half is kept as is, or multiplied by its coverage
+        assert_eq!(straight.get_pixel(1, 0), Rgba::from_color(grey, 128));
+        assert_eq!(premultiplied.get_pixel(1, 0), Rgba::from_color(half_grey, 128));
+        assert_eq!(straight.get_pixel(2, 0), Rgba::new(0, 0, 0, 0));
+        assert_eq!(premultiplied.get_pixel(2, 0), Rgba::new(0, 0, 0, 0));
+    }
+}
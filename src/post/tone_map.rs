@@ -0,0 +1,178 @@
+use crate::math::{Matrix3, Vector};
+use crate::film::luminance;
+
+// operators bringing linear Rec.709 radiance into the [0, 1] range of a
+// display, their result being linear as well
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    // no compression, anything over one is clipped
+    Clamp,
+    // on the luminance, keeping the hue, `white` being the luminance mapped
+    // to one, none meaning that only infinity gets there
+    Reinhard { white: Option<f32> },
+    // fit of the ACES reference rendering and sRGB output transforms by Stephen Hill
+    Aces,
+    // Troy Sobotka's AgX base look, with the polynomial fit of its sigmoid
+    Agx,
+}
+
+const ACES_INPUT: Matrix3 = Matrix3::new([
+    [0.597_19, 0.354_58, 0.048_23],
+    [0.076, 0.908_34, 0.015_66],
+    [0.028_4, 0.133_83, 0.837_77],
+]);
+const ACES_OUTPUT: Matrix3 = Matrix3::new([
+    [1.604_75, -0.531_08, -0.073_67],
+    [-0.102_08, 1.108_13, -0.006_05],
+    [-0.003_27, -0.072_76, 1.076_02],
+]);
+
+const AGX_INSET: Matrix3 = Matrix3::new([
+    [0.842_479_06, 0.078_433_6, 0.079_223_745],
+    [0.042_328_242, 0.878_468_6, 0.079_166_13],
+    [0.042_375_655, 0.078_433_6, 0.879_143],
+]);
+const AGX_OUTSET: Matrix3 = Matrix3::new([
+    [1.196_879, -0.098_020_88, -0.099_029_74],
+    [-0.052_896_85, 1.151_903_1, -0.098_961_18],
+    [-0.052_971_635, -0.098_043_45, 1.151_073_7],
+]);
+// exposure range in stops around middle grey covered by the sigmoid
+const AGX_MIN_EV: f32 = -12.473_93;
+const AGX_MAX_EV: f32 = 4.026_069;
+
+impl ToneMap {
+    pub const NAMES: [&'static str; 4] = ["clamp", "reinhard", "aces", "agx"];
+
+    pub fn from_name(name: &str) -> Option<ToneMap> {
+        match name {
+            "clamp" => Some(ToneMap::Clamp),
+            "reinhard" => Some(ToneMap::Reinhard { white: None }),
+            "aces" => Some(ToneMap::Aces),
+            "agx" => Some(ToneMap::Agx),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, v: Vector) -> Vector {
+        let v = match self {
+            ToneMap::Clamp => v,
+            ToneMap::Reinhard { white } => reinhard(v, white),
+            ToneMap::Aces => aces(v),
+            ToneMap::Agx => agx(v),
+        };
+        let clamp = |c: f32| c.clamp(0.0, 1.0);
+        Vector::new(clamp(v.x), clamp(v.y), clamp(v.z))
+    }
+}
+
+fn reinhard(v: Vector, white: Option<f32>) -> Vector {
+    let l = luminance(v);
+    if l <= 0.0 {
+        return Vector::zero();
+    }
+
+    let mapped = match white {
+        Some(white) => l * (1.0 + l / (white * white)) / (1.0 + l),
+        None => l / (1.0 + l),
+    };
+    v * (mapped / l)
+}
+
+fn aces(v: Vector) -> Vector {
+    let fit = |c: f32| {
+        let a = c * (c + 0.024_578_6) - 0.000_090_537;
+        let b = c * (0.983_729 * c + 0.432_951) + 0.238_081;
+        a / b
+    };
+    let v = ACES_INPUT.apply(v);
+    ACES_OUTPUT.apply(Vector::new(fit(v.x), fit(v.y), fit(v.z)))
+}
+
+fn agx(v: Vector) -> Vector {
+    let curve = |c: f32| {
+        // log encoding, then the sigmoid giving gamma 2.2 encoded values
+        let c = (c.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV) - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV);
+        let c2 = c * c;
+        let c4 = c2 * c2;
+        15.5 * c4 * c2 - 40.14 * c4 * c + 31.96 * c4 - 6.868 * c2 * c + 0.429_8 * c2 + 0.119_1 * c - 0.002_32
+    };
+    let v = AGX_INSET.apply(v);
+    let v = AGX_OUTSET.apply(Vector::new(curve(v.x), curve(v.y), curve(v.z)));
+    let linear = |c: f32| c.max(0.0).powf(2.2);
+    Vector::new(linear(v.x), linear(v.y), linear(v.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [ToneMap; 5] = [
+        ToneMap::Clamp,
+        ToneMap::Reinhard { white: None },
+        ToneMap::Reinhard { white: Some(4.0) },
+        ToneMap::Aces,
+        ToneMap::Agx,
+    ];
+
+    fn grey(v: f32) -> Vector {
+        Vector::new(v, v, v)
+    }
+
+    #[test]
+    fn names_give_the_operators() {
+        for name in &ToneMap::NAMES {
+            assert!(ToneMap::from_name(name).is_some(), "{}", name);
+        }
+        assert_eq!(ToneMap::from_name("filmic"), None);
+    }
+
+    #[test]
+    fn black_stays_black_and_brightness_grows() {
+        for &tone_map in &ALL {
+            let black = tone_map.apply(Vector::zero());
+            assert!(luminance(black) < 1e-3, "{:?} maps black to {:?}", tone_map, black);
+            let mut previous = luminance(black);
+            for i in 1..=200 {
+                let mapped = tone_map.apply(grey(0.05 * i as f32));
+                let l = luminance(mapped);
+                assert!(l >= previous - 1e-6, "{:?} darkens at {}", tone_map, 0.05 * i as f32);
+                for c in &[mapped.x, mapped.y, mapped.z] {
+                    assert!((0.0..=1.0).contains(c), "{:?} gives {}", tone_map, c);
+                }
+                previous = l;
+            }
+        }
+    }
+
+    #[test]
+    fn clamp_only_clips() {
+        assert_eq!(ToneMap::Clamp.apply(Vector::new(0.25, 3.0, -1.0)), Vector::new(0.25, 1.0, 0.0));
+    }
+
+    #[test]
+    fn reinhard_maps_the_white_point_to_one_and_keeps_the_hue() {
+        let white = ToneMap::Reinhard { white: Some(4.0) };
+        assert!((luminance(white.apply(grey(4.0))) - 1.0).abs() < 1e-5);
+        assert!(luminance(white.apply(grey(2.0))) < 1.0);
+        // without a white point, only infinity gets to one
+        let unbounded = ToneMap::Reinhard { white: None };
+        assert!((luminance(unbounded.apply(grey(1.0))) - 0.5).abs() < 1e-5);
+        assert!(luminance(unbounded.apply(grey(1000.0))) < 1.0);
+
+        let color = Vector::new(0.6, 0.3, 0.15);
+        let mapped = unbounded.apply(color);
+        assert!((mapped.x / mapped.y - 2.0).abs() < 1e-4 && (mapped.y / mapped.z - 2.0).abs() < 1e-4, "{:?}", mapped);
+    }
+
+    #[test]
+    fn filmic_curves_roll_off_the_highlights() {
+        for &tone_map in &[ToneMap::Aces, ToneMap::Agx] {
+            // middle grey stays in the middle, and very bright light gets close to white
+            let middle = luminance(tone_map.apply(grey(0.18)));
+            assert!(middle > 0.05 && middle < 0.4, "{:?} maps middle grey to {}", tone_map, middle);
+            let bright = luminance(tone_map.apply(grey(100.0)));
+            assert!(bright > 0.8, "{:?} maps 100 to {}", tone_map, bright);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::math::Vector;
+use crate::ray_image::RayImage;
+use super::{utils, Effect};
+
+// natural darkening towards the edges, following the cosine to the fourth law
+// for a lens whose corners are seen at an angle of tangent `strength`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vignette {
+    pub strength: f32,
+}
+
+impl Vignette {
+    pub fn new(strength: f32) -> Vignette {
+        Vignette { strength }
+    }
+}
+
+impl Effect for Vignette {
+    fn apply(&self, image: &RayImage<Vector>) -> RayImage<Vector> {
+        let (width, height) = image.get_dimensions();
+        let mut result = image.clone();
+        utils::for_each_pixel(&mut result, |x, y, pixel| {
+            // one at the corners
+            let r = utils::radius(x as f32 + 0.5, y as f32 + 0.5, width, height);
+            let tangent = self.strength * r;
+            *pixel = *pixel / ((1.0 + tangent * tangent) * (1.0 + tangent * tangent));
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_follow_the_cosine_to_the_fourth() {
+        let (width, height) = (40, 30);
+        let image = RayImage::from_fn(width, height, |_, _| Vector::new(1.0, 0.5, 0.25));
+        let result = Vignette::new(0.75).apply(&image);
+
+        // the center is left as is, the corner pixels nearly reaching the corner angle
+        let center = result.get_pixel(width / 2, height / 2);
+        assert!((center.x - 1.0).abs() < 1e-3, "center {}", center.x);
+        let cos4 = 1.0 / ((1.0 + 0.75f32 * 0.75) * (1.0 + 0.75 * 0.75));
+        for &(x, y) in &[(0, 0), (width - 1, 0), (0, height - 1), (width - 1, height - 1)] {
+            let corner = result.get_pixel(x, y);
+            assert!((corner.x - cos4).abs() < 0.02, "corner {} against {}", corner.x, cos4);
+            assert!((corner.y - 0.5 * corner.x).abs() < 1e-6 && (corner.z - 0.25 * corner.x).abs() < 1e-6);
+        }
+        // darker the further from the center
+        for x in width / 2..width - 1 {
+            assert!(result.get_pixel(x + 1, height / 2).x < result.get_pixel(x, height / 2).x);
+        }
+    }
+
+    #[test]
+    fn no_strength_changes_nothing() {
+        let image = RayImage::from_fn(8, 6, |x, y| Vector::new(x as f32, y as f32, 1.0));
+        assert!(Vignette::new(0.0).apply(&image).pixels() == image.pixels());
+    }
+}
@@ -0,0 +1,298 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use raytracer::camera::Camera;
+use raytracer::math::{Matrix3, Point, Vector};
+use raytracer::ray_image::RayImage;
+
+// page of the preview: the image reloads itself as it refines, dragging
+// orbits around the target, shift or right dragging pans, the wheel zooms
+const PAGE: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>rt_driver preview</title>
+<style>
+body { background: #222; color: #ddd; font-family: monospace; margin: 1em; }
+img { max-width: 100%; cursor: grab; user-select: none; }
+button { margin-right: 0.5em; }
+</style>
+</head>
+<body>
+<img id="image" src="/image.png" draggable="false">
+<p id="status"></p>
+<p>
+<button onclick="send('/zoom?f=0.8')">zoom in</button>
+<button onclick="send('/zoom?f=1.25')">zoom out</button>
+<button onclick="send('/reset')">reset</button>
+drag to orbit, shift drag or right drag to pan, wheel or +/- to zoom, arrows to orbit
+</p>
+<script>
+const image = document.getElementById('image');
+const status = document.getElementById('status');
+function send(path) { fetch(path, { method: 'POST' }); }
+function refresh() {
+    fetch('/status').then(r => r.text()).then(t => status.textContent = t);
+    const next = new Image();
+    next.onload = () => { image.src = next.src; setTimeout(refresh, 200); };
+    next.onerror = () => setTimeout(refresh, 1000);
+    next.src = '/image.png?' + Date.now();
+}
+let drag = null;
+image.addEventListener('contextmenu', e => e.preventDefault());
+image.addEventListener('mousedown', e => drag = { x: e.clientX, y: e.clientY, pan: e.shiftKey || e.button == 2 });
+window.addEventListener('mouseup', e => {
+    if (!drag) return;
+    const dx = (e.clientX - drag.x) / image.clientHeight, dy = (e.clientY - drag.y) / image.clientHeight;
+    if (dx != 0 || dy != 0) send((drag.pan ? '/pan' : '/orbit') + '?x=' + dx + '&y=' + dy);
+    drag = null;
+});
+image.addEventListener('wheel', e => { e.preventDefault(); send('/zoom?f=' + (e.deltaY > 0 ? 1.25 : 0.8)); });
+window.addEventListener('keydown', e => {
+    const moves = { ArrowLeft: [-0.1, 0], ArrowRight: [0.1, 0], ArrowUp: [0, -0.1], ArrowDown: [0, 0.1] };
+    if (e.key in moves) send((e.shiftKey ? '/pan' : '/orbit') + '?x=' + moves[e.key][0] + '&y=' + moves[e.key][1]);
+    if (e.key == '+') send('/zoom?f=0.8');
+    if (e.key == '-') send('/zoom?f=1.25');
+});
+refresh();
+</script>
+</body>
+</html>
+"#;
+
+// in scene units
+const MIN_DISTANCE: f32 = 1e-3;
+
+// placement of the camera, moved by the controls of the page
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    pub lookfrom: Point,
+    pub lookat: Point,
+    pub vup: Vector,
+    pub vfov: f32,
+    pub aperture: f32,
+}
+
+impl View {
+    // focused on the target
+    pub fn camera(&self, aspect: f32) -> Camera {
+        let focus_dist = (self.lookfrom - self.lookat).norm();
+        Camera::new(self.lookfrom, self.lookat, self.vup, self.vfov, aspect, self.aperture, focus_dist)
+    }
+
+    // a drag across the whole height of the image turns by 180 degrees, the
+    // camera stopping short of the poles
+    fn orbit(&mut self, x: f32, y: f32) {
+        let up = self.vup.normalized();
+        let offset = Matrix3::rotation(up, -x * 180.0).apply(self.lookfrom - self.lookat);
+        let right = offset.cross(up).normalized();
+        let elevation = offset.normalized().dot(up).asin().to_degrees();
+        let pitch = (elevation + y * 180.0).clamp(-85.0, 85.0) - elevation;
+        self.lookfrom = self.lookat + Matrix3::rotation(right, pitch).apply(offset);
+    }
+
+    // the scene follows the drag at the distance of the target
+    fn pan(&mut self, x: f32, y: f32) {
+        let offset = self.lookfrom - self.lookat;
+        let height = 2.0 * (self.vfov.to_radians() / 2.0).tan() * offset.norm();
+        let right = offset.cross(self.vup).normalized();
+        let up = right.cross(offset).normalized();
+        let shift = right * (x * height) + up * (y * height);
+        self.lookfrom = self.lookfrom + shift;
+        self.lookat = self.lookat + shift;
+    }
+
+    // scales the distance to the target, never getting closer than
+    // MIN_DISTANCE for the camera to keep a direction
+    fn zoom(&mut self, factor: f32) {
+        let offset = self.lookfrom - self.lookat;
+        let distance = (offset.norm() * factor).max(MIN_DISTANCE);
+        self.lookfrom = self.lookat + offset.normalized() * distance;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Control {
+    Orbit(f32, f32),
+    Pan(f32, f32),
+    Zoom(f32),
+    Reset,
+}
+
+// what the page gets and what it asks for
+#[derive(Debug, Default)]
+struct Shared {
+    png: Vec<u8>,
+    status: String,
+    controls: Vec<Control>,
+}
+
+type SharedState = Arc<(Mutex<Shared>, Condvar)>;
+
+// local http server showing the image being rendered, each request having
+// its own thread, the render loop picking up the controls between passes
+pub struct Preview {
+    shared: SharedState,
+    initial: View,
+}
+
+impl Preview {
+    pub fn serve<A: ToSocketAddrs>(address: A, initial: View) -> io::Result<Preview> {
+        let listener = TcpListener::bind(address)?;
+        let shared = SharedState::default();
+
+        let served = shared.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = served.clone();
+                thread::spawn(move || {
+                    if let Err(err) = respond(stream, &shared) {
+                        eprintln!("Error answering the preview page: {}", err);
+                    }
+                });
+            }
+        });
+        Ok(Preview { shared, initial })
+    }
+
+    // applies the controls sent since the last call to `view`, false when
+    // there were none, waiting for one first when `wait` is set
+    pub fn update(&self, view: &mut View, wait: bool) -> bool {
+        let (shared, changed) = &*self.shared;
+        let mut shared = shared.lock().unwrap();
+        while wait && shared.controls.is_empty() {
+            shared = changed.wait(shared).unwrap();
+        }
+        if shared.controls.is_empty() {
+            return false;
+        }
+
+        for control in shared.controls.drain(..) {
+            match control {
+                Control::Orbit(x, y) => view.orbit(x, y),
+                Control::Pan(x, y) => view.pan(x, y),
+                Control::Zoom(factor) => view.zoom(factor),
+                Control::Reset => *view = self.initial,
+            }
+        }
+        true
+    }
+
+    pub fn publish(&self, image: &RayImage, status: String) -> io::Result<()> {
+        let (width, height) = image.get_dimensions();
+        let mut data = Vec::with_capacity(width * height * 3);
+        for pixel in image.pixels() {
+            data.extend_from_slice(&[pixel.red, pixel.green, pixel.blue]);
+        }
+        let mut png = Vec::new();
+        image::png::PNGEncoder::new(&mut png).encode(&data, width as u32, height as u32, image::ColorType::RGB(8))?;
+
+        let mut shared = self.shared.0.lock().unwrap();
+        shared.png = png;
+        shared.status = status;
+        Ok(())
+    }
+}
+
+fn respond(stream: TcpStream, shared: &SharedState) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // the headers don't matter
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let method = request.split_whitespace().next().unwrap_or("");
+    let target = request.split_whitespace().nth(1).unwrap_or("/");
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (target, ""),
+    };
+    let param = |name: &str| -> f32 {
+        query.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|&(key, _)| key == name)
+            .and_then(|(_, value)| value.parse().ok())
+            .filter(|value: &f32| value.is_finite())
+            .unwrap_or(0.0)
+    };
+    let control = match path {
+        "/orbit" => Some(Control::Orbit(param("x"), param("y"))),
+        "/pan" => Some(Control::Pan(param("x"), param("y"))),
+        "/zoom" if param("f") > 0.0 => Some(Control::Zoom(param("f"))),
+        "/reset" => Some(Control::Reset),
+        _ => None,
+    };
+
+    let mut stream = stream;
+    // controls change the view, a plain link or a prefetch mustn't
+    if control.is_some() && method != "POST" {
+        return write_response(&mut stream, "405 Method Not Allowed", "text/plain", b"controls only accept POST");
+    }
+    if let Some(control) = control {
+        let (shared, changed) = &**shared;
+        shared.lock().unwrap().controls.push(control);
+        changed.notify_all();
+        return write_response(&mut stream, "204 No Content", "text/plain", &[]);
+    }
+    match path {
+        "/" => write_response(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE.as_bytes()),
+        "/image.png" => {
+            let png = shared.0.lock().unwrap().png.clone();
+            if png.is_empty() {
+                write_response(&mut stream, "503 Service Unavailable", "text/plain", b"no image yet")
+            } else {
+                write_response(&mut stream, "200 OK", "image/png", &png)
+            }
+        },
+        "/status" => {
+            let status = shared.0.lock().unwrap().status.clone();
+            write_response(&mut stream, "200 OK", "text/plain; charset=utf-8", status.as_bytes())
+        },
+        _ => write_response(&mut stream, "404 Not Found", "text/plain", b"not found"),
+    }
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len(),
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_stops_short_of_the_target() {
+        let mut view = View {
+            lookfrom: Point::new(0.0, 0.0, 2.0),
+            lookat: Point::new(0.0, 0.0, 0.0),
+            vup: Vector::new(0.0, 1.0, 0.0),
+            vfov: 40.0,
+            aperture: 0.0,
+        };
+        for _ in 0..1000 {
+            view.zoom(0.5);
+        }
+        let distance = (view.lookfrom - view.lookat).norm();
+        assert!((distance - MIN_DISTANCE).abs() < 1e-6, "{}", distance);
+
+        view.zoom(4.0);
+        assert!(((view.lookfrom - view.lookat).norm() - 4.0 * MIN_DISTANCE).abs() < 1e-6);
+        let ray = view.camera(16.0 / 9.0).get_ray(0.5, 0.5);
+        assert!(ray.direction.x.is_finite() && ray.direction.z < 0.0);
+    }
+}
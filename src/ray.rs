@@ -4,6 +4,8 @@ use crate::math::*;
 pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
+    // in nanometers, only set when rendering spectrally
+    pub wavelength: Option<f32>,
 }
 
 impl Ray {
@@ -11,10 +13,15 @@ impl Ray {
         Ray {
             origin,
             direction,
+            wavelength: None,
         }
     }
 
+    pub fn with_wavelength(self, wavelength: Option<f32>) -> Ray {
+        Ray { wavelength, ..self }
+    }
+
     pub fn point_at(&self, t: f32) -> Point {
         self.origin + self.direction * t
     }
-}
\ No newline at end of file
+}
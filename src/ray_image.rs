@@ -1,94 +1,327 @@
 use std::fmt;
-use std::marker::PhantomData;
 
 use crate::color::*;
+use crate::math::Vector;
 
+// what an image can hold: 8 bit rgb or rgba, or floats for linear values
+pub trait Pixel: Copy + Send + Sync {
+    fn black() -> Self;
+}
+
+impl Pixel for Color {
+    fn black() -> Color {
+        Color::new(0, 0, 0)
+    }
+}
+
+impl Pixel for Rgba {
+    fn black() -> Rgba {
+        Rgba::new(0, 0, 0, 0)
+    }
+}
+
+impl Pixel for f32 {
+    fn black() -> f32 {
+        0.0
+    }
+}
+
+impl Pixel for Vector {
+    fn black() -> Vector {
+        Vector::zero()
+    }
+}
+
+// pixels row by row, y going down
 #[derive(Debug, Clone)]
-pub struct RayImage {
+pub struct RayImage<P: Pixel = Color> {
     width: usize,
     height: usize,
-    pixels: Vec<Color>
+    pixels: Vec<P>
 }
 
-impl RayImage {
-    pub fn new(width: usize, height: usize) -> RayImage {
+impl<P: Pixel> RayImage<P> {
+    pub fn new(width: usize, height: usize) -> RayImage<P> {
         RayImage {
             width,
             height,
-            pixels: vec![Color::new(0, 0, 0); width * height]
+            pixels: vec![P::black(); width * height]
         }
     }
 
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<P>) -> RayImage<P> {
+        assert_eq!(pixels.len(), width * height);
+        RayImage { width, height, pixels }
+    }
+
+    pub fn from_fn<F: Fn(usize, usize) -> P>(width: usize, height: usize, func: F) -> RayImage<P> {
+        let pixels = (0..width * height).map(|i| func(i % width, i / width)).collect();
+        RayImage { width, height, pixels }
+    }
+
     fn assert_coord_in_range(&self, x: usize, y: usize) {
         assert!(x < self.width);
         assert!(y < self.height);
     }
 
-    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+    pub fn get_pixel(&self, x: usize, y: usize) -> P {
         self.assert_coord_in_range(x, y);
         self.pixels[y * self.width + x]
     }
 
-    // pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
-    //     self.assert_coord_in_range(x, y);
-    //     self.pixels[y * self.width + x] = color;
-    // }
-
-    pub fn pixel_mut_iter<'a>(&'a mut self) -> RayImagePixelIter<'a> {
-        RayImagePixelIter {
-            image_start: self.pixels.as_mut_ptr(),
-            current: 0,
-            width: self.width,
-            height: self.height,
-            _marker: PhantomData,
-        }
+    pub fn set_pixel(&mut self, x: usize, y: usize, pixel: P) {
+        self.assert_coord_in_range(x, y);
+        self.pixels[y * self.width + x] = pixel;
     }
 
     pub fn get_dimensions(&self) -> (usize, usize) {
         (self.width, self.height)
     }
+
+    pub fn pixels(&self) -> &[P] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut P)> {
+        let width = self.width;
+        self.pixels.iter_mut().enumerate().map(move |(i, pixel)| (i % width, i / width, pixel))
+    }
+
+    // disjoint tiles covering the image, the ones on the right and bottom
+    // edges being smaller, which can be handed to different threads, a size
+    // of zero being taken as one
+    pub fn tiles_mut(&mut self, tile_width: usize, tile_height: usize) -> Vec<TileMut<'_, P>> {
+        if self.width == 0 {
+            return Vec::new();
+        }
+        let (tile_width, tile_height) = (tile_width.max(1), tile_height.max(1));
+        let tiles_x = self.width.div_ceil(tile_width);
+        let mut tiles: Vec<TileMut<P>> = Vec::new();
+        for (y, row) in self.pixels.chunks_mut(self.width).enumerate() {
+            if y % tile_height == 0 {
+                tiles.extend((0..tiles_x).map(|tx| TileMut { x0: tx * tile_width, y0: y, rows: Vec::new() }));
+            }
+            let band = tiles.len() - tiles_x;
+            for (tile, part) in tiles[band..].iter_mut().zip(row.chunks_mut(tile_width)) {
+                tile.rows.push(part);
+            }
+        }
+        tiles
+    }
+
+    pub fn view(&self, x0: usize, y0: usize, width: usize, height: usize) -> ImageView<'_, P> {
+        assert!(x0 + width <= self.width && y0 + height <= self.height);
+        ImageView { image: self, x0, y0, width, height }
+    }
+
+    pub fn crop(&self, x0: usize, y0: usize, width: usize, height: usize) -> RayImage<P> {
+        self.view(x0, y0, width, height).to_image()
+    }
+
+    pub fn map<Q: Pixel, F: Fn(P) -> Q>(&self, func: F) -> RayImage<Q> {
+        RayImage::from_pixels(self.width, self.height, self.pixels.iter().map(|&p| func(p)).collect())
+    }
+
+    pub fn side_by_side(left: &RayImage<P>, right: &RayImage<P>) -> RayImage<P> {
+        assert_eq!(left.height, right.height);
+        let width = left.width + right.width;
+        let mut pixels = Vec::with_capacity(width * left.height);
+        for y in 0..left.height {
+            pixels.extend_from_slice(&left.pixels[y * left.width..(y + 1) * left.width]);
+            pixels.extend_from_slice(&right.pixels[y * right.width..(y + 1) * right.width]);
+        }
+
+        RayImage {
+            width,
+            height: left.height,
+            pixels,
+        }
+    }
+
+    pub fn top_bottom(top: &RayImage<P>, bottom: &RayImage<P>) -> RayImage<P> {
+        assert_eq!(top.width, bottom.width);
+        let mut pixels = top.pixels.clone();
+        pixels.extend_from_slice(&bottom.pixels);
+
+        RayImage {
+            width: top.width,
+            height: top.height + bottom.height,
+            pixels,
+        }
+    }
 }
 
-impl fmt::Display for RayImage {
+impl fmt::Display for RayImage<Color> {
     // we display the ppm version
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "P3\n{} {}\n255\n", self.width, self.height)?;
         for y in 0..self.height {
             for x in 0..self.width {
                 let color = self.pixels[y * self.width + x];
-                write!(f, "{} {} {}\n", color.red, color.green, color.blue)?;
+                writeln!(f, "{} {} {}", color.red, color.green, color.blue)?;
             }
         }
         Ok(())
     }
 }
 
-pub struct RayImagePixelIter<'a> {
-    image_start: *mut Color,
-    current: isize,
+// mutable rectangle of an image, coordinates being those of the whole image
+pub struct TileMut<'a, P: Pixel> {
+    x0: usize,
+    y0: usize,
+    rows: Vec<&'a mut [P]>,
+}
+
+impl<'a, P: Pixel> TileMut<'a, P> {
+    pub fn origin(&self) -> (usize, usize) {
+        (self.x0, self.y0)
+    }
+
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.rows.first().map_or(0, |row| row.len()), self.rows.len())
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> P {
+        self.rows[y - self.y0][x - self.x0]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, pixel: P) {
+        self.rows[y - self.y0][x - self.x0] = pixel;
+    }
+
+    pub fn pixels_mut(&mut self) -> Box<dyn Iterator<Item = (usize, usize, &mut P)> + '_> {
+        let (x0, y0) = (self.x0, self.y0);
+        Box::new(self.rows.iter_mut().enumerate().flat_map(move |(dy, row)| {
+            row.iter_mut().enumerate().map(move |(dx, pixel)| (x0 + dx, y0 + dy, pixel))
+        }))
+    }
+}
+
+// read only rectangle of an image, with its own coordinates
+#[derive(Debug, Clone, Copy)]
+pub struct ImageView<'a, P: Pixel> {
+    image: &'a RayImage<P>,
+    x0: usize,
+    y0: usize,
     width: usize,
     height: usize,
-    _marker: PhantomData<&'a ()>
 }
 
-impl<'a> Iterator for RayImagePixelIter<'a> {
-    type Item = (usize, usize, &'a mut Color);
+impl<'a, P: Pixel> ImageView<'a, P> {
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> P {
+        assert!(x < self.width && y < self.height);
+        self.image.get_pixel(self.x0 + x, self.y0 + y)
+    }
+
+    pub fn row(&self, y: usize) -> &'a [P] {
+        assert!(y < self.height);
+        let start = (self.y0 + y) * self.image.width + self.x0;
+        &self.image.pixels[start..start + self.width]
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let current_u = self.current as usize;
-        if current_u >= self.width * self.height {
-            return None;
+    pub fn to_image(&self) -> RayImage<P> {
+        let mut pixels = Vec::with_capacity(self.width * self.height);
+        for y in 0..self.height {
+            pixels.extend_from_slice(self.row(y));
         }
+        RayImage::from_pixels(self.width, self.height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let y = current_u / self.width;
-        let x = current_u % self.width;
-        
-        let pixel_ref = unsafe {
-            let pixel_ref: &'a mut Color = &mut *self.image_start.offset(self.current);
-            self.current += 1;
-            pixel_ref
-        };
+    fn numbered(width: usize, height: usize) -> RayImage<f32> {
+        RayImage::from_fn(width, height, |x, y| (y * width + x) as f32)
+    }
+
+    #[test]
+    fn tiles_cover_the_image_once() {
+        let mut image = numbered(10, 7);
+        let mut covered = vec![0; 10 * 7];
+        let tiles = image.tiles_mut(4, 3);
+        assert_eq!(tiles.len(), 3 * 3);
+        for tile in &tiles {
+            let (x0, y0) = tile.origin();
+            let (width, height) = tile.get_dimensions();
+            assert_eq!((width, height), ((10 - x0).min(4), (7 - y0).min(3)));
+            for y in y0..y0 + height {
+                for x in x0..x0 + width {
+                    assert_eq!(tile.get_pixel(x, y), (y * 10 + x) as f32);
+                    covered[y * 10 + x] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&count| count == 1));
+    }
 
-        Some((x, y, pixel_ref))
+    #[test]
+    fn tiles_write_to_the_image() {
+        let mut image = numbered(9, 5);
+        std::thread::scope(|scope| {
+            for mut tile in image.tiles_mut(2, 2) {
+                scope.spawn(move || {
+                    for (x, y, pixel) in tile.pixels_mut() {
+                        *pixel = -(y as f32 * 9.0 + x as f32);
+                    }
+                    let (x0, y0) = tile.origin();
+                    tile.set_pixel(x0, y0, 100.0);
+                });
+            }
+        });
+        for y in 0..5 {
+            for x in 0..9 {
+                let expected = if x % 2 == 0 && y % 2 == 0 { 100.0 } else { -((y * 9 + x) as f32) };
+                assert_eq!(image.get_pixel(x, y), expected);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn empty_image_or_tile_size_does_not_panic() {
+        assert!(RayImage::<f32>::new(0, 4).tiles_mut(2, 2).is_empty());
+        assert!(RayImage::<f32>::new(4, 0).tiles_mut(2, 2).is_empty());
+        let mut image = numbered(3, 2);
+        let tiles = image.tiles_mut(0, 0);
+        assert_eq!(tiles.len(), 6);
+        assert!(tiles.iter().all(|tile| tile.get_dimensions() == (1, 1)));
+    }
+
+    #[test]
+    fn view_and_crop_see_the_rectangle() {
+        let image = numbered(6, 5);
+        let view = image.view(2, 1, 3, 4);
+        assert_eq!(view.get_dimensions(), (3, 4));
+        assert_eq!(view.get_pixel(0, 0), 8.0);
+        assert_eq!(view.get_pixel(2, 3), 28.0);
+        assert_eq!(view.row(1), &[14.0, 15.0, 16.0][..]);
+
+        let crop = image.crop(2, 1, 3, 4);
+        assert_eq!(crop.get_dimensions(), (3, 4));
+        for y in 0..4 {
+            for x in 0..3 {
+                assert_eq!(crop.get_pixel(x, y), image.get_pixel(x + 2, y + 1));
+            }
+        }
+        // the whole image, or nothing of it
+        assert_eq!(image.crop(0, 0, 6, 5).pixels(), image.pixels());
+        assert_eq!(image.crop(6, 5, 0, 0).get_dimensions(), (0, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn view_past_the_edge_panics() {
+        numbered(6, 5).view(4, 0, 3, 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn view_pixel_outside_of_it_panics() {
+        numbered(6, 5).view(1, 1, 2, 2).get_pixel(2, 0);
+    }
+}
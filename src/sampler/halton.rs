@@ -0,0 +1,50 @@
+use super::*;
+
+const PRIMES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+// Halton sequence in each pixel, with a different Owen scrambling per pixel
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    pub seed: u32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u32) -> HaltonSampler {
+        HaltonSampler { seed }
+    }
+}
+
+// radical inverse where each digit is permuted depending on the digits that
+// precede it, which keeps the stratification of the sequence
+fn owen_scrambled_radical_inverse(base: u32, mut index: u64, seed: u64) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut reversed_digits: u64 = 0;
+    // past this many digits the contributions are below f32 precision
+    let digits = (32.0 / (base as f32).log2()).ceil() as usize;
+
+    for _ in 0..digits {
+        let next = index / u64::from(base);
+        let digit = (index - next * u64::from(base)) as u32;
+        let digit_hash = utils::mix_bits(seed ^ reversed_digits) as u32;
+        let digit = utils::permutation_element(digit, base, digit_hash);
+        reversed_digits = reversed_digits * u64::from(base) + u64::from(digit);
+        inv_base_m *= inv_base;
+        index = next;
+    }
+
+    ((reversed_digits as f64 * inv_base_m) as f32).min(ONE_MINUS_EPSILON)
+}
+
+impl Sampler for HaltonSampler {
+    fn get_2d(&self, at: SampleIndex, dimension: usize) -> (f32, f32) {
+        // dimensions past the table reuse the primes with another scrambling
+        let pair = dimension % (PRIMES.len() / 2);
+        let seed = utils::pixel_hash(at, dimension, self.seed);
+        let index = at.index as u64;
+        (
+            owen_scrambled_radical_inverse(PRIMES[2 * pair], index, seed),
+            owen_scrambled_radical_inverse(PRIMES[2 * pair + 1], index, utils::mix_bits(seed)),
+        )
+    }
+}
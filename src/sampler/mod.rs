@@ -0,0 +1,216 @@
+use rand::SeedableRng;
+use rand::prng::XorShiftRng;
+
+mod random;
+mod stratified;
+mod halton;
+mod sobol;
+pub use self::random::*;
+pub use self::stratified::*;
+pub use self::halton::*;
+pub use self::sobol::*;
+
+// which sample is being taken: the pixel and the index of the sample in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleIndex {
+    pub x: usize,
+    pub y: usize,
+    pub index: usize,
+}
+
+// samplers are stateless, the same index and dimension always give back the
+// same point, which keeps them usable from many threads at once
+pub trait Sampler: Send + Sync {
+    // 2D point in [0, 1)^2, dimension 0 is used for the position in the pixel
+    fn get_2d(&self, at: SampleIndex, dimension: usize) -> (f32, f32);
+}
+
+// the samplers, to pick one by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplerKind {
+    Random,
+    Stratified,
+    LatinHypercube,
+    Halton,
+    #[default]
+    Sobol,
+}
+
+impl SamplerKind {
+    pub const ALL: [SamplerKind; 5] = [
+        SamplerKind::Random,
+        SamplerKind::Stratified,
+        SamplerKind::LatinHypercube,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SamplerKind::Random => "random",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::LatinHypercube => "latin-hypercube",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SamplerKind> {
+        SamplerKind::ALL.iter().cloned().find(|kind| kind.name() == name)
+    }
+
+    // the stratified samplers spread `samples_per_pixel` samples, the others
+    // don't need to know
+    pub fn build(self, samples_per_pixel: usize, seed: u32) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Random => Box::new(RandomSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerKind::LatinHypercube => Box::new(LatinHypercubeSampler::new(samples_per_pixel, seed)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+// generator for the random choices the samplers don't drive, like picking a
+// lobe or a point on the lens, seeded from the sample so renders repeat
+pub fn sample_rng(at: SampleIndex, seed: u32) -> XorShiftRng {
+    let h = utils::hash(at, usize::MAX, seed);
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&h.to_le_bytes());
+    bytes[8..].copy_from_slice(&utils::mix_bits(h).to_le_bytes());
+    XorShiftRng::from_seed(bytes)
+}
+
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+pub(crate) mod utils {
+    use super::*;
+
+    pub fn mix_bits(mut v: u64) -> u64 {
+        v ^= v >> 31;
+        v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+        v ^= v >> 27;
+        v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+        v ^= v >> 33;
+        v
+    }
+
+    pub fn hash(at: SampleIndex, dimension: usize, seed: u32) -> u64 {
+        let mut h = mix_bits(u64::from(seed) ^ 0x9e37_79b9_7f4a_7c15);
+        for &v in &[at.x as u64, at.y as u64, at.index as u64, dimension as u64] {
+            h = mix_bits(h ^ v.wrapping_add(0x9e37_79b9_7f4a_7c15).wrapping_add(h << 6));
+        }
+        h
+    }
+
+    // hash of the pixel and dimension only, shared by all the samples of a pixel
+    pub fn pixel_hash(at: SampleIndex, dimension: usize, seed: u32) -> u64 {
+        hash(SampleIndex { index: 0, ..at }, dimension, seed)
+    }
+
+    pub fn to_unit_float(bits: u32) -> f32 {
+        (bits as f32 * (1.0 / 4_294_967_296.0)).min(ONE_MINUS_EPSILON)
+    }
+
+    // element i of a pseudo random permutation of 0..l (Kensler 2013)
+    pub fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+        let mut w = l - 1;
+        w |= w >> 1;
+        w |= w >> 2;
+        w |= w >> 4;
+        w |= w >> 8;
+        w |= w >> 16;
+        loop {
+            i ^= p;
+            i = i.wrapping_mul(0xe170_893d);
+            i ^= p >> 16;
+            i ^= (i & w) >> 4;
+            i ^= p >> 8;
+            i = i.wrapping_mul(0x0929_eb3f);
+            i ^= p >> 23;
+            i ^= (i & w) >> 1;
+            i = i.wrapping_mul(1 | p >> 27);
+            i = i.wrapping_mul(0x6935_fa69);
+            i ^= (i & w) >> 11;
+            i = i.wrapping_mul(0x74dc_b303);
+            i ^= (i & w) >> 2;
+            i = i.wrapping_mul(0x9e50_1cc3);
+            i ^= (i & w) >> 2;
+            i = i.wrapping_mul(0xc860_a3df);
+            i &= w;
+            i ^= i >> 5;
+            if i < l {
+                break;
+            }
+        }
+        (i.wrapping_add(p)) % l
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u32 = 3;
+
+    fn at(x: usize, y: usize, index: usize) -> SampleIndex {
+        SampleIndex { x, y, index }
+    }
+
+    // cell of a point of [0, 1) cut in n parts
+    fn cell(v: f32, n: usize) -> usize {
+        (v * n as f32) as usize
+    }
+
+    #[test]
+    fn stratified_puts_one_sample_per_stratum() {
+        let sampler = StratifiedSampler::new(16, SEED);
+        for &(x, y, dimension) in &[(0, 0, 0), (5, 9, 0), (5, 9, 3)] {
+            let mut strata = [0; 16];
+            for index in 0..16 {
+                let (u, v) = sampler.get_2d(at(x, y, index), dimension);
+                strata[cell(v, 4) * 4 + cell(u, 4)] += 1;
+            }
+            assert_eq!(strata, [1; 16], "pixel ({}, {}), dimension {}", x, y, dimension);
+        }
+    }
+
+    #[test]
+    fn latin_hypercube_puts_one_sample_per_row_and_column() {
+        let n = 10;
+        let sampler = LatinHypercubeSampler::new(n, SEED);
+        for &(x, y, dimension) in &[(0, 0, 0), (7, 2, 0), (7, 2, 5)] {
+            let (mut columns, mut rows) = (vec![0; n], vec![0; n]);
+            for index in 0..n {
+                let (u, v) = sampler.get_2d(at(x, y, index), dimension);
+                columns[cell(u, n)] += 1;
+                rows[cell(v, n)] += 1;
+            }
+            assert_eq!(columns, vec![1; n]);
+            assert_eq!(rows, vec![1; n]);
+        }
+    }
+
+    #[test]
+    fn samplers_stay_in_the_unit_square_and_repeat() {
+        for &kind in SamplerKind::ALL.iter() {
+            let (sampler, again) = (kind.build(16, SEED), kind.build(16, SEED));
+            for index in 0..256 {
+                for dimension in 0..4 {
+                    let at = at(index % 7, index / 7, index);
+                    let (u, v) = sampler.get_2d(at, dimension);
+                    assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v), "{} gave ({}, {})", kind.name(), u, v);
+                    assert_eq!((u, v), again.get_2d(at, dimension), "{} doesn't repeat", kind.name());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samplers_have_their_name() {
+        for &kind in SamplerKind::ALL.iter() {
+            assert_eq!(SamplerKind::from_name(kind.name()), Some(kind));
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use super::*;
+
+// independent uniform samples
+#[derive(Debug, Clone)]
+pub struct RandomSampler {
+    pub seed: u32,
+}
+
+impl RandomSampler {
+    pub fn new(seed: u32) -> RandomSampler {
+        RandomSampler { seed }
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn get_2d(&self, at: SampleIndex, dimension: usize) -> (f32, f32) {
+        let h = utils::hash(at, dimension, self.seed);
+        (utils::to_unit_float(h as u32), utils::to_unit_float((h >> 32) as u32))
+    }
+}
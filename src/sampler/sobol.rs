@@ -0,0 +1,54 @@
+use super::*;
+
+// first two dimensions of the Sobol sequence with hash based Owen scrambling
+// (Burley 2020), every dimension pair getting its own shuffle and scrambling
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    pub seed: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u32) -> SobolSampler {
+        SobolSampler { seed }
+    }
+}
+
+fn sobol_2d(index: u32) -> (u32, u32) {
+    let mut v = 1u32 << 31;
+    let mut i = index;
+    let mut y = 0;
+    while i != 0 {
+        if i & 1 != 0 {
+            y ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    (index.reverse_bits(), y)
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+impl Sampler for SobolSampler {
+    fn get_2d(&self, at: SampleIndex, dimension: usize) -> (f32, f32) {
+        let seed = utils::pixel_hash(at, dimension, self.seed);
+        let index = nested_uniform_scramble(at.index as u32, seed as u32);
+        let (x, y) = sobol_2d(index);
+        let scramble = utils::mix_bits(seed);
+        (
+            utils::to_unit_float(nested_uniform_scramble(x, scramble as u32)),
+            utils::to_unit_float(nested_uniform_scramble(y, (scramble >> 32) as u32)),
+        )
+    }
+}
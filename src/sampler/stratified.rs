@@ -0,0 +1,70 @@
+use super::*;
+
+// jittered grid of floor(sqrt(n))^2 strata, the samples past the grid being
+// uniform, and the strata being visited in a different order per dimension
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    pub samples_per_pixel: usize,
+    pub seed: u32,
+    side: usize,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: usize, seed: u32) -> StratifiedSampler {
+        let side = (samples_per_pixel as f32).sqrt() as usize;
+        StratifiedSampler { samples_per_pixel, seed, side: side.max(1) }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn get_2d(&self, at: SampleIndex, dimension: usize) -> (f32, f32) {
+        let h = utils::hash(at, dimension, self.seed);
+        let jitter = (utils::to_unit_float(h as u32), utils::to_unit_float((h >> 32) as u32));
+
+        let strata = self.side * self.side;
+        if at.index >= strata {
+            return jitter;
+        }
+
+        let p = utils::pixel_hash(at, dimension, self.seed) as u32;
+        let stratum = utils::permutation_element(at.index as u32, strata as u32, p) as usize;
+        let (sx, sy) = (stratum % self.side, stratum / self.side);
+        (
+            ((sx as f32 + jitter.0) / self.side as f32).min(ONE_MINUS_EPSILON),
+            ((sy as f32 + jitter.1) / self.side as f32).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+// n samples with exactly one of them in each of the n columns and rows
+#[derive(Debug, Clone)]
+pub struct LatinHypercubeSampler {
+    pub samples_per_pixel: usize,
+    pub seed: u32,
+}
+
+impl LatinHypercubeSampler {
+    pub fn new(samples_per_pixel: usize, seed: u32) -> LatinHypercubeSampler {
+        LatinHypercubeSampler { samples_per_pixel, seed }
+    }
+}
+
+impl Sampler for LatinHypercubeSampler {
+    fn get_2d(&self, at: SampleIndex, dimension: usize) -> (f32, f32) {
+        let h = utils::hash(at, dimension, self.seed);
+        let jitter = (utils::to_unit_float(h as u32), utils::to_unit_float((h >> 32) as u32));
+
+        let n = self.samples_per_pixel.max(1);
+        if at.index >= n {
+            return jitter;
+        }
+
+        let p = utils::pixel_hash(at, dimension, self.seed);
+        let column = utils::permutation_element(at.index as u32, n as u32, p as u32);
+        let row = utils::permutation_element(at.index as u32, n as u32, (p >> 32) as u32);
+        (
+            ((column as f32 + jitter.0) / n as f32).min(ONE_MINUS_EPSILON),
+            ((row as f32 + jitter.1) / n as f32).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
@@ -0,0 +1,200 @@
+use crate::math::Vector;
+
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+
+// index of refraction as a function of the wavelength
+#[derive(Debug, Clone, Copy)]
+pub enum Ior {
+    Constant(f32),
+    // n = a + b / lambda^2, lambda in micrometers
+    Cauchy { a: f32, b: f32 },
+    // n^2 = 1 + sum(b_i lambda^2 / (lambda^2 - c_i)), lambda in micrometers
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Ior {
+    pub fn bk7() -> Ior {
+        Ior::Sellmeier {
+            b: [1.039_612, 0.231_792_34, 1.010_469_5],
+            c: [0.006_000_699, 0.020_017_914, 103.560_65],
+        }
+    }
+
+    pub fn diamond() -> Ior {
+        Ior::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030_625, 0.011_236, 0.0],
+        }
+    }
+
+    pub fn at(self, wavelength: f32) -> f32 {
+        let l = wavelength / 1000.0;
+        let l2 = l * l;
+        match self {
+            Ior::Constant(n) => n,
+            Ior::Cauchy { a, b } => a + b / l2,
+            Ior::Sellmeier { b, c } => {
+                let sum: f32 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    // value at the helium d line, used outside of spectral rendering
+    pub fn nominal(self) -> f32 {
+        self.at(587.6)
+    }
+}
+
+// CIE 1931 color matching functions, multi-lobe gaussian fit of Wyman et al. 2013
+pub fn cie_xyz(wavelength: f32) -> Vector {
+    fn g(l: f32, mu: f32, sigma1: f32, sigma2: f32) -> f32 {
+        let t = (l - mu) / if l < mu { sigma1 } else { sigma2 };
+        (-0.5 * t * t).exp()
+    }
+
+    let l = wavelength;
+    let x = 1.056 * g(l, 599.8, 37.9, 31.0) + 0.362 * g(l, 442.0, 16.0, 26.7) - 0.065 * g(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * g(l, 568.8, 46.9, 40.5) + 0.286 * g(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * g(l, 437.0, 11.8, 36.0) + 0.681 * g(l, 459.0, 26.0, 13.8);
+    Vector::new(x, y, z)
+}
+
+pub fn xyz_to_linear_srgb(xyz: Vector) -> Vector {
+    Vector::new(
+        3.240_454_2 * xyz.x - 1.537_138_5 * xyz.y - 0.498_531_4 * xyz.z,
+        -0.969_266 * xyz.x + 1.876_010_8 * xyz.y + 0.041_556 * xyz.z,
+        0.055_643_4 * xyz.x - 0.204_025_9 * xyz.y + 1.057_225_2 * xyz.z,
+    )
+}
+
+fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// spectral value of an rgb reflectance or radiance, the red, green and blue
+// basis functions sum to one so white stays a flat spectrum
+pub fn uplift(rgb: Vector, wavelength: f32) -> f32 {
+    let blue = 1.0 - smoothstep((wavelength - 465.0) / 50.0);
+    let red = smoothstep((wavelength - 555.0) / 50.0);
+    let green = 1.0 - blue - red;
+    rgb.x * red + rgb.y * green + rgb.z * blue
+}
+
+// rgb attenuations are reduced to their value at the ray wavelength, when there is one
+pub fn at_wavelength(rgb: Vector, wavelength: Option<f32>) -> Vector {
+    match wavelength {
+        Some(l) => {
+            let v = uplift(rgb, l);
+            Vector::new(v, v, v)
+        },
+        None => rgb,
+    }
+}
+
+// picks one wavelength per path, and brings the spectral samples back to rgb
+#[derive(Debug, Clone)]
+pub struct WavelengthSampler {
+    white_balance: Vector,
+}
+
+impl WavelengthSampler {
+    pub fn new() -> WavelengthSampler {
+        // a flat spectrum should come back as rgb white
+        const STEPS: usize = 1000;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / STEPS as f32;
+        let mut white = Vector::zero();
+        for i in 0..STEPS {
+            let l = LAMBDA_MIN + (i as f32 + 0.5) * step;
+            white = white + xyz_to_linear_srgb(cie_xyz(l)) * step;
+        }
+
+        WavelengthSampler {
+            white_balance: Vector::new(1.0 / white.x, 1.0 / white.y, 1.0 / white.z),
+        }
+    }
+
+    // uniform over the visible range, returns the wavelength and its density
+    pub fn sample(&self, u: f32) -> (f32, f32) {
+        let wavelength = LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN);
+        (wavelength, 1.0 / (LAMBDA_MAX - LAMBDA_MIN))
+    }
+
+    pub fn xyz(&self, wavelength: f32, radiance: f32, pdf: f32) -> Vector {
+        cie_xyz(wavelength) * (radiance / pdf)
+    }
+
+    // estimate of the linear rgb color from one spectral radiance sample
+    pub fn rgb(&self, wavelength: f32, radiance: f32, pdf: f32) -> Vector {
+        xyz_to_linear_srgb(self.xyz(wavelength, radiance, pdf)) * self.white_balance
+    }
+}
+
+impl Default for WavelengthSampler {
+    fn default() -> WavelengthSampler {
+        WavelengthSampler::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32, tolerance: f32, what: &str) {
+        assert!((a - b).abs() <= tolerance, "{}: {} != {}", what, a, b);
+    }
+
+    // rgb estimate of an rgb color, through its spectrum at stratified wavelengths
+    fn round_trip(rgb: Vector) -> Vector {
+        const SAMPLES: usize = 10_000;
+        let sampler = WavelengthSampler::new();
+        let mut sum = Vector::zero();
+        for i in 0..SAMPLES {
+            let (wavelength, pdf) = sampler.sample((i as f32 + 0.5) / SAMPLES as f32);
+            sum = sum + sampler.rgb(wavelength, uplift(rgb, wavelength), pdf);
+        }
+        sum / SAMPLES as f32
+    }
+
+    #[test]
+    fn white_comes_back_white() {
+        let white = round_trip(Vector::new(1.0, 1.0, 1.0));
+        for &c in &[white.x, white.y, white.z] {
+            assert_close(c, 1.0, 1e-3, "white");
+        }
+        let grey = round_trip(Vector::new(0.25, 0.25, 0.25));
+        assert_close(grey.y, 0.25, 1e-3, "grey");
+    }
+
+    #[test]
+    fn primaries_keep_their_hue() {
+        for (index, rgb) in [Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 0.0, 1.0)].iter().enumerate() {
+            let back = round_trip(*rgb);
+            let channels = [back.x, back.y, back.z];
+            let brightest = (0..3).max_by(|&a, &b| channels[a].partial_cmp(&channels[b]).unwrap()).unwrap();
+            assert_eq!(brightest, index, "{:?} came back as {:?}", rgb, back);
+        }
+    }
+
+    #[test]
+    fn grey_is_a_flat_spectrum() {
+        for &wavelength in &[LAMBDA_MIN, 450.0, 500.0, 555.0, 600.0, LAMBDA_MAX] {
+            assert_close(uplift(Vector::new(0.6, 0.6, 0.6), wavelength), 0.6, 1e-6, "grey");
+        }
+        let rgb = Vector::new(0.1, 0.5, 0.9);
+        assert_eq!(at_wavelength(rgb, None), rgb);
+    }
+
+    #[test]
+    fn glass_disperses_blue_more_than_red() {
+        for ior in &[Ior::bk7(), Ior::diamond(), Ior::Cauchy { a: 1.5, b: 0.004 }] {
+            assert!(ior.at(400.0) > ior.nominal() && ior.nominal() > ior.at(700.0), "{:?}", ior);
+        }
+        assert_close(Ior::bk7().nominal(), 1.5168, 1e-3, "bk7");
+        assert_close(Ior::diamond().nominal(), 2.4175, 1e-3, "diamond");
+        assert_close(Ior::Cauchy { a: 1.5, b: 0.004 }.at(500.0), 1.516, 1e-5, "cauchy");
+        assert_eq!(Ior::Constant(1.33).at(400.0), Ior::Constant(1.33).at(700.0));
+    }
+}
@@ -9,6 +9,10 @@ impl ConstantTexture {
     pub fn new(color: Color) -> ConstantTexture {
         ConstantTexture { color }
     }
+
+    pub fn scalar(value: f32) -> ConstantTexture {
+        ConstantTexture::new(Color::from_floats(value, value, value))
+    }
 }
 
 impl Texture for ConstantTexture {
@@ -9,4 +9,10 @@ pub use self::perlin::*;
 
 pub trait Texture: Send + Sync {
     fn value(&self, u: f32, v: f32, point: Point) -> Color;
+
+    // scalar parameters (roughness, metallic, ...) are read as the mean of the channels
+    fn scalar_value(&self, u: f32, v: f32, point: Point) -> f32 {
+        let c = self.value(u, v, point).as_vector();
+        (c.x + c.y + c.z) / 3.0
+    }
 }
\ No newline at end of file